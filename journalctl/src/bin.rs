@@ -1,12 +1,35 @@
-use chrono::prelude::DateTime;
-use chrono::Utc;
-use clap::{Arg, App};
+use clap::{App, Arg};
+use sdjournal::boot::{Boot, BootDescriptor};
+use sdjournal::catalog::{header, Catalog, CATALOG_DIRS};
+use sdjournal::entry::Entry;
+use sdjournal::export;
+use sdjournal::fields::{parse_id128, Priority};
+use sdjournal::filter::Filter;
+use sdjournal::grep::Grep;
 use sdjournal::journal::*;
-use std::fs::File;
-use std::io::{Error, Write};
-use std::time::{Duration, UNIX_EPOCH};
+use sdjournal::json::{self, JsonMode};
+use sdjournal::multi::MultiJournal;
+use sdjournal::output::{format_bytes, OutputMode, TextFormatter};
+use sdjournal::repair::repair;
+use sdjournal::seal::{SealingKey, VerificationKey};
+use sdjournal::summary::{write_header_report, FileSummary};
+use sdjournal::time::{format_timespan, format_timestamp, parse_timespan, parse_timestamp};
+use sdjournal::unit::{self, SYSTEM_UNIT_FIELDS, USER_UNIT_FIELDS};
+use sdjournal::vacuum::*;
+use sdjournal::verify::VerifyStatus;
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::io::{self, BufReader, BufWriter, Error, ErrorKind, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-// TODO: work on entrt struct to allow for propper formatting of entries
+/// Exit status of --verify when only problems readers work around were found
+const EXIT_VERIFY_REPAIRED: i32 = 2;
+/// Exit status of --verify when entries are lost or cannot be trusted
+const EXIT_VERIFY_CORRUPT: i32 = 3;
 
 fn main() -> Result<(), Error> {
 
@@ -15,57 +38,731 @@ fn main() -> Result<(), Error> {
                           .author("Joshua A. <j.abraham1776@gmail.com>")
                           .about("Journalctl clone in rust")
                           .arg(Arg::with_name("INPUT")
-                               .help("Sets the journal file to use")
-                               .required(true)
+                               .help("Sets the journal files to use, or the message ids for --list-catalog and \
+                                      --dump-catalog")
+                               .required_unless_one(&["directory", "setup-keys", "import", "list-catalog",
+                                                      "dump-catalog"])
+                               .conflicts_with("directory")
+                               .multiple(true)
                                .index(1))
+                          .arg(Arg::with_name("directory")
+                               .short("D")
+                               .long("directory")
+                               .takes_value(true)
+                               .help("Sets the journal directory to use"))
+                          .arg(Arg::with_name("boot")
+                               .short("b")
+                               .long("boot")
+                               .takes_value(true)
+                               .value_name("ID±OFFSET")
+                               .help("Show entries of the newest boot, or of the boot with this id, offset from the \
+                                      newest (0, -1, ...), position from the oldest (1, 2, ...), or id±offset"))
+                          .arg(Arg::with_name("since")
+                               .short("S")
+                               .long("since")
+                               .takes_value(true)
+                               .allow_hyphen_values(true)
+                               .value_name("DATE")
+                               .help("Show entries not older than the specified date, e.g. \"2026-10-17 08:00:00\", \
+                                      today, yesterday or -1h"))
+                          .arg(Arg::with_name("until")
+                               .short("U")
+                               .long("until")
+                               .takes_value(true)
+                               .allow_hyphen_values(true)
+                               .value_name("DATE")
+                               .help("Show entries not newer than the specified date"))
+                          .arg(Arg::with_name("list-boots")
+                                .long("list-boots")
+                               .help("Show the boots the journal has entries of"))
+                          .arg(Arg::with_name("disk-usage")
+                                .long("disk-usage")
+                               .help("Show the disk space the journal files take up"))
+                          .arg(Arg::with_name("list-files")
+                                .long("list-files")
+                               .help("Show the state, size, entries and time span of every journal file, from \
+                                      their headers, and the totals"))
                           .arg(Arg::with_name("header")
                                 .long("header")
                                .help("Print info in the journal header"))
                           .arg(Arg::with_name("verify")
                                 .long("verify")
-                               .help("Verify journal file consistency"))
+                               .help("Verify journal file consistency. Exits with 2 if only problems readers work around \
+                                      were found and 3 if the file is corrupt"))
+                          .arg(Arg::with_name("verify-key")
+                                .long("verify-key")
+                                .takes_value(true)
+                                .requires("verify")
+                               .help("Verify the Forward Secure Sealing of the journal with this key"))
+                          .arg(Arg::with_name("repair")
+                                .long("repair")
+                                .takes_value(true)
+                                .value_name("OUT")
+                               .help("Copy every intact entry of a damaged journal into a new journal file OUT"))
+                          .arg(Arg::with_name("import")
+                                .long("import")
+                                .takes_value(true)
+                                .value_name("OUT")
+                               .help("Write the entries of a Journal Export Format stream read from INPUT, or stdin \
+                                      if there is none, into a new journal file OUT"))
+                          .arg(Arg::with_name("catalog")
+                               .short("x")
+                               .long("catalog")
+                               .help("Add explanations from the message catalog to entries shown as text"))
+                          .arg(Arg::with_name("list-catalog")
+                                .long("list-catalog")
+                               .help("Show the message ids the catalog has texts for, or the ones given as INPUT, \
+                                      with who defined them and their subject"))
+                          .arg(Arg::with_name("dump-catalog")
+                                .long("dump-catalog")
+                               .conflicts_with("list-catalog")
+                               .help("Show the texts of the message catalog, or for the message ids given as INPUT"))
+                          .arg(Arg::with_name("catalog-directory")
+                               .long("catalog-directory")
+                               .takes_value(true)
+                               .multiple(true)
+                               .number_of_values(1)
+                               .value_name("DIR")
+                               .help("Read the message catalog from the .catalog files in this directory instead of \
+                                      the ones of systemd"))
+                          .arg(Arg::with_name("setup-keys")
+                                .long("setup-keys")
+                               .help("Generate a new sealing and verification key pair"))
+                          .arg(Arg::with_name("interval")
+                                .long("interval")
+                                .takes_value(true)
+                                .requires("setup-keys")
+                               .help("Time span after which the sealing key changes [default: 15min]"))
+                          .arg(Arg::with_name("force")
+                                .long("force")
+                                .requires("setup-keys")
+                               .help("Overwrite an existing sealing key"))
+                          .arg(Arg::with_name("vacuum-size")
+                               .long("vacuum-size")
+                               .takes_value(true)
+                               .requires("directory")
+                               .help("Remove archived journal files until the directory uses at most this much disk space"))
+                          .arg(Arg::with_name("vacuum-time")
+                               .long("vacuum-time")
+                               .takes_value(true)
+                               .requires("directory")
+                               .help("Remove archived journal files with no entries newer than this time span"))
+                          .arg(Arg::with_name("vacuum-files")
+                               .long("vacuum-files")
+                               .takes_value(true)
+                               .requires("directory")
+                               .help("Remove archived journal files until at most this many journal files are left"))
+                          .arg(Arg::with_name("dry-run")
+                               .long("dry-run")
+                               .help("List the files vacuuming would remove without removing them"))
+                          .arg(Arg::with_name("output")
+                               .short("o")
+                               .long("output")
+                               .takes_value(true)
+                               .possible_values(&["short", "short-full", "short-iso", "short-iso-precise", "short-precise",
+                                                 "short-monotonic", "short-unix", "short-delta", "verbose", "cat",
+                                                 "with-unit", "export", "json", "json-pretty", "json-sse", "json-seq"])
+                               .default_value("short")
+                               .help("Change the journal output mode"))
+                          .arg(Arg::with_name("priority")
+                               .short("p")
+                               .long("priority")
+                               .takes_value(true)
+                               .help("Show entries with the given priority or a more severe one, or with a priority \
+                                      in a range like err..alert, by name or number"))
+                          .arg(Arg::with_name("unit")
+                               .short("u")
+                               .long("unit")
+                               .takes_value(true)
+                               .multiple(true)
+                               .number_of_values(1)
+                               .value_name("UNIT")
+                               .help("Show entries about the specified systemd unit or units matching a glob"))
+                          .arg(Arg::with_name("user-unit")
+                               .long("user-unit")
+                               .takes_value(true)
+                               .multiple(true)
+                               .number_of_values(1)
+                               .value_name("UNIT")
+                               .help("Show entries about the specified user unit or user units matching a glob"))
+                          .arg(Arg::with_name("grep")
+                               .short("g")
+                               .long("grep")
+                               .takes_value(true)
+                               .allow_hyphen_values(true)
+                               .value_name("PATTERN")
+                               .help("Show entries with a MESSAGE matching the regular expression"))
+                          .arg(Arg::with_name("case-sensitive")
+                               .long("case-sensitive")
+                               .takes_value(true)
+                               .min_values(0)
+                               .require_equals(true)
+                               .value_name("BOOL")
+                               .requires("grep")
+                               .help("Match the --grep pattern case-sensitively, or insensitively with =false [default: \
+                                      only if the pattern has uppercase letters]"))
+                          .arg(Arg::with_name("lines")
+                               .short("n")
+                               .long("lines")
+                               .takes_value(true)
+                               .value_name("INTEGER")
+                               .help("Show the newest entries, 10 or this many or all, walking back from the end of \
+                                      the journal. With --since, show the oldest entries from then on instead"))
+                          .arg(Arg::with_name("pager-end")
+                               .short("e")
+                               .long("pager-end")
+                               .help("Jump to the end of the journal: show the newest 1000 entries of the newest boot \
+                                      unless -n or -b say otherwise"))
+                          .arg(Arg::with_name("all")
+                               .short("a")
+                               .long("all")
+                               .help("Show all fields in full, even if they are long"))
+                          .arg(Arg::with_name("utc")
+                               .long("utc")
+                               .help("Express time in Coordinated Universal Time (UTC)"))
                           .arg(Arg::with_name("v")
                                .short("v")
                                .multiple(true)
                                .help("Sets the level of verbosity"))
-                          .get_matches();
+                          .get_matches_from(optional_value_args(env::args_os()));
+
+    if matches.is_present("setup-keys") {
+        return setup_keys(&matches);
+    }
+
+    if matches.is_present("vacuum-size") || matches.is_present("vacuum-time") || matches.is_present("vacuum-files") {
+        let directory = matches.value_of("directory").expect("directory missing");
+        let limits = vacuum_limits(&matches)?;
+        let dry_run = matches.is_present("dry-run");
+        let removed = if dry_run {
+            vacuum_plan(directory, &limits)?
+        } else {
+            vacuum(directory, &limits)?
+        };
+
+        let mut freed = 0;
+        for file in removed.iter() {
+            let verb = if dry_run { "Would delete" } else { "Deleted" };
+            println!("{} archived journal {} ({}).", verb, file.path.display(), format_bytes(file.disk_usage));
+            freed += file.disk_usage;
+        }
+        let verb = if dry_run { "would free" } else { "freed" };
+        println!("Vacuuming done, {} {} of archived journals from {}.", verb, format_bytes(freed), directory);
+        return Ok(());
+    }
+
+    if let Some(out) = matches.value_of("import") {
+        let out = OpenOptions::new().read(true).write(true).create_new(true).open(out)?;
+        let n_entries = match matches.value_of("INPUT") {
+            Some(input) => export::import(BufReader::new(File::open(input)?), out)?,
+            None => export::import(io::stdin().lock(), out)?,
+        };
+        println!("Imported {} entries.", n_entries);
+        return Ok(());
+    }
+
+    if matches.is_present("list-catalog") || matches.is_present("dump-catalog") {
+        return list_catalog(&matches, &open_catalog(&matches)?);
+    }
+
+    let inputs: Vec<&str> = matches.values_of("INPUT").map(|v| v.collect()).unwrap_or_default();
+    if matches.is_present("header") || matches.is_present("verify") || matches.is_present("repair") {
+        let input = match inputs.as_slice() {
+            [input] => input,
+            _ => return Err(Error::new(ErrorKind::InvalidInput, "--header, --verify and --repair take a single journal file")),
+        };
+        let journal = Journal::new(File::open(input)?)?;
+        return inspect_file(&matches, Path::new(input), &journal);
+    }
+
+    let journals = match matches.value_of("directory") {
+        Some(directory) => MultiJournal::open_directory(directory)?,
+        None => MultiJournal::open(&inputs)?,
+    };
+    if journals.journals.is_empty() {
+        eprintln!("No journal files were found.");
+    }
+
+    if matches.is_present("list-boots") {
+        return list_boots(&journals.boots()?);
+    }
+    if matches.is_present("disk-usage") {
+        println!("Archived and active journals take up {} in the file system.", format_bytes(journals.disk_usage()?));
+        return Ok(());
+    }
+    if matches.is_present("list-files") {
+        return list_files(&journals.summaries()?, matches.is_present("utc"));
+    }
+
+    let output = matches.value_of("output").expect("output missing");
+    let json_mode = match output {
+        "json" => Some(JsonMode::Json),
+        "json-pretty" => Some(JsonMode::Pretty),
+        "json-sse" => Some(JsonMode::Sse),
+        "json-seq" => Some(JsonMode::Seq),
+        _ => None,
+    };
+    let mut formatter = if output == "export" || json_mode.is_some() {
+        None
+    } else {
+        let mut formatter = TextFormatter::new(output.parse()?);
+        formatter.utc = matches.is_present("utc");
+        formatter.show_all = matches.is_present("all");
+        if matches.is_present("catalog") {
+            formatter.set_catalog(open_catalog(&matches)?);
+        }
+        Some(formatter)
+    };
+
+    let mut filter = Filter::new();
+    if let Some(priority) = matches.value_of("priority") {
+        filter.add_priorities(Priority::parse_range(priority)?);
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_micros() as u64).unwrap_or(0);
+    let timestamp = |name: &str| -> Result<Option<u64>, Error> {
+        match matches.value_of(name) {
+            Some(value) => parse_timestamp(value, now).map(Some).ok_or_else(|| {
+                Error::new(ErrorKind::InvalidInput, format!("Failed to parse timestamp: {}", value))
+            }),
+            None => Ok(None),
+        }
+    };
+    let (since, until) = (timestamp("since")?, timestamp("until")?);
+    if let (Some(since), Some(until)) = (since, until) {
+        if since > until {
+            return Err(Error::new(ErrorKind::InvalidInput, "--since= must be before --until="));
+        }
+    }
+    if let Some(since) = since {
+        filter.set_since(since);
+    }
+    if let Some(until) = until {
+        filter.set_until(until);
+    }
+    if matches.is_present("unit") || matches.is_present("user-unit") {
+        filter.add_any(unit_alternatives(&matches, &journals)?);
+    }
+    if matches.is_present("boot") || matches.is_present("pager-end") {
+        let descriptor: BootDescriptor = matches.value_of("boot").unwrap_or_default().parse()?;
+        let boots = journals.boots()?;
+        let boot = descriptor.resolve(&boots).ok_or_else(|| {
+            Error::new(ErrorKind::NotFound, format!("Data from the specified boot ({}) is not available", descriptor))
+        })?;
+        filter.add_boot(boot.boot_id);
+    }
+    let grep = match matches.value_of("grep") {
+        Some(pattern) => Some(Grep::new(pattern, case_sensitive(&matches)?)?),
+        None => None,
+    };
+    let lines = match matches.value_of("lines") {
+        Some(lines) => parse_lines(lines)?,
+        None if matches.is_present("pager-end") => Some(1000),
+        None => None,
+    };
+    // Like journalctl, skip entries that cannot be read instead of stopping
+    let readable = |ent: Result<Entry, Error>| match ent {
+        Ok(ent) => Some(ent),
+        Err(e) => {
+            eprintln!("Skipping entry that cannot be read: {}", e);
+            None
+        }
+    };
+    let grepped = |ent: &Entry| grep.as_ref().is_none_or(|grep| grep.matches(ent));
+    let entries: Box<dyn Iterator<Item = Entry>> = match lines {
+        None => Box::new(journals.entries_matching(&filter)?.filter_map(readable).filter(grepped)),
+        // Like journalctl, start from --since rather than from the end
+        Some(n) if since.is_some() => {
+            Box::new(journals.entries_matching(&filter)?.filter_map(readable).filter(grepped).take(n))
+        }
+        Some(n) => {
+            let mut last: Vec<_> = journals.entries_matching_rev(&filter)?.filter_map(readable).filter(grepped).take(n).collect();
+            last.reverse();
+            Box::new(last.into_iter())
+        }
+    };
+
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    // Like journalctl, only the text modes other than cat mark boots and say
+    // when nothing matched
+    let text = formatter.as_ref().is_some_and(|f| f.mode != OutputMode::Cat);
+    let mut n_shown = 0;
+    let mut last_boot = None;
+    for ent in entries {
+        n_shown += 1;
+        let written = match (&mut formatter, json_mode) {
+            (Some(formatter), _) if text && last_boot.is_some_and(|b| b != ent.boot_id) => {
+                writeln!(out, "-- Boot {:032x} --", ent.boot_id).and_then(|()| formatter.write_entry(&mut out, &ent))
+            }
+            (Some(formatter), _) => formatter.write_entry(&mut out, &ent),
+            (None, Some(mode)) => json::write_entry(&mut out, &ent, mode, matches.is_present("all")),
+            (None, None) => export::write_entry(&mut out, &ent),
+        };
+        match written {
+            Ok(()) => {}
+            // Stop quietly when the reader went away, e.g. `| head`
+            Err(ref e) if e.kind() == ErrorKind::BrokenPipe => return Ok(()),
+            Err(e) => return Err(e),
+        }
+        last_boot = Some(ent.boot_id);
+    }
+    if n_shown == 0 && text {
+        let written = writeln!(out, "-- No entries --");
+        if let Err(e) = written {
+            if e.kind() != ErrorKind::BrokenPipe {
+                return Err(e);
+            }
+        }
+    }
+    match out.flush() {
+        Err(ref e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
 
-    let mut file = File::open(matches.value_of("INPUT").expect("input missing"))?;
-    let journal = Journal::new(file)?;
+/// The options whose value is required and so may be given as the next
+/// argument, even one that looks like an option
+const VALUE_OPTIONS: &[&str] = &[
+    "-D", "--directory", "-S", "--since", "-U", "--until", "--verify-key", "--repair",
+    "--import", "--catalog-directory", "--interval", "--vacuum-size", "--vacuum-time",
+    "--vacuum-files", "-o", "--output", "-p", "--priority", "-u", "--unit", "--user-unit",
+    "-g", "--grep",
+];
 
+/// Gives every `-b` and `-n` an explicit value, as `--boot=VALUE` and
+/// `--lines=VALUE`. Their values are optional, which clap cannot tell apart
+/// from the journal files that may follow, so like journalctl the next
+/// argument is only taken as the value if it is valid for the option.
+/// Values of the other options and everything after `--` are left alone.
+fn optional_value_args<I: Iterator<Item = OsString>>(args: I) -> Vec<OsString> {
+    let mut result = Vec::new();
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        let (long, valid, default): (&str, fn(&str) -> bool, &str) = match arg.to_str() {
+            Some("-b" | "--boot") => ("--boot", |v| v.parse::<BootDescriptor>().is_ok(), ""),
+            Some("-n" | "--lines") => ("--lines", |v| parse_lines(v).is_ok(), "10"),
+            Some("--") => {
+                result.push(arg);
+                result.extend(args);
+                break;
+            }
+            Some(option) if VALUE_OPTIONS.contains(&option) => {
+                result.push(arg);
+                result.extend(args.next());
+                continue;
+            }
+            _ => {
+                result.push(arg);
+                continue;
+            }
+        };
+        let value = match args.peek().and_then(|v| v.to_str()) {
+            Some(v) if !v.is_empty() && valid(v) => v.to_owned(),
+            _ => String::new(),
+        };
+        if !value.is_empty() {
+            args.next();
+        }
+        let value = if value.is_empty() { default } else { &value };
+        result.push(OsString::from(format!("{}={}", long, value)));
+    }
+    result
+}
+
+/// Parses the number of entries -n should show, or `None` for `all`
+fn parse_lines(s: &str) -> Result<Option<usize>, Error> {
+    if s == "all" {
+        return Ok(None);
+    }
+    // Like in journalctl, a plus sign changes nothing
+    let digits = s.strip_prefix('+').unwrap_or(s);
+    match digits.parse() {
+        Ok(n) if !digits.starts_with('+') => Ok(Some(n)),
+        _ => Err(Error::new(ErrorKind::InvalidInput, format!("Failed to parse lines '{}'", s))),
+    }
+}
+
+/// Whether --case-sensitive asks for case-sensitive matching, or `None` to
+/// decide by the pattern. Its value is a boolean like in systemd, and no
+/// value means true.
+fn case_sensitive(matches: &clap::ArgMatches) -> Result<Option<bool>, Error> {
+    if !matches.is_present("case-sensitive") {
+        return Ok(None);
+    }
+    match matches.value_of("case-sensitive") {
+        None | Some("1" | "yes" | "y" | "true" | "t" | "on") => Ok(Some(true)),
+        Some("0" | "no" | "n" | "false" | "f" | "off") => Ok(Some(false)),
+        Some(value) => Err(Error::new(ErrorKind::InvalidInput, format!("Bad --case-sensitive= argument \"{}\"", value))),
+    }
+}
+
+/// The filters selecting the entries about the units of -u and --user-unit,
+/// with globs expanded to the units the journal has entries of
+fn unit_alternatives(matches: &clap::ArgMatches, journals: &MultiJournal) -> Result<Vec<Filter>, Error> {
+    // Safety: getuid cannot fail
+    let uid = unsafe { libc::getuid() };
+    let mut alternatives = Vec::new();
+    let mut n_units = 0;
+    for (arg, fields) in [("unit", SYSTEM_UNIT_FIELDS), ("user-unit", USER_UNIT_FIELDS)] {
+        let names: Vec<String> = matches.values_of(arg).map(|v| v.map(unit::mangle_unit_name).collect()).unwrap_or_default();
+        let (patterns, mut units): (Vec<String>, Vec<String>) = names.into_iter().partition(|n| unit::is_glob(n));
+        if !patterns.is_empty() {
+            units.extend(unit::expand_unit_globs(journals, &fields, &patterns)?);
+        }
+        for name in units.iter() {
+            alternatives.extend(match arg {
+                "unit" => unit::unit_matches(name),
+                _ => unit::user_unit_matches(name, uid),
+            });
+        }
+        n_units += units.len();
+    }
+    // Like journalctl, refuse rather than show everything when no unit matched
+    if n_units == 0 {
+        return Err(Error::new(ErrorKind::NotFound, "Failed to add filter for units: No data available"));
+    }
+    Ok(alternatives)
+}
+
+/// The message catalog, from --catalog-directory or else the catalog
+/// directories of systemd
+fn open_catalog(matches: &clap::ArgMatches) -> Result<Catalog, Error> {
+    match matches.values_of("catalog-directory") {
+        Some(dirs) => Catalog::open(&dirs.collect::<Vec<_>>()),
+        None => Catalog::open(&CATALOG_DIRS),
+    }
+}
+
+/// Runs --list-catalog or --dump-catalog on every message id of the catalog,
+/// or on the ones given as INPUT
+fn list_catalog(matches: &clap::ArgMatches, catalog: &Catalog) -> Result<(), Error> {
+    let show = |id: u128, text: &str| {
+        if matches.is_present("list-catalog") {
+            let (defined_by, subject) = (header(text, "Defined-By"), header(text, "Subject"));
+            println!("{:032x} {}: {}", id, defined_by.unwrap_or("n/a"), subject.unwrap_or("n/a"));
+        } else {
+            println!("-- {:032x}\n{}", id, text);
+        }
+    };
+    let inputs = match matches.values_of("INPUT") {
+        Some(inputs) => inputs,
+        None => {
+            for id in catalog.ids() {
+                show(id, catalog.get(id).expect("catalog id without text"));
+            }
+            return Ok(());
+        }
+    };
+
+    // Like journalctl, go on with the other ids if one cannot be shown
+    let mut failed = false;
+    for input in inputs {
+        match parse_id128(input).map(|id| (id, catalog.get(id))) {
+            Some((id, Some(text))) => show(id, text),
+            Some((_, None)) => {
+                eprintln!("Failed to retrieve catalog entry for '{}': No such file or directory", input);
+                failed = true;
+            }
+            None => {
+                eprintln!("Failed to parse id128 '{}': Invalid argument", input);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        return Err(Error::new(ErrorKind::InvalidInput, "Failed to list catalog"));
+    }
+    Ok(())
+}
+
+/// Runs --header, --verify or --repair on a single journal file
+fn inspect_file(matches: &clap::ArgMatches, path: &Path, journal: &Journal<File>) -> Result<(), Error> {
     if matches.is_present("header") {
-        println!("{}", journal.header);
+        return write_header_report(&mut io::stdout().lock(), path, journal);
+    }
+
+    if let Some(out) = matches.value_of("repair") {
+        let out = OpenOptions::new().read(true).write(true).create_new(true).open(out)?;
+        let report = repair(journal, out)?;
+        for (start, end) in report.skipped.iter() {
+            println!("Skipped {} damaged bytes at {:#x}.", end - start, start);
+        }
+        println!("Recovered {} entries, lost {} entries.", report.recovered, report.lost());
         return Ok(());
     }
 
     if matches.is_present("verify") {
-        println!("Journal verification: {}", journal.verify());
-        return Ok(());
+        let report = journal.verify();
+        for problem in report.problems.iter() {
+            println!("{}", problem);
+        }
+        println!("Checked {} objects: {} entry, {} data, {} field, {} entry array, {} hash table, {} tag",
+                 report.n_objects, report.n_entries, report.n_data, report.n_fields,
+                 report.n_entry_arrays, report.n_hash_tables, report.n_tags);
+        let mut status = report.status();
+        let verdict = match status {
+            VerifyStatus::Clean => "PASS",
+            VerifyStatus::Repaired => "PASS (with warnings)",
+            VerifyStatus::Corrupt => "FAIL",
+        };
+        println!("Journal verification: {}", verdict);
+
+        if let Some(key) = matches.value_of("verify-key") {
+            let key: VerificationKey = key.parse()?;
+            match journal.verify_seal(&key) {
+                Ok(report) => {
+                    println!("Seal verification: true");
+                    let format = |usec| format_timestamp(usec, "%a %Y-%m-%d %H:%M:%S %Z", matches.is_present("utc"));
+                    println!("=> Validated from {} to {}, final {} entries not sealed.",
+                             format(report.first_contained),
+                             format(report.last_validated),
+                             format_timespan(report.last_contained.saturating_sub(report.last_validated), 0));
+                }
+                Err(e) => {
+                    println!("Seal verification: false ({})", e);
+                    status = VerifyStatus::Corrupt;
+                }
+            }
+        }
+        match status {
+            VerifyStatus::Clean => return Ok(()),
+            VerifyStatus::Repaired => process::exit(EXIT_VERIFY_REPAIRED),
+            VerifyStatus::Corrupt => process::exit(EXIT_VERIFY_CORRUPT),
+        }
     }
+    Ok(())
+}
 
-    // Iterate over all entry objects
-    for ent in journal.iter_entries() {
-        let d = UNIX_EPOCH + Duration::from_micros(ent.realtime);
-        let datetime = DateTime::<Utc>::from(d);
-        // Formats the combined date and time with the specified format string.
-        print!("{} ", datetime.format("%b %d %H:%M:%S"));
+/// Prints the boots in the table layout of `journalctl --list-boots`
+fn list_boots(boots: &[Boot]) -> Result<(), Error> {
+    if boots.is_empty() {
+        return Err(Error::new(ErrorKind::NotFound, "Failed to determine boots: No data available"));
+    }
+    let format = |usec| format_timestamp(usec, "%a %Y-%m-%d %H:%M:%S %Z", false);
+    let rows: Vec<[String; 4]> = boots.iter()
+        .map(|b| [b.index.to_string(), format!("{:032x}", b.boot_id), format(b.first_realtime), format(b.last_realtime)])
+        .collect();
+    let header = ["IDX", "BOOT ID", "FIRST ENTRY", "LAST ENTRY"];
+    let widths: Vec<usize> = (0..3)
+        .map(|i| rows.iter().map(|r| r[i].len()).chain(Some(header[i].len())).max().unwrap_or(0))
+        .collect();
 
-        for obj in ent.items {
-            let data = match obj.item {
-                Object::Data(d) => d,
-                _ => continue,
-            };
+    println!("{:>w0$} {:w1$} {:w2$} {}", header[0], header[1], header[2], header[3],
+             w0 = widths[0], w1 = widths[1], w2 = widths[2]);
+    for row in rows.iter() {
+        println!("{:>w0$} {:w1$} {:w2$} {}", row[0], row[1], row[2], row[3],
+                 w0 = widths[0], w1 = widths[1], w2 = widths[2]);
+    }
+    Ok(())
+}
 
-            if data.payload.starts_with(b"_HOSTNAME=") {
-                std::io::stdout().write_all(&data.payload[10..])?;
-            } else if data.payload.starts_with(b"SYSLOG_IDENTIFIER") {
-                std::io::stdout().write_all(&data.payload[18..])?;
-            } else if data.payload.starts_with(b"MESSAGE") {
-                std::io::stdout().write_all(&data.payload[7..])?;
-                std::io::stdout().write_all(b"\n")?;
+/// Prints a table of `summaries` and a line with their totals
+fn list_files(summaries: &[FileSummary], utc: bool) -> Result<(), Error> {
+    let format = |usec| format_timestamp(usec, "%a %Y-%m-%d %H:%M:%S %Z", utc);
+    let rows: Vec<[String; 8]> = summaries.iter()
+        .map(|s| {
+            let (seqnums, first, last) = match s.n_entries {
+                0 => ("-".to_owned(), "-".to_owned(), "-".to_owned()),
+                _ => (format!("{}-{}", s.head_seqnum, s.tail_seqnum), format(s.head_realtime), format(s.tail_realtime)),
+            };
+            [s.state.to_string(), format_bytes(s.disk_usage), format_bytes(s.arena_size),
+             s.arena_used.map_or_else(|| "-".to_owned(), format_bytes),
+             s.n_entries.to_string(), seqnums, first, last]
+        })
+        .collect();
+    let header = ["STATE", "DISK", "ARENA", "USED", "ENTRIES", "SEQNUMS", "FIRST ENTRY", "LAST ENTRY"];
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| rows.iter().map(|r| r[i].len()).chain(Some(header[i].len())).max().unwrap_or(0))
+        .collect();
+    // Text columns are aligned left and sizes and counts right
+    let line = |cells: &[&str], path: &str| {
+        let mut line = String::new();
+        for (i, cell) in cells.iter().enumerate() {
+            match i {
+                1..=4 => line.push_str(&format!("{:>w$} ", cell, w = widths[i])),
+                _ => line.push_str(&format!("{:w$} ", cell, w = widths[i])),
             }
         }
+        line + path
+    };
+
+    println!("{}", line(&header, "FILE"));
+    for (row, summary) in rows.iter().zip(summaries) {
+        let cells: Vec<&str> = row.iter().map(|c| c.as_str()).collect();
+        println!("{}", line(&cells, &summary.path.display().to_string()));
     }
+    let sum = |f: fn(&FileSummary) -> u64| summaries.iter().map(f).sum::<u64>();
+    println!("{} files take up {} on disk with {} of objects and {} entries.", summaries.len(),
+             format_bytes(sum(|s| s.disk_usage)), format_bytes(sum(|s| s.arena_used.unwrap_or(0))), sum(|s| s.n_entries));
+    Ok(())
+}
+
+/// Writes a new sealing key to `fss` in the journal directory of this
+/// machine, or the one given with `-D`, and prints the verification key
+fn setup_keys(matches: &clap::ArgMatches) -> Result<(), Error> {
+    let invalid = |what: &str| Error::new(ErrorKind::InvalidInput, format!("Invalid {}", what));
+    let interval = parse_timespan(matches.value_of("interval").unwrap_or("15min"))
+        .filter(|&i| i > 0)
+        .ok_or_else(|| invalid("--interval"))?;
+    let boot_id = read_id("/proc/sys/kernel/random/boot_id")?;
 
+    // The machine id is only required to find the default directory. With -D
+    // it is still recorded in the key when known, as journald checks it.
+    let (directory, machine_id) = match matches.value_of("directory") {
+        Some(d) => (PathBuf::from(d), read_id("/etc/machine-id").unwrap_or(0)),
+        None => {
+            let machine_id = read_id("/etc/machine-id")?;
+            (PathBuf::from(format!("/var/log/journal/{:032x}", machine_id)), machine_id)
+        }
+    };
+    let path = directory.join("fss");
+    if path.exists() && !matches.is_present("force") {
+        return Err(Error::new(ErrorKind::AlreadyExists,
+                              format!("Sealing key file {} exists already. Use --force to recreate.", path.display())));
+    }
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_err(|_| invalid("system time"))?;
+    let (sealing, verification) = SealingKey::generate(machine_id, boot_id, interval, now.as_micros() as u64)?;
+
+    // Write to a temporary file first so an existing key is never left half written
+    // and the secret is never readable by others
+    let tmp_path = directory.join("fss.tmp");
+    let tmp = OpenOptions::new().write(true).create_new(true).mode(0o600).open(&tmp_path)?;
+    if let Err(e) = sealing.write(tmp).and_then(|()| fs::rename(&tmp_path, &path)) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    println!("The new sealing key has been written to {}.", path.display());
+    println!("Please write down this secret verification key. It should be stored");
+    println!("at a safe location and should not be saved locally on disk.");
+    println!();
+    println!("{}", verification);
+    println!();
+    println!("The sealing key is automatically changed every {}.", format_timespan(interval, 0));
     Ok(())
 }
+
+/// Reads a 128-bit id such as the machine id, with or without dashes
+fn read_id(path: &str) -> Result<u128, Error> {
+    parse_id128(fs::read_to_string(path)?.trim())
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("Invalid id in {}", path)))
+}
+
+fn vacuum_limits(matches: &clap::ArgMatches) -> Result<VacuumLimits, Error> {
+    let invalid = |what: &str| Error::new(ErrorKind::InvalidInput, format!("Invalid {}", what));
+    let mut limits = VacuumLimits::default();
+
+    if let Some(size) = matches.value_of("vacuum-size") {
+        limits.max_disk_usage = Some(parse_size(size).ok_or_else(|| invalid("--vacuum-size"))?);
+    }
+    if let Some(files) = matches.value_of("vacuum-files") {
+        limits.max_files = Some(files.parse().map_err(|_| invalid("--vacuum-files"))?);
+    }
+    if let Some(time) = matches.value_of("vacuum-time") {
+        let span = parse_timespan(time).ok_or_else(|| invalid("--vacuum-time"))?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_err(|_| invalid("system time"))?;
+        limits.min_realtime = Some((now.as_micros() as u64).saturating_sub(span));
+    }
+    Ok(limits)
+}
@@ -0,0 +1,188 @@
+//! The boots a journal has entries of, like `journalctl --list-boots`.
+//! Boots are found through the distinct values of `_BOOT_ID`, and the first
+//! and last entry of each boot through the entry list of its DATA object,
+//! so only two entries per boot are read. Files without `_BOOT_ID` fields
+//! fall back to the boot ids in the headers of all their entries.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::io::{Error, ErrorKind, Read, Result, Seek};
+use std::str::{self, FromStr};
+
+use crate::entry::EntryPosition;
+use crate::fields::{parse_id128, BOOT_ID};
+use crate::iter::EntryArrayChain;
+use crate::journal::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Boot {
+    /// Position relative to the most recent boot, which is 0, with earlier
+    /// boots counting down from -1
+    pub index: i64,
+    pub boot_id: u128,
+    /// Realtime timestamp of the first entry of the boot
+    pub first_realtime: u64,
+    /// Realtime timestamp of the last entry of the boot
+    pub last_realtime: u64,
+}
+
+/// Selects a boot like the argument of `journalctl -b`: a boot id, an
+/// offset, or both as `<boot id>±<offset>`. Without a boot id, offsets of 1
+/// and more count from the oldest boot, which is 1, and offsets of 0 and
+/// less from the newest, which is 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BootDescriptor {
+    pub boot_id: Option<u128>,
+    pub offset: i64,
+}
+
+impl BootDescriptor {
+    /// Finds the selected boot among `boots`, which are ordered oldest first
+    /// as `Journal::boots` returns them
+    pub fn resolve<'b>(&self, boots: &'b [Boot]) -> Option<&'b Boot> {
+        let position = match self.boot_id {
+            Some(id) => boots.iter().position(|b| b.boot_id == id)? as i64 + self.offset,
+            None if self.offset > 0 => self.offset - 1,
+            None => boots.len() as i64 - 1 + self.offset,
+        };
+        if position < 0 {
+            return None;
+        }
+        boots.get(position as usize)
+    }
+}
+
+impl FromStr for BootDescriptor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<BootDescriptor> {
+        let invalid = || Error::new(ErrorKind::InvalidInput, format!("Invalid boot descriptor: {}", s));
+        let boot_id = s.get(..32).and_then(parse_id128);
+        let offset = match boot_id {
+            // An offset from a boot id needs its sign
+            Some(_) if s.len() == 32 => 0,
+            Some(_) if s[32..].starts_with(&['+', '-'][..]) => s[32..].parse().map_err(|_| invalid())?,
+            Some(_) => return Err(invalid()),
+            None if s.is_empty() => 0,
+            None => s.parse().map_err(|_| invalid())?,
+        };
+        Ok(BootDescriptor { boot_id, offset })
+    }
+}
+
+impl fmt::Display for BootDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.boot_id {
+            Some(id) if self.offset == 0 => write!(f, "{:032x}", id),
+            Some(id) => write!(f, "{:032x}{:+}", id, self.offset),
+            None => write!(f, "{:+}", self.offset),
+        }
+    }
+}
+
+/// The first and last entry of a boot in one file
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BootSpan {
+    pub boot_id: u128,
+    pub first: EntryPosition,
+    pub last: EntryPosition,
+}
+
+impl BootSpan {
+    /// The span from `a` to `b`, whichever `EntryPosition::compare` puts first
+    fn new(boot_id: u128, a: EntryPosition, b: EntryPosition) -> BootSpan {
+        match b.compare(&a) {
+            Ordering::Less => BootSpan { boot_id, first: b, last: a },
+            _ => BootSpan { boot_id, first: a, last: b },
+        }
+    }
+}
+
+/// Reads the position of the entry at `offset` from its header alone
+fn read_position<'a, T>(journal: &'a Journal<T>, offset: ObjectOffset) -> Result<EntryPosition>
+where
+    T: 'a + Read + Seek,
+    &'a T: Read + Seek,
+{
+    let (entry, _) = get_entry_at_offset(&mut &journal.file, offset)?;
+    Ok(EntryPosition::new(&journal.header, &entry))
+}
+
+/// Adds `span` to `spans`, widening the span of the same boot if there is one
+fn merge_span(spans: &mut Vec<BootSpan>, span: BootSpan) {
+    match spans.iter_mut().find(|m| m.boot_id == span.boot_id) {
+        Some(m) => {
+            if span.first.compare(&m.first) == Ordering::Less {
+                m.first = span.first;
+            }
+            if span.last.compare(&m.last) == Ordering::Greater {
+                m.last = span.last;
+            }
+        }
+        None => spans.push(span),
+    }
+}
+
+/// The span of every boot with entries in `journal`, in no particular order
+pub(crate) fn boot_spans<'a, T>(journal: &'a Journal<T>) -> Result<Vec<BootSpan>>
+where
+    T: 'a + Read + Seek,
+    &'a T: Read + Seek,
+{
+    let mut spans = Vec::new();
+    let values = journal.unique_values(BOOT_ID)?;
+    if values.is_empty() {
+        return header_boot_spans(journal);
+    }
+    for data in values {
+        let boot_id = data.payload.get(BOOT_ID.len() + 1..)
+            .and_then(|id| str::from_utf8(id).ok())
+            .and_then(parse_id128);
+        let boot_id = match boot_id {
+            Some(id) => id,
+            None => continue,
+        };
+        let offsets = journal.data_entry_offsets(&data)?;
+        if let (Some(&first), Some(&last)) = (offsets.first(), offsets.last()) {
+            spans.push(BootSpan::new(boot_id, read_position(journal, first)?, read_position(journal, last)?));
+        }
+    }
+    Ok(spans)
+}
+
+/// The span of every boot in `journal` from the boot ids of the entry
+/// headers, for files whose entries have no `_BOOT_ID` field
+fn header_boot_spans<'a, T>(journal: &'a Journal<T>) -> Result<Vec<BootSpan>>
+where
+    T: 'a + Read + Seek,
+    &'a T: Read + Seek,
+{
+    let chain = EntryArrayChain::new(&journal.file, journal.header.entry_array_offset, journal.header.n_entries)?;
+    let mut spans = Vec::new();
+    for i in 0..chain.len() {
+        let position = read_position(journal, chain.get(i)?)?;
+        merge_span(&mut spans, BootSpan::new(position.boot_id, position, position));
+    }
+    Ok(spans)
+}
+
+/// Joins the spans of the same boot, possibly from several files, and
+/// numbers the boots in the order of their first entries
+pub(crate) fn list_boots(spans: Vec<BootSpan>) -> Vec<Boot> {
+    let mut merged: Vec<BootSpan> = Vec::new();
+    for span in spans {
+        merge_span(&mut merged, span);
+    }
+    merged.sort_by(|a, b| a.first.compare(&b.first));
+
+    let newest = merged.len() as i64 - 1;
+    merged.into_iter()
+        .enumerate()
+        .map(|(i, span)| Boot {
+            index: i as i64 - newest,
+            boot_id: span.boot_id,
+            first_realtime: span.first.realtime,
+            last_realtime: span.last.realtime,
+        })
+        .collect()
+}
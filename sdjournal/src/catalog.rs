@@ -0,0 +1,295 @@
+//! The message catalog, which explains entries by their `MESSAGE_ID` like
+//! `journalctl -x` does. Unlike journalctl, which reads the database
+//! `journalctl --update-catalog` compiles, the catalog is read straight from
+//! the `.catalog` source files.
+//!
+//! A source file is a list of texts, each after a `-- <id> [language]` line
+//! and a blank line before that. A text starts with `Header: value` lines,
+//! followed by a blank line and the body. Lines starting with `#` are
+//! comments. `@FIELD@` in a text stands for the value of `FIELD` in the
+//! entry it explains.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::{CString, OsString};
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+use crate::entry::Entry;
+use crate::fields::{parse_id128, MESSAGE_ID};
+
+/// The directories systemd reads catalog source files from. Files in earlier
+/// directories override files with the same name in later ones.
+pub const CATALOG_DIRS: [&str; 2] = ["/usr/local/lib/systemd/catalog", "/usr/lib/systemd/catalog"];
+
+/// Fields whose `FIELD=value` is longer than this are not put into texts,
+/// their name is
+const REPLACE_VAR_MAX: usize = 4096;
+
+#[derive(Debug, Clone)]
+pub struct Catalog {
+    /// Texts by message id and language, `""` for texts in the default
+    /// language
+    texts: BTreeMap<(u128, String), String>,
+    /// The language to look texts up in, like `de_DE` or `de`, or `None` for
+    /// the default language. `Catalog::new` takes it from the locale.
+    pub language: Option<String>,
+}
+
+impl Catalog {
+    /// An empty catalog looking up texts in the language of the locale
+    pub fn new() -> Catalog {
+        Catalog { texts: BTreeMap::new(), language: locale_language() }
+    }
+
+    /// Reads the `.catalog` files in `dirs`, like `CATALOG_DIRS`. Files are
+    /// imported in order of their names and a file hides files with the same
+    /// name in later directories. Directories that do not exist are skipped.
+    pub fn open<P: AsRef<Path>>(dirs: &[P]) -> Result<Catalog> {
+        let mut files: BTreeMap<OsString, PathBuf> = BTreeMap::new();
+        for dir in dirs {
+            let entries = match fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            for entry in entries {
+                let entry = entry?;
+                let name = entry.file_name();
+                let catalog = name.to_str().is_some_and(|n| n.ends_with(".catalog") && !n.starts_with('.'));
+                if catalog {
+                    files.entry(name).or_insert_with(|| entry.path());
+                }
+            }
+        }
+
+        let mut catalog = Catalog::new();
+        for path in files.values() {
+            // Like in systemd, a link to /dev/null masks files of that name
+            if fs::read_link(path).is_ok_and(|target| target == Path::new("/dev/null")) {
+                continue;
+            }
+            catalog.import_file(path)?;
+        }
+        Ok(catalog)
+    }
+
+    /// Imports a `.catalog` file. Its texts are in the language named before
+    /// `.catalog` in the file name, like `systemd.de.catalog`, unless they
+    /// name their own.
+    pub fn import_file(&mut self, path: &Path) -> Result<()> {
+        let source = fs::read_to_string(path)?;
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let language = name.strip_suffix(".catalog").and_then(|stem| stem.rsplit_once('.')).map(|(_, l)| l);
+        self.import(&source, language.filter(|l| !l.is_empty() && l.len() < 32), &path.display().to_string())
+    }
+
+    /// Imports the texts of the catalog source `source`, in `language` unless
+    /// they name their own. `origin` names the source in errors.
+    pub fn import(&mut self, source: &str, language: Option<&str>, origin: &str) -> Result<()> {
+        let invalid = |n: usize, what: &str| Error::new(ErrorKind::InvalidData, format!("[{}:{}] {}", origin, n, what));
+        // The id, language and text of the text being read
+        let mut current: Option<(u128, Option<String>, String)> = None;
+        let mut empty_line = true;
+        for (n, line) in source.lines().enumerate().map(|(i, l)| (i + 1, l)) {
+            if line.is_empty() {
+                empty_line = true;
+                continue;
+            }
+            if line.starts_with('#') {
+                continue;
+            }
+
+            let id = match line.strip_prefix("-- ") {
+                Some(rest) if empty_line && rest.get(32..).is_some_and(|l| l.is_empty() || l.starts_with(' ')) => {
+                    parse_id128(&rest[..32]).map(|id| (id, rest[32..].trim()))
+                }
+                _ => None,
+            };
+            if let Some((id, own_language)) = id {
+                match current.take() {
+                    Some((_, _, text)) if text.is_empty() => return Err(invalid(n, "No payload text.")),
+                    Some(previous) => self.finish(previous, language),
+                    None => {}
+                }
+                let own_language = match own_language.len() {
+                    0 => None,
+                    1 => return Err(invalid(n, "Language too short.")),
+                    2..=31 => Some(own_language.to_owned()),
+                    _ => return Err(invalid(n, "Language too long.")),
+                };
+                current = Some((id, own_language, String::new()));
+                empty_line = false;
+                continue;
+            }
+
+            let text = match current.as_mut() {
+                Some((_, _, text)) => text,
+                None => return Err(invalid(n, "Got payload before ID.")),
+            };
+            // Blank lines are kept inside texts, but not at their start or end
+            if empty_line && !text.is_empty() {
+                text.push('\n');
+            }
+            text.push_str(line);
+            text.push('\n');
+            empty_line = false;
+        }
+        match current {
+            Some((_, _, text)) if text.is_empty() => Err(invalid(source.lines().count(), "No payload text.")),
+            Some(last) => {
+                self.finish(last, language);
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Adds a text that was read. If there already is a text for the id and
+    /// language, the headers of both are kept, with the new ones first, and
+    /// the new body replaces the old one unless it is empty.
+    fn finish(&mut self, (id, own_language, text): (u128, Option<String>, String), language: Option<&str>) {
+        let language = own_language.or_else(|| language.map(str::to_owned)).unwrap_or_default();
+        let text = match self.texts.remove(&(id, language.clone())) {
+            Some(previous) => {
+                let (headers, body) = split_headers(&text);
+                let (previous_headers, previous_body) = split_headers(&previous);
+                let body = if body.is_empty() { previous_body } else { body };
+                [headers, previous_headers, body].concat()
+            }
+            None => text,
+        };
+        self.texts.insert((id, language), text);
+    }
+
+    /// The text for message id `id` in the catalog's language, or else in
+    /// the language without its territory, like `de` for `de_DE`, or else in
+    /// the default language. Texts of ids that only have texts in other
+    /// languages are in the first of those.
+    pub fn get(&self, id: u128) -> Option<&str> {
+        let mut languages = Vec::new();
+        if let Some(language) = self.language.as_deref() {
+            languages.push(language);
+            if let Some((short, _)) = language.split_once('_') {
+                languages.push(short);
+            }
+        }
+        languages.push("");
+        languages.iter()
+            .find_map(|language| self.texts.get(&(id, language.to_string())))
+            .or_else(|| self.texts.range((id, String::new())..).next().filter(|((i, _), _)| *i == id).map(|(_, t)| t))
+            .map(String::as_str)
+    }
+
+    /// The message ids the catalog has texts for, in ascending order
+    pub fn ids(&self) -> impl Iterator<Item = u128> + '_ {
+        let mut last = None;
+        self.texts.keys().map(|(id, _)| *id).filter(move |&id| last.replace(id) != Some(id))
+    }
+
+    /// The text explaining `entry`, with the `@FIELD@`s in it replaced by the
+    /// values of the entry's fields, or by the field name if the entry has no
+    /// such field or its value is too long
+    pub fn explain(&self, entry: &Entry) -> Option<String> {
+        let id = entry.get_str(MESSAGE_ID).and_then(parse_id128)?;
+        let text = self.get(id)?.as_bytes();
+        let mut explanation = Vec::with_capacity(text.len());
+        let mut i = 0;
+        while i < text.len() {
+            let name_len = text[i + 1..].iter().take_while(|&&c| c.is_ascii_uppercase() || c == b'_').count();
+            if text[i] != b'@' || name_len == 0 || text.get(i + 1 + name_len) != Some(&b'@') {
+                explanation.push(text[i]);
+                i += 1;
+                continue;
+            }
+            // Only uppercase letters and underscores, so UTF-8
+            let name = std::str::from_utf8(&text[i + 1..i + 1 + name_len]).unwrap();
+            match entry.get(name).filter(|v| name.len() + 1 + v.len() <= REPLACE_VAR_MAX) {
+                // Like systemd, values end at the first NUL
+                Some(value) => explanation.extend(value.iter().take_while(|&&c| c != 0)),
+                None => explanation.extend_from_slice(name.as_bytes()),
+            }
+            i += name_len + 2;
+        }
+        Some(String::from_utf8_lossy(&explanation).into_owned())
+    }
+}
+
+impl Default for Catalog {
+    /// The same as `Catalog::new`, with the language of the locale
+    fn default() -> Catalog {
+        Catalog::new()
+    }
+}
+
+/// Splits a text into its header lines, up to and including the newline
+/// before the blank line, and the rest
+fn split_headers(text: &str) -> (&str, &str) {
+    match text.find("\n\n") {
+        Some(end) => text.split_at(end + 1),
+        None => (text, ""),
+    }
+}
+
+/// The value of the header `name`, like `Subject`, of a catalog text
+pub fn header<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    text.lines()
+        .take_while(|line| !line.is_empty())
+        .find_map(|line| line.strip_prefix(name).and_then(|rest| rest.strip_prefix(':')))
+        .map(|value| value.trim_start())
+}
+
+/// The first of the locale environment variables `names` that is set
+fn locale_env(names: &[&str]) -> Option<String> {
+    names.iter().filter_map(|name| env::var(name).ok()).find(|value| !value.is_empty())
+}
+
+/// Whether the locale `name` is installed, so `setlocale(3)` would use it
+fn locale_installed(name: &str, mask: libc::c_int) -> bool {
+    let Ok(name) = CString::new(name) else {
+        return false;
+    };
+    // Safety: the name is a NUL-terminated string, and the locale is freed
+    // right away
+    unsafe {
+        let locale = libc::newlocale(mask, name.as_ptr(), ptr::null_mut());
+        if locale.is_null() {
+            return false;
+        }
+        libc::freelocale(locale);
+    }
+    true
+}
+
+/// The language messages are shown in, from `LC_ALL`, `LC_MESSAGES` or
+/// `LANG`, without the codeset and modifier, e.g. `de_DE` for
+/// `de_DE.UTF-8`. `None` for the C locale and locales that are not
+/// installed.
+pub fn locale_language() -> Option<String> {
+    let locale = locale_env(&["LC_ALL", "LC_MESSAGES", "LANG"])?;
+    if locale == "C" || locale == "POSIX" || !locale_installed(&locale, libc::LC_MESSAGES_MASK) {
+        return None;
+    }
+    let language = &locale[..locale.find(['.', '@']).unwrap_or(locale.len())];
+    Some(language.to_owned()).filter(|l| !l.is_empty() && l.len() < 32)
+}
+
+/// Whether the terminal can be expected to show UTF-8, like systemd's
+/// `is_locale_utf8()`: if the locale's codeset is UTF-8, and also if no
+/// locale is set or it is not installed
+pub fn is_locale_utf8() -> bool {
+    let locale = match locale_env(&["LC_ALL", "LC_CTYPE", "LANG"]) {
+        Some(locale) => locale,
+        None => return true,
+    };
+    if locale == "C" || locale == "POSIX" {
+        return false;
+    }
+    if !locale_installed(&locale, libc::LC_CTYPE_MASK) {
+        return true;
+    }
+    let codeset = locale.split_once('.').map(|(_, c)| c.split('@').next().unwrap_or(c)).unwrap_or("");
+    codeset.eq_ignore_ascii_case("utf-8") || codeset.eq_ignore_ascii_case("utf8")
+}
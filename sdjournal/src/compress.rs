@@ -0,0 +1,75 @@
+//! Decompression of DATA object payloads, which journald compresses with
+//! XZ, LZ4 or ZSTD once they are larger than its compression threshold.
+
+use std::borrow::Cow;
+use std::convert::TryInto;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+use crate::journal::*;
+
+/// Largest payload a DATA object may decompress to, like journald's
+/// `DATA_SIZE_MAX`
+pub const DATA_SIZE_MAX: u64 = 768 * 1024 * 1024;
+
+/// The payload of an object with the object header `flags`, decompressed if
+/// one of the compression flags is set
+pub fn decompress(flags: u8, payload: &[u8]) -> Result<Cow<'_, [u8]>> {
+    match flags & OBJECT_COMPRESSED_MASK {
+        0 => Ok(Cow::Borrowed(payload)),
+        OBJECT_COMPRESSED_XZ => {
+            let mut out = CappedWriter(Vec::new());
+            lzma_rs::xz_decompress(&mut &payload[..], &mut out)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, format!("Bad XZ payload: {}", e)))?;
+            Ok(Cow::Owned(out.0))
+        }
+        OBJECT_COMPRESSED_LZ4 => {
+            // journald puts the uncompressed size before the LZ4 block
+            if payload.len() < 8 {
+                return Err(Error::new(ErrorKind::InvalidData, "LZ4 payload too short"));
+            }
+            let size = u64::from_le_bytes(payload[..8].try_into().unwrap());
+            if size > DATA_SIZE_MAX {
+                return Err(too_large());
+            }
+            let out = lz4_flex::block::decompress(&payload[8..], size as usize)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, format!("Bad LZ4 payload: {}", e)))?;
+            if out.len() as u64 != size {
+                return Err(Error::new(ErrorKind::InvalidData, "LZ4 payload has the wrong size"));
+            }
+            Ok(Cow::Owned(out))
+        }
+        OBJECT_COMPRESSED_ZSTD => {
+            let decoder = ruzstd::decoding::StreamingDecoder::new(payload)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, format!("Bad ZSTD payload: {}", e)))?;
+            let mut out = Vec::new();
+            decoder.take(DATA_SIZE_MAX + 1).read_to_end(&mut out)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, format!("Bad ZSTD payload: {}", e)))?;
+            if out.len() as u64 > DATA_SIZE_MAX {
+                return Err(too_large());
+            }
+            Ok(Cow::Owned(out))
+        }
+        _ => Err(Error::new(ErrorKind::InvalidData, "Object has more than one compression flag")),
+    }
+}
+
+fn too_large() -> Error {
+    Error::new(ErrorKind::InvalidData, "Decompressed payload too large")
+}
+
+/// Collects output up to `DATA_SIZE_MAX` bytes
+struct CappedWriter(Vec<u8>);
+
+impl Write for CappedWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if (self.0.len() + buf.len()) as u64 > DATA_SIZE_MAX {
+            return Err(too_large());
+        }
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+//! A log entry with its fields split into names and values, as the output
+//! formatters and filters see it. Unlike `EntryObject`, which mirrors the
+//! on-disk layout, an `Entry` owns its payloads and looks fields up by exact
+//! name.
+
+use std::cmp::Ordering;
+use std::io::{Error, ErrorKind, Result};
+use std::str;
+
+use crate::journal::*;
+
+/// What decides where an entry goes when the entries of several files are
+/// interleaved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryPosition {
+    pub seqnum_id: u128,
+    pub seqnum: u64,
+    pub boot_id: u128,
+    pub monotonic: u64,
+    pub realtime: u64,
+    pub xor_hash: u64,
+}
+
+impl EntryPosition {
+    pub fn new(header: &JournalHeader, entry: &EntryObject) -> EntryPosition {
+        EntryPosition {
+            seqnum_id: header.seqnum_id,
+            seqnum: entry.seqnum,
+            boot_id: entry.boot_id,
+            monotonic: entry.monotonic,
+            realtime: entry.realtime,
+            xor_hash: entry.xor_hash,
+        }
+    }
+
+    /// Orders entries like journalctl interleaves them: by seqnum if both
+    /// belong to the same sequence, else by monotonic time if both are of
+    /// the same boot, else by realtime
+    pub fn compare(&self, other: &EntryPosition) -> Ordering {
+        if self.seqnum_id == other.seqnum_id && self.seqnum != other.seqnum {
+            return self.seqnum.cmp(&other.seqnum);
+        }
+        if self.boot_id == other.boot_id && self.monotonic != other.monotonic {
+            return self.monotonic.cmp(&other.monotonic);
+        }
+        self.realtime.cmp(&other.realtime).then(self.xor_hash.cmp(&other.xor_hash))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// Identifies the sequence `seqnum` belongs to
+    pub seqnum_id: u128,
+    pub seqnum: u64,
+    pub realtime: u64,
+    pub monotonic: u64,
+    pub boot_id: u128,
+    pub xor_hash: u64,
+    /// `FIELD=value` payloads and the position of their `=`, in the order of
+    /// the entry's items
+    fields: Vec<(usize, Vec<u8>)>,
+}
+
+impl Entry {
+    /// Builds an entry from an entry object of the journal with `header`,
+    /// decompressing compressed items. Fails if an item is not a DATA object
+    /// holding `FIELD=value`.
+    pub fn new(header: &JournalHeader, entry: EntryObject) -> Result<Entry> {
+        let mut fields = Vec::with_capacity(entry.items.len());
+        for item in entry.items {
+            let data = match item.item {
+                Object::Data(d) => d,
+                _ => return Err(Error::new(ErrorKind::InvalidData, "Entry item is not a data object")),
+            };
+            let payload = if data.object.is_compressed() {
+                data.decompressed_payload()?.into_owned()
+            } else {
+                data.payload
+            };
+            match payload.iter().position(|&c| c == b'=') {
+                Some(eq) if eq > 0 && str::from_utf8(&payload[..eq]).is_ok() => fields.push((eq, payload)),
+                _ => return Err(Error::new(ErrorKind::InvalidData, "Entry item is not FIELD=value")),
+            }
+        }
+        Ok(Entry {
+            seqnum_id: header.seqnum_id,
+            seqnum: entry.seqnum,
+            realtime: entry.realtime,
+            monotonic: entry.monotonic,
+            boot_id: entry.boot_id,
+            xor_hash: entry.xor_hash,
+            fields,
+        })
+    }
+
+    /// The cursor of the entry in the format journalctl uses, e.g.
+    /// `s=<seqnum id>;i=<seqnum>;b=<boot id>;m=<monotonic>;t=<realtime>;x=<xor hash>`
+    pub fn cursor(&self) -> String {
+        format!("s={:032x};i={:x};b={:032x};m={:x};t={:x};x={:x}",
+                self.seqnum_id, self.seqnum, self.boot_id, self.monotonic, self.realtime, self.xor_hash)
+    }
+
+    pub fn position(&self) -> EntryPosition {
+        EntryPosition {
+            seqnum_id: self.seqnum_id,
+            seqnum: self.seqnum,
+            boot_id: self.boot_id,
+            monotonic: self.monotonic,
+            realtime: self.realtime,
+            xor_hash: self.xor_hash,
+        }
+    }
+
+    /// All fields as `(name, value)` pairs, in the order of the entry's
+    /// items. Repeated fields appear once per value.
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        // Names were checked to be UTF-8 in `new`
+        self.fields.iter().map(|(eq, p)| (str::from_utf8(&p[..*eq]).unwrap(), &p[eq + 1..]))
+    }
+
+    /// All `FIELD=value` payloads, in the order of the entry's items
+    pub fn payloads(&self) -> impl Iterator<Item = &[u8]> {
+        self.fields.iter().map(|(_, p)| &p[..])
+    }
+
+    /// Every value of the field `name`
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a [u8]> + 'a {
+        self.fields().filter(move |(n, _)| *n == name).map(|(_, v)| v)
+    }
+
+    /// The first value of the field `name`
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.fields().find(|(n, _)| *n == name).map(|(_, v)| v)
+    }
+
+    /// The first value of the field `name`, if it is UTF-8
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        self.get(name).and_then(|v| str::from_utf8(v).ok())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+}
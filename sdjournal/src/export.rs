@@ -0,0 +1,277 @@
+//! The Journal Export Format, as read by `systemd-journal-remote` and
+//! written by `journalctl -o export`. Each entry is a list of `FIELD=value`
+//! lines followed by an empty line. Values that are not printable text, or
+//! contain a newline, are written as the field name, a newline, the value
+//! length as a little endian u64 and the raw value.
+
+use std::io::{BufRead, Error, ErrorKind, Read, Result, Seek, Write};
+use std::str;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::entry::Entry;
+use crate::fields::{parse_id128, BOOT_ID, MACHINE_ID};
+use crate::writer::*;
+
+/// Largest field value accepted from an export stream
+const DATA_SIZE_MAX: u64 = 768 * 1024 * 1024;
+
+/// Whether a value is UTF-8 without control characters other than tabs and,
+/// if `allow_newline` is set, newlines
+pub(crate) fn is_printable(value: &[u8], allow_newline: bool) -> bool {
+    match str::from_utf8(value) {
+        Ok(s) => !s.chars().any(|c| c != '\t' && !(allow_newline && c == '\n') && c.is_control()),
+        Err(_) => false,
+    }
+}
+
+/// Writes one field in the export format, including the trailing newline
+pub fn write_field<W: Write>(out: &mut W, payload: &[u8]) -> Result<()> {
+    let eq = match payload.iter().position(|&c| c == b'=') {
+        Some(eq) if eq > 0 => eq,
+        _ => return Err(Error::new(ErrorKind::InvalidData, "Invalid field")),
+    };
+    let value = &payload[eq + 1..];
+    if is_printable(value, false) {
+        out.write_all(payload)?;
+    } else {
+        out.write_all(&payload[..eq])?;
+        out.write_all(b"\n")?;
+        out.write_all(&(value.len() as u64).to_le_bytes())?;
+        out.write_all(value)?;
+    }
+    out.write_all(b"\n")
+}
+
+/// Writes `entry` in the export format, including the empty line that ends it
+pub fn write_entry<W: Write>(out: &mut W, entry: &Entry) -> Result<()> {
+    writeln!(out, "__CURSOR={}", entry.cursor())?;
+    writeln!(out, "__REALTIME_TIMESTAMP={}", entry.realtime)?;
+    writeln!(out, "__MONOTONIC_TIMESTAMP={}", entry.monotonic)?;
+    writeln!(out, "_BOOT_ID={:032x}", entry.boot_id)?;
+    for payload in entry.payloads() {
+        // The boot id was written from the entry itself already
+        if payload.starts_with(b"_BOOT_ID=") {
+            continue;
+        }
+        write_field(out, payload)?;
+    }
+    out.write_all(b"\n")
+}
+
+/// One entry read from an export stream
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExportEntry {
+    pub cursor: Option<String>,
+    pub realtime: Option<u64>,
+    pub monotonic: Option<u64>,
+    /// From `_BOOT_ID`, which is also kept in `fields`
+    pub boot_id: Option<u128>,
+    /// From `__SEQNUM`, or else the cursor
+    pub seqnum: Option<u64>,
+    /// From `__SEQNUM_ID`, or else the cursor
+    pub seqnum_id: Option<u128>,
+    /// The `FIELD=value` payloads, without the `__` address fields
+    pub fields: Vec<Vec<u8>>,
+}
+
+impl ExportEntry {
+    fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.cursor.is_none() && self.realtime.is_none() && self.monotonic.is_none()
+    }
+
+    /// The value of the first `name` field
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.fields.iter()
+            .find(|f| f.len() > name.len() && f.starts_with(name.as_bytes()) && f[name.len()] == b'=')
+            .map(|f| &f[name.len() + 1..])
+    }
+
+    fn set_address_field(&mut self, name: &str, value: &[u8]) -> Result<()> {
+        let value = str::from_utf8(value).map_err(|_| invalid_data(&format!("Invalid {} value", name)))?;
+        let invalid = || invalid_data(&format!("Invalid {} value: {}", name, value));
+        match name {
+            "__CURSOR" => {
+                self.cursor = Some(value.to_owned());
+                if let Some((seqnum_id, seqnum)) = parse_cursor_seqnum(value) {
+                    self.seqnum_id = self.seqnum_id.or(Some(seqnum_id));
+                    self.seqnum = self.seqnum.or(Some(seqnum));
+                }
+            }
+            "__REALTIME_TIMESTAMP" => self.realtime = Some(value.parse().map_err(|_| invalid())?),
+            "__MONOTONIC_TIMESTAMP" => self.monotonic = Some(value.parse().map_err(|_| invalid())?),
+            "__SEQNUM" => self.seqnum = Some(value.parse().map_err(|_| invalid())?),
+            "__SEQNUM_ID" => self.seqnum_id = Some(u128::from_str_radix(value, 16).map_err(|_| invalid())?),
+            // Other address fields carry nothing the journal stores
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn invalid_data(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+/// The seqnum id and seqnum of a cursor
+fn parse_cursor_seqnum(cursor: &str) -> Option<(u128, u64)> {
+    let mut seqnum_id = None;
+    let mut seqnum = None;
+    for part in cursor.split(';') {
+        if let Some(id) = part.strip_prefix("s=") {
+            seqnum_id = u128::from_str_radix(id, 16).ok();
+        } else if let Some(i) = part.strip_prefix("i=") {
+            seqnum = u64::from_str_radix(i, 16).ok();
+        }
+    }
+    Some((seqnum_id?, seqnum?))
+}
+
+/// Whether `name` can be the name of a journal field: upper case letters,
+/// digits and underscores, not starting with a digit, at most 64 bytes
+pub fn is_valid_field_name(name: &[u8]) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && !name[0].is_ascii_digit()
+        && name.iter().all(|&c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == b'_')
+}
+
+/// Reads the entries of an export stream one by one
+pub struct ExportReader<R: BufRead> {
+    reader: R,
+    line: Vec<u8>,
+}
+
+impl<R: BufRead> ExportReader<R> {
+    pub fn new(reader: R) -> ExportReader<R> {
+        ExportReader { reader, line: Vec::new() }
+    }
+
+    /// Reads the next line without its newline. Returns false at the end of
+    /// the stream.
+    fn read_line(&mut self) -> Result<bool> {
+        self.line.clear();
+        if self.reader.read_until(b'\n', &mut self.line)? == 0 {
+            return Ok(false);
+        }
+        if self.line.last() == Some(&b'\n') {
+            self.line.pop();
+        }
+        Ok(true)
+    }
+
+    /// Reads the length prefixed value following a field name on its own line
+    fn read_binary_value(&mut self) -> Result<Vec<u8>> {
+        let mut len = [0u8; 8];
+        self.reader.read_exact(&mut len)?;
+        let len = u64::from_le_bytes(len);
+        if len > DATA_SIZE_MAX {
+            return Err(invalid_data("Field value too large"));
+        }
+        let mut value = Vec::new();
+        (&mut self.reader).take(len).read_to_end(&mut value)?;
+        let mut newline = [0u8; 1];
+        if value.len() as u64 != len || self.reader.read(&mut newline)? != 1 {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "Truncated binary field"));
+        }
+        if newline[0] != b'\n' {
+            return Err(invalid_data("Binary field not followed by a newline"));
+        }
+        Ok(value)
+    }
+
+    fn read_entry(&mut self) -> Result<Option<ExportEntry>> {
+        let mut entry = ExportEntry::default();
+        loop {
+            if !self.read_line()? || self.line.is_empty() {
+                if entry.is_empty() {
+                    // Skip over extra empty lines between entries
+                    if self.line.is_empty() && !self.reader.fill_buf()?.is_empty() {
+                        continue;
+                    }
+                    return Ok(None);
+                }
+                return Ok(Some(entry));
+            }
+
+            let (name, value) = match self.line.iter().position(|&c| c == b'=') {
+                Some(eq) => (self.line[..eq].to_vec(), self.line[eq + 1..].to_vec()),
+                None => {
+                    let name = self.line.clone();
+                    (name, self.read_binary_value()?)
+                }
+            };
+            if !is_valid_field_name(&name) {
+                return Err(invalid_data(&format!("Invalid field name: {}", String::from_utf8_lossy(&name))));
+            }
+
+            if name.starts_with(b"__") {
+                // Names are ASCII, as checked above
+                entry.set_address_field(str::from_utf8(&name).unwrap(), &value)?;
+                continue;
+            }
+            if name == BOOT_ID.as_bytes() {
+                let id = str::from_utf8(&value).ok().and_then(parse_id128);
+                entry.boot_id = Some(id.ok_or_else(|| invalid_data("Invalid _BOOT_ID value"))?);
+            }
+            let mut field = name;
+            field.push(b'=');
+            field.extend_from_slice(&value);
+            entry.fields.push(field);
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for ExportReader<R> {
+    type Item = Result<ExportEntry>;
+
+    fn next(&mut self) -> Option<Result<ExportEntry>> {
+        self.read_entry().transpose()
+    }
+}
+
+/// Writes the entries of an export stream into a new journal written to
+/// `out` and returns how many were imported. The machine id and seqnum id of
+/// the file are taken from the first entry. Seqnums are kept where the stream
+/// has them for the same seqnum id and they still increase.
+pub fn import<R: BufRead, W>(input: R, out: W) -> Result<u64>
+where
+    W: Read + Write + Seek,
+{
+    let mut entries = ExportReader::new(input).peekable();
+    let first = match entries.peek() {
+        Some(Ok(e)) => e.clone(),
+        Some(Err(_)) => return Err(entries.next().unwrap().unwrap_err()),
+        None => ExportEntry::default(),
+    };
+    let machine_id = first.get(MACHINE_ID)
+        .and_then(|id| str::from_utf8(id).ok())
+        .and_then(parse_id128)
+        .unwrap_or(0);
+    let mut params = JournalParams::new(machine_id, first.boot_id.unwrap_or(0))?;
+    if let Some(seqnum_id) = first.seqnum_id {
+        params.seqnum_id = seqnum_id;
+    }
+    let mut writer = JournalWriter::create(out, &params)?;
+
+    let mut n_entries = 0;
+    for entry in entries {
+        let entry = entry?;
+        if entry.fields.is_empty() {
+            continue;
+        }
+        if let Some(seqnum) = entry.seqnum {
+            if entry.seqnum_id == Some(params.seqnum_id) && seqnum >= writer.next_seqnum() {
+                writer.set_next_seqnum(seqnum)?;
+            }
+        }
+        let realtime = match entry.realtime {
+            Some(r) => r,
+            None => SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_micros() as u64).unwrap_or(0),
+        };
+        let fields: Vec<&[u8]> = entry.fields.iter().map(|f| &f[..]).collect();
+        writer.append_entry(realtime, entry.monotonic.unwrap_or(0), entry.boot_id.unwrap_or(params.boot_id), &fields)?;
+        n_entries += 1;
+    }
+    writer.close()?;
+    Ok(n_entries)
+}
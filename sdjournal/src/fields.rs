@@ -0,0 +1,282 @@
+//! Names of well-known journal fields, see `systemd.journal-fields(7)`, and
+//! typed accessors for them on `Entry`. The accessors return `Ok(None)` if
+//! the entry lacks the field and an `InvalidData` error if its value is
+//! malformed.
+//!
+//! Fields starting with an underscore are trusted: they are added by
+//! journald and cannot be set by the logging client. Their constants drop
+//! the underscore, e.g. `PID` is `_PID`.
+
+use std::cmp;
+use std::fmt;
+use std::io::{Error, ErrorKind, Result};
+use std::ops::RangeInclusive;
+use std::str::{self, FromStr};
+
+use crate::entry::Entry;
+
+pub const MESSAGE: &str = "MESSAGE";
+pub const MESSAGE_ID: &str = "MESSAGE_ID";
+pub const PRIORITY: &str = "PRIORITY";
+pub const CODE_FILE: &str = "CODE_FILE";
+pub const CODE_LINE: &str = "CODE_LINE";
+pub const CODE_FUNC: &str = "CODE_FUNC";
+pub const SYSLOG_FACILITY: &str = "SYSLOG_FACILITY";
+pub const SYSLOG_IDENTIFIER: &str = "SYSLOG_IDENTIFIER";
+pub const SYSLOG_PID: &str = "SYSLOG_PID";
+pub const UNIT: &str = "UNIT";
+pub const USER_UNIT: &str = "USER_UNIT";
+pub const COREDUMP_UNIT: &str = "COREDUMP_UNIT";
+pub const COREDUMP_USER_UNIT: &str = "COREDUMP_USER_UNIT";
+pub const OBJECT_SYSTEMD_UNIT: &str = "OBJECT_SYSTEMD_UNIT";
+pub const OBJECT_SYSTEMD_USER_UNIT: &str = "OBJECT_SYSTEMD_USER_UNIT";
+
+pub const PID: &str = "_PID";
+pub const UID: &str = "_UID";
+pub const GID: &str = "_GID";
+pub const COMM: &str = "_COMM";
+pub const EXE: &str = "_EXE";
+pub const CMDLINE: &str = "_CMDLINE";
+pub const HOSTNAME: &str = "_HOSTNAME";
+pub const TRANSPORT: &str = "_TRANSPORT";
+pub const BOOT_ID: &str = "_BOOT_ID";
+pub const MACHINE_ID: &str = "_MACHINE_ID";
+pub const SYSTEMD_UNIT: &str = "_SYSTEMD_UNIT";
+pub const SYSTEMD_USER_UNIT: &str = "_SYSTEMD_USER_UNIT";
+pub const SYSTEMD_SLICE: &str = "_SYSTEMD_SLICE";
+pub const SYSTEMD_USER_SLICE: &str = "_SYSTEMD_USER_SLICE";
+pub const SOURCE_REALTIME_TIMESTAMP: &str = "_SOURCE_REALTIME_TIMESTAMP";
+pub const SOURCE_MONOTONIC_TIMESTAMP: &str = "_SOURCE_MONOTONIC_TIMESTAMP";
+
+/// Parses a 128-bit id such as a boot or machine id, written as 32 hex
+/// digits or as a UUID with dashes
+pub fn parse_id128(s: &str) -> Option<u128> {
+    let hex: String = match s.len() {
+        32 => s.to_owned(),
+        36 if [8, 13, 18, 23].iter().all(|&i| s.as_bytes()[i] == b'-') => s.chars().filter(|&c| c != '-').collect(),
+        _ => return None,
+    };
+    if !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    u128::from_str_radix(&hex, 16).ok()
+}
+
+/// Syslog priority of an entry, from most to least severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Emergency = 0,
+    Alert = 1,
+    Critical = 2,
+    Error = 3,
+    Warning = 4,
+    Notice = 5,
+    Info = 6,
+    Debug = 7,
+}
+
+const PRIORITY_NAMES: [(&str, Priority); 8] = [
+    ("emerg", Priority::Emergency),
+    ("alert", Priority::Alert),
+    ("crit", Priority::Critical),
+    ("err", Priority::Error),
+    ("warning", Priority::Warning),
+    ("notice", Priority::Notice),
+    ("info", Priority::Info),
+    ("debug", Priority::Debug),
+];
+
+impl Priority {
+    pub fn from_u8(level: u8) -> Option<Priority> {
+        PRIORITY_NAMES.get(level as usize).map(|&(_, p)| p)
+    }
+
+    /// Parses the priorities to show like `journalctl -p`: a single priority
+    /// selects it and all more severe ones, `from..to` the priorities between
+    /// the two in either order, e.g. `warning`, `0..3` or `err..alert`
+    pub fn parse_range(s: &str) -> Result<RangeInclusive<Priority>> {
+        match s.find("..") {
+            Some(dots) => {
+                let from: Priority = s[..dots].parse()?;
+                let to: Priority = s[dots + 2..].parse()?;
+                Ok(cmp::min(from, to)..=cmp::max(from, to))
+            }
+            None => Ok(Priority::Emergency..=s.parse()?),
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    /// The name journalctl uses for the priority, e.g. `err`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", PRIORITY_NAMES[*self as usize].0)
+    }
+}
+
+impl FromStr for Priority {
+    type Err = Error;
+
+    /// Parses a priority from its number or name, e.g. `3` or `err`
+    fn from_str(s: &str) -> Result<Priority> {
+        s.parse().ok()
+            .and_then(Priority::from_u8)
+            .or_else(|| PRIORITY_NAMES.iter().find(|(name, _)| *name == s).map(|&(_, p)| p))
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("Invalid priority: {}", s)))
+    }
+}
+
+/// How journald received an entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Transport {
+    /// The kernel audit subsystem
+    Audit,
+    /// Generated by journald itself
+    Driver,
+    /// The syslog socket
+    Syslog,
+    /// The native journal protocol
+    Journal,
+    /// Standard output or error of a service
+    Stdout,
+    /// The kernel log buffer
+    Kernel,
+}
+
+const TRANSPORT_NAMES: [(&str, Transport); 6] = [
+    ("audit", Transport::Audit),
+    ("driver", Transport::Driver),
+    ("syslog", Transport::Syslog),
+    ("journal", Transport::Journal),
+    ("stdout", Transport::Stdout),
+    ("kernel", Transport::Kernel),
+];
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = TRANSPORT_NAMES.iter().find(|(_, t)| t == self).map(|(name, _)| *name).unwrap_or_default();
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Transport {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Transport> {
+        TRANSPORT_NAMES.iter()
+            .find(|(name, _)| *name == s)
+            .map(|&(_, t)| t)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("Invalid transport: {}", s)))
+    }
+}
+
+fn invalid_value(name: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid {} value", name))
+}
+
+impl Entry {
+    /// The value of the field `name` as text
+    fn text(&self, name: &str) -> Result<Option<&str>> {
+        match self.get(name) {
+            Some(value) => str::from_utf8(value).map(Some).map_err(|_| invalid_value(name)),
+            None => Ok(None),
+        }
+    }
+
+    /// The value of the field `name` parsed with `FromStr`
+    fn parse<T: FromStr>(&self, name: &str) -> Result<Option<T>> {
+        match self.text(name)? {
+            Some(value) => value.parse().map(Some).map_err(|_| invalid_value(name)),
+            None => Ok(None),
+        }
+    }
+
+    fn id128(&self, name: &str) -> Result<Option<u128>> {
+        match self.text(name)? {
+            Some(value) => parse_id128(value).map(Some).ok_or_else(|| invalid_value(name)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn message(&self) -> Result<Option<&str>> {
+        self.text(MESSAGE)
+    }
+
+    /// The id of the message catalog entry describing this kind of message
+    pub fn message_id(&self) -> Result<Option<u128>> {
+        self.id128(MESSAGE_ID)
+    }
+
+    pub fn priority(&self) -> Result<Option<Priority>> {
+        match self.parse::<u8>(PRIORITY)? {
+            Some(level) => Priority::from_u8(level).map(Some).ok_or_else(|| invalid_value(PRIORITY)),
+            None => Ok(None),
+        }
+    }
+
+    /// The syslog facility, 0 to 23
+    pub fn syslog_facility(&self) -> Result<Option<u8>> {
+        match self.parse::<u8>(SYSLOG_FACILITY)? {
+            Some(facility) if facility >= 24 => Err(invalid_value(SYSLOG_FACILITY)),
+            facility => Ok(facility),
+        }
+    }
+
+    pub fn syslog_identifier(&self) -> Result<Option<&str>> {
+        self.text(SYSLOG_IDENTIFIER)
+    }
+
+    pub fn pid(&self) -> Result<Option<u32>> {
+        self.parse(PID)
+    }
+
+    pub fn uid(&self) -> Result<Option<u32>> {
+        self.parse(UID)
+    }
+
+    pub fn gid(&self) -> Result<Option<u32>> {
+        self.parse(GID)
+    }
+
+    pub fn hostname(&self) -> Result<Option<&str>> {
+        self.text(HOSTNAME)
+    }
+
+    pub fn transport(&self) -> Result<Option<Transport>> {
+        self.parse(TRANSPORT)
+    }
+
+    pub fn machine_id(&self) -> Result<Option<u128>> {
+        self.id128(MACHINE_ID)
+    }
+
+    pub fn systemd_unit(&self) -> Result<Option<&str>> {
+        self.text(SYSTEMD_UNIT)
+    }
+
+    pub fn systemd_user_unit(&self) -> Result<Option<&str>> {
+        self.text(SYSTEMD_USER_UNIT)
+    }
+
+    pub fn code_file(&self) -> Result<Option<&str>> {
+        self.text(CODE_FILE)
+    }
+
+    pub fn code_line(&self) -> Result<Option<u32>> {
+        self.parse(CODE_LINE)
+    }
+
+    pub fn code_func(&self) -> Result<Option<&str>> {
+        self.text(CODE_FUNC)
+    }
+
+    /// When the client logged the entry, as opposed to when journald
+    /// received it, in microseconds since the epoch
+    pub fn source_realtime(&self) -> Result<Option<u64>> {
+        self.parse(SOURCE_REALTIME_TIMESTAMP)
+    }
+
+    /// When the client logged the entry, in microseconds since boot
+    pub fn source_monotonic(&self) -> Result<Option<u64>> {
+        self.parse(SOURCE_MONOTONIC_TIMESTAMP)
+    }
+}
@@ -0,0 +1,194 @@
+//! Selecting entries by the values of their fields, like
+//! `sd_journal_add_match(3)`, and by time. Matches are resolved through the
+//! data hash table and the entry lists of the matching DATA objects, and
+//! time ranges by bisecting the resulting entry list, so only the selected
+//! entries are ever read.
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::{Error, ErrorKind, Result, Read, Seek, SeekFrom};
+use std::ops::{Range, RangeInclusive};
+
+use crate::export::is_valid_field_name;
+use crate::fields::{Priority, BOOT_ID, PRIORITY};
+use crate::iter::{EntryArrayChain, EntryOffsets};
+use crate::journal::{Journal, ObjectOffset, OBJECT_HEADER_SZ};
+
+/// A set of `FIELD=value` matches, groups of alternative filters and a
+/// realtime range. An entry is selected if, for every field with matches, it
+/// has one of the values given for that field, it is selected by a filter of
+/// every group, and its realtime timestamp is in the range.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filter {
+    /// The field names with the payloads they may have
+    fields: Vec<(Vec<u8>, Vec<Vec<u8>>)>,
+    /// Groups of filters of which each entry must match one
+    any: Vec<Vec<Filter>>,
+    since: Option<u64>,
+    until: Option<u64>,
+}
+
+impl Filter {
+    pub fn new() -> Filter {
+        Filter::default()
+    }
+
+    /// Adds a match for the `FIELD=value` payload. It is an alternative to
+    /// the other matches of the same field and required in addition to the
+    /// matches of other fields.
+    pub fn add_match(&mut self, payload: &[u8]) -> Result<()> {
+        let name = match payload.iter().position(|&c| c == b'=') {
+            Some(eq) if is_valid_field_name(&payload[..eq]) => &payload[..eq],
+            _ => {
+                let payload = String::from_utf8_lossy(payload);
+                return Err(Error::new(ErrorKind::InvalidInput, format!("Invalid match: {}", payload)));
+            }
+        };
+        match self.fields.iter_mut().find(|(n, _)| n == name) {
+            Some((_, payloads)) => {
+                if !payloads.iter().any(|p| p == payload) {
+                    payloads.push(payload.to_vec());
+                }
+            }
+            None => self.fields.push((name.to_vec(), vec![payload.to_vec()])),
+        }
+        Ok(())
+    }
+
+    /// Selects only entries that at least one of `alternatives` selects, like
+    /// matches separated by `sd_journal_add_disjunction(3)`. An empty list
+    /// selects no entries.
+    pub fn add_any(&mut self, alternatives: Vec<Filter>) {
+        self.any.push(alternatives);
+    }
+
+    /// Selects entries with a priority in `priorities`. Like journalctl, a
+    /// range of all priorities adds no matches, so entries without a
+    /// priority are still selected.
+    pub fn add_priorities(&mut self, priorities: RangeInclusive<Priority>) {
+        if priorities == (Priority::Emergency..=Priority::Debug) {
+            return;
+        }
+        for level in *priorities.start() as u8..=*priorities.end() as u8 {
+            self.add_match(format!("{}={}", PRIORITY, level).as_bytes()).expect("valid priority match");
+        }
+    }
+
+    /// Selects entries of the boot `boot_id`
+    pub fn add_boot(&mut self, boot_id: u128) {
+        self.add_match(format!("{}={:032x}", BOOT_ID, boot_id).as_bytes()).expect("valid boot match");
+    }
+
+    /// Selects only entries with a realtime timestamp of `usec` or later
+    pub fn set_since(&mut self, usec: u64) {
+        self.since = Some(usec);
+    }
+
+    /// Selects only entries with a realtime timestamp of `usec` or earlier
+    pub fn set_until(&mut self, usec: u64) {
+        self.until = Some(usec);
+    }
+
+    /// Whether the filter selects every entry
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.any.is_empty() && self.since.is_none() && self.until.is_none()
+    }
+}
+
+/// Offsets of the entries of `journal` selected by `filter`, in file order.
+/// Without matches they are read from the entry arrays only as they are
+/// reached, so walking back from the newest entry reads just those needed.
+pub(crate) fn entry_offsets<'a, T>(journal: &'a Journal<T>, filter: &Filter) -> Result<EntryOffsets<'a, T>>
+where
+    T: 'a + Read + Seek,
+    &'a T: Read + Seek,
+{
+    // Entries are in realtime order unless the clock jumped back, which
+    // journalctl does not account for when seeking either
+    let range = |len: u64, offset_at: &dyn Fn(u64) -> Result<ObjectOffset>| -> Result<Range<u64>> {
+        let mut end = len;
+        if let Some(until) = filter.until {
+            end = bisect(end, |i| Ok(entry_realtime(journal, offset_at(i)?)? <= until))?;
+        }
+        let mut start = 0;
+        if let Some(since) = filter.since {
+            start = bisect(end, |i| Ok(entry_realtime(journal, offset_at(i)?)? < since))?;
+        }
+        Ok(start..end)
+    };
+
+    if filter.fields.is_empty() && filter.any.is_empty() {
+        let chain = EntryArrayChain::new(&journal.file, journal.header.entry_array_offset, journal.header.n_entries)?;
+        let range = range(chain.len(), &|i| chain.get(i))?;
+        return Ok(EntryOffsets::Chain(chain, range));
+    }
+    let offsets = matching_offsets(journal, filter)?;
+    let range = range(offsets.len() as u64, &|i| Ok(offsets[i as usize]))?;
+    Ok(EntryOffsets::Listed(offsets[range.start as usize..range.end as usize].iter().copied().collect()))
+}
+
+/// The number of leading indices below `len` for which `before` holds,
+/// assuming it holds for none once it stops holding for one
+fn bisect<F>(len: u64, mut before: F) -> Result<u64>
+where
+    F: FnMut(u64) -> Result<bool>,
+{
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if before(middle)? {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    Ok(low)
+}
+
+/// Reads just the realtime timestamp of the entry at `offset`
+fn entry_realtime<'a, T>(journal: &'a Journal<T>, offset: ObjectOffset) -> Result<u64>
+where
+    T: 'a + Read + Seek,
+    &'a T: Read + Seek,
+{
+    let mut file = &journal.file;
+    file.seek(SeekFrom::Start(offset + OBJECT_HEADER_SZ + 8))?;
+    file.read_u64::<LittleEndian>()
+}
+
+/// Offsets of the entries with the field values and of the alternatives
+/// `filter` asks for
+fn matching_offsets<'a, T>(journal: &'a Journal<T>, filter: &Filter) -> Result<Vec<ObjectOffset>>
+where
+    T: 'a + Read + Seek,
+    &'a T: Read + Seek,
+{
+    let mut selected: Option<Vec<ObjectOffset>> = None;
+    for (_, payloads) in filter.fields.iter() {
+        let mut offsets = Vec::new();
+        for payload in payloads {
+            if let Some(data) = journal.find_data(payload)? {
+                offsets.extend(journal.data_entry_offsets(&data)?);
+            }
+        }
+        selected = Some(intersect(selected, offsets));
+    }
+    for alternatives in filter.any.iter() {
+        let mut offsets = Vec::new();
+        for alternative in alternatives {
+            offsets.extend(entry_offsets(journal, alternative)?.into_vec()?);
+        }
+        selected = Some(intersect(selected, offsets));
+    }
+    Ok(selected.unwrap_or_default())
+}
+
+/// The offsets in both `selected`, if there is a selection yet, and `offsets`
+fn intersect(selected: Option<Vec<ObjectOffset>>, mut offsets: Vec<ObjectOffset>) -> Vec<ObjectOffset> {
+    // Entries are appended, so their offsets grow in the order they were written
+    offsets.sort_unstable();
+    offsets.dedup();
+    match selected {
+        Some(previous) => previous.into_iter().filter(|o| offsets.binary_search(o).is_ok()).collect(),
+        None => offsets,
+    }
+}
@@ -0,0 +1,285 @@
+//! Forward Secure Pseudo Random Generator, as used by systemd for Forward
+//! Secure Sealing. This is a port of systemd's `fsprg.c` and produces
+//! byte-identical keys and states, so sealing keys generated by
+//! `journalctl --setup-keys` can be used here and vice versa.
+//!
+//! The generator state is `x` in Z_n for a Blum integer `n = p * q`.
+//! Evolving squares `x`, which is easy, while going back an epoch needs the
+//! factors of `n`, which only the holder of the seed has.
+
+use num_bigint::BigUint;
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+pub const FSPRG_RECOMMENDED_SECPAR: u16 = 1536;
+pub const FSPRG_RECOMMENDED_SEEDLEN: usize = 96 / 8;
+
+const RND_GEN_P: u32 = 0x01;
+const RND_GEN_Q: u32 = 0x02;
+const RND_GEN_X: u32 = 0x03;
+
+/// Odd primes below 1000, used to cheaply sieve prime candidates
+const SMALL_PRIMES: [u32; 167] = [
+    3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311, 313, 317, 331, 337, 347, 349, 353, 359, 367, 373, 379, 383, 389, 397, 401, 409, 419, 421,
+    431, 433, 439, 443, 449, 457, 461, 463, 467, 479, 487, 491, 499, 503, 509, 521, 523, 541, 547,
+    557, 563, 569, 571, 577, 587, 593, 599, 601, 607, 613, 617, 619, 631, 641, 643, 647, 653, 659,
+    661, 673, 677, 683, 691, 701, 709, 719, 727, 733, 739, 743, 751, 757, 761, 769, 773, 787, 797,
+    809, 811, 821, 823, 827, 829, 839, 853, 857, 859, 863, 877, 881, 883, 887, 907, 911, 919, 929,
+    937, 941, 947, 953, 967, 971, 977, 983, 991, 997,
+];
+
+/// Number of Miller-Rabin rounds, each with one of `SMALL_PRIMES` as base
+const MILLER_RABIN_ROUNDS: usize = 32;
+
+pub fn is_valid_secpar(secpar: u16) -> bool {
+    secpar & 15 == 0 && (16..=16384).contains(&secpar)
+}
+
+/// Size of a master secret key: secpar, p and q
+pub fn msk_size(secpar: u16) -> usize {
+    2 + 2 * (secpar as usize / 2) / 8
+}
+
+/// Size of a master public key: secpar and n
+pub fn mpk_size(secpar: u16) -> usize {
+    2 + secpar as usize / 8
+}
+
+/// Size of a generator state: secpar, n, x and the epoch
+pub fn state_size(secpar: u16) -> usize {
+    2 + 2 * secpar as usize / 8 + 8
+}
+
+fn store_secpar(buf: &mut [u8], secpar: u16) {
+    let stored = secpar / 16 - 1;
+    buf[..2].copy_from_slice(&stored.to_be_bytes());
+}
+
+fn read_secpar(buf: &[u8]) -> u16 {
+    16 * (u16::from_be_bytes([buf[0], buf[1]]) + 1)
+}
+
+/// Writes `x` big endian, left padded with zeroes to fill `buf`
+fn mpi_export(buf: &mut [u8], x: &BigUint) {
+    let bytes = x.to_bytes_be();
+    assert!(bytes.len() <= buf.len());
+    let pad = buf.len() - bytes.len();
+    buf[..pad].iter_mut().for_each(|b| *b = 0);
+    buf[pad..].copy_from_slice(&bytes);
+}
+
+fn mpi_import(buf: &[u8]) -> BigUint {
+    BigUint::from_bytes_be(buf)
+}
+
+/// Deterministically fills `buf` with pseudorandom bytes derived from
+/// `seed` and `idx`
+fn det_randomize(buf: &mut [u8], seed: &[u8], idx: u32) {
+    let mut base = Sha256::new();
+    base.update(seed);
+    base.update(idx.to_be_bytes());
+
+    for (ctr, chunk) in buf.chunks_mut(32).enumerate() {
+        let mut hasher = base.clone();
+        hasher.update((ctr as u32).to_be_bytes());
+        let digest = hasher.finalize();
+        chunk.copy_from_slice(&digest[..chunk.len()]);
+    }
+}
+
+fn is_probable_prime(n: &BigUint) -> bool {
+    let one = BigUint::from(1u32);
+    let two = BigUint::from(2u32);
+    let n_minus_one = n - &one;
+    let s = n_minus_one.trailing_zeros().unwrap_or(0);
+    let d = &n_minus_one >> s;
+
+    'witness: for base in SMALL_PRIMES.iter().take(MILLER_RABIN_ROUNDS) {
+        let mut x = BigUint::from(*base).modpow(&d, n);
+        if x == one || x == n_minus_one {
+            continue;
+        }
+        for _ in 1..s {
+            x = x.modpow(&two, n);
+            if x == n_minus_one {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+/// Deterministically generates from `seed` and `idx` a prime of `bits`
+/// length that is 3 (mod 4)
+fn genprime3mod4(bits: usize, seed: &[u8], idx: u32) -> BigUint {
+    let mut buf = vec![0u8; bits / 8];
+    det_randomize(&mut buf, seed, idx);
+    let last = buf.len() - 1;
+    buf[0] |= 0xc0; // so that n = pq has maximum size
+    buf[last] |= 0x03; // so that the result is 3 (mod 4)
+
+    let mut p = mpi_import(&buf);
+    // Remainders of p by the small primes, updated as p is stepped by 4
+    let mut residues: Vec<u32> = SMALL_PRIMES.iter()
+        .map(|&s| (&p % s).to_u32_digits().first().cloned().unwrap_or(0))
+        .collect();
+    loop {
+        if !residues.contains(&0) && is_probable_prime(&p) {
+            return p;
+        }
+        p += 4u32;
+        for (r, &s) in residues.iter_mut().zip(SMALL_PRIMES.iter()) {
+            *r = (*r + 4) % s;
+        }
+    }
+}
+
+/// Deterministically generates from `seed` and `idx` a quadratic residue (mod n)
+fn gensquare(n: &BigUint, seed: &[u8], idx: u32, secpar: u16) -> BigUint {
+    let mut buf = vec![0u8; secpar as usize / 8];
+    det_randomize(&mut buf, seed, idx);
+    buf[0] &= 0x7f; // so that x < n
+    let x = mpi_import(&buf);
+    assert!(&x < n);
+    (&x * &x) % n
+}
+
+/// Computes 2^m (mod phi(p)) for a prime p
+fn twopowmodphi(m: u64, p: &BigUint) -> BigUint {
+    let phi = p - 1u32;
+    BigUint::from(2u32).modpow(&BigUint::from(m), &phi)
+}
+
+/// Composes (xp, xq) in Z_p x Z_q into x in Z_n using the Chinese Remainder Theorem
+fn crt_compose(xp: &BigUint, xq: &BigUint, p: &BigUint, q: &BigUint) -> BigUint {
+    // a = (xq - xp) / p (mod q), with p^-1 = p^(q-2) (mod q) as q is prime
+    let a = (xq + q - (xp % q)) % q;
+    let u = p.modpow(&(q - 2u32), q);
+    let a = (a * u) % q;
+    p * a + xp
+}
+
+/// Generates the master secret key (the factors of n) and the master public
+/// key (n) from `seed`. Returns `(msk, mpk)`.
+pub fn gen_mk(seed: &[u8], secpar: u16) -> (Vec<u8>, Vec<u8>) {
+    assert!(is_valid_secpar(secpar));
+    let half = secpar as usize / 2 / 8;
+
+    let p = genprime3mod4(secpar as usize / 2, seed, RND_GEN_P);
+    let q = genprime3mod4(secpar as usize / 2, seed, RND_GEN_Q);
+
+    let mut msk = vec![0u8; msk_size(secpar)];
+    store_secpar(&mut msk, secpar);
+    mpi_export(&mut msk[2..2 + half], &p);
+    mpi_export(&mut msk[2 + half..], &q);
+
+    let n = &p * &q;
+    assert_eq!(n.bits(), secpar as u64);
+    let mut mpk = vec![0u8; mpk_size(secpar)];
+    store_secpar(&mut mpk, secpar);
+    mpi_export(&mut mpk[2..], &n);
+
+    (msk, mpk)
+}
+
+/// A generator state, in the byte layout systemd stores in its `fss` file
+#[derive(Clone, PartialEq)]
+pub struct FsprgState {
+    bytes: Vec<u8>,
+}
+
+impl FsprgState {
+    /// The epoch 0 state for the master public key generated from `seed`
+    pub fn new(mpk: &[u8], seed: &[u8]) -> FsprgState {
+        let secpar = read_secpar(mpk);
+        let len = secpar as usize / 8;
+        let n = mpi_import(&mpk[2..2 + len]);
+        let x = gensquare(&n, seed, RND_GEN_X, secpar);
+
+        let mut bytes = vec![0u8; state_size(secpar)];
+        bytes[..2 + len].copy_from_slice(&mpk[..2 + len]);
+        mpi_export(&mut bytes[2 + len..2 + 2 * len], &x);
+        FsprgState { bytes }
+    }
+
+    /// Wraps a serialized state, such as the one following the header of an
+    /// `fss` file. Returns `None` if the length does not match the secpar.
+    pub fn from_bytes(bytes: &[u8]) -> Option<FsprgState> {
+        if bytes.len() < 2 || !is_valid_secpar(read_secpar(bytes)) || bytes.len() != state_size(read_secpar(bytes)) {
+            return None;
+        }
+        Some(FsprgState { bytes: bytes.to_vec() })
+    }
+
+    /// The state at `epoch`, computed from the master secret key. This can
+    /// go backwards as well as forwards.
+    pub fn seek(epoch: u64, msk: &[u8], seed: &[u8]) -> FsprgState {
+        let secpar = read_secpar(msk);
+        let half = secpar as usize / 2 / 8;
+        let len = secpar as usize / 8;
+        let p = mpi_import(&msk[2..2 + half]);
+        let q = mpi_import(&msk[2 + half..2 + 2 * half]);
+        let n = &p * &q;
+
+        let x = gensquare(&n, seed, RND_GEN_X, secpar);
+        let xp = (&x % &p).modpow(&twopowmodphi(epoch, &p), &p);
+        let xq = (&x % &q).modpow(&twopowmodphi(epoch, &q), &q);
+        let xm = crt_compose(&xp, &xq, &p, &q);
+
+        let mut bytes = vec![0u8; state_size(secpar)];
+        store_secpar(&mut bytes, secpar);
+        mpi_export(&mut bytes[2..2 + len], &n);
+        mpi_export(&mut bytes[2 + len..2 + 2 * len], &xm);
+        bytes[2 + 2 * len..].copy_from_slice(&epoch.to_be_bytes());
+        FsprgState { bytes }
+    }
+
+    pub fn secpar(&self) -> u16 {
+        read_secpar(&self.bytes)
+    }
+
+    pub fn epoch(&self) -> u64 {
+        let len = self.secpar() as usize / 8;
+        let mut epoch = [0u8; 8];
+        epoch.copy_from_slice(&self.bytes[2 + 2 * len..]);
+        u64::from_be_bytes(epoch)
+    }
+
+    /// Moves the state one epoch forward. There is no going back without the
+    /// master secret key.
+    pub fn evolve(&mut self) {
+        let len = self.secpar() as usize / 8;
+        let n = mpi_import(&self.bytes[2..2 + len]);
+        let x = mpi_import(&self.bytes[2 + len..2 + 2 * len]);
+        let x = (&x * &x) % &n;
+        let epoch = self.epoch() + 1;
+
+        mpi_export(&mut self.bytes[2 + len..2 + 2 * len], &x);
+        self.bytes[2 + 2 * len..].copy_from_slice(&epoch.to_be_bytes());
+    }
+
+    /// Derives `len` bytes of key material for the current epoch
+    pub fn key(&self, len: usize, idx: u32) -> Vec<u8> {
+        let mut key = vec![0u8; len];
+        det_randomize(&mut key, &self.bytes[2..], idx);
+        key
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl fmt::Debug for FsprgState {
+    // The state is key material, so keep it out of logs
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FsprgState")
+            .field("secpar", &self.secpar())
+            .field("epoch", &self.epoch())
+            .finish()
+    }
+}
@@ -0,0 +1,34 @@
+//! Selecting entries by their message like `journalctl -g`. Messages cannot
+//! be looked up through the hash tables, so the pattern is applied to each
+//! entry the other matches of a `Filter` selected.
+
+use regex::bytes::{Regex, RegexBuilder};
+use std::io::{Error, ErrorKind, Result};
+
+use crate::entry::Entry;
+use crate::fields::MESSAGE;
+
+/// A regular expression entries must have a matching `MESSAGE` for
+#[derive(Debug, Clone)]
+pub struct Grep {
+    regex: Regex,
+}
+
+impl Grep {
+    /// Compiles `pattern`. Like journalctl, it matches case-insensitively if
+    /// `case_sensitive` is `None` and the pattern has no uppercase letters.
+    pub fn new(pattern: &str, case_sensitive: Option<bool>) -> Result<Grep> {
+        let case_sensitive = case_sensitive.unwrap_or_else(|| pattern.chars().any(char::is_uppercase));
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(!case_sensitive)
+            .build()
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("Bad pattern \"{}\": {}", pattern, e)))?;
+        Ok(Grep { regex })
+    }
+
+    /// Whether `entry` has a message and it matches. Messages that are not
+    /// valid UTF-8 are matched byte by byte.
+    pub fn matches(&self, entry: &Entry) -> bool {
+        entry.get(MESSAGE).is_some_and(|message| self.regex.is_match(message))
+    }
+}
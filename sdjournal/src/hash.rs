@@ -1,3 +1,5 @@
+use std::convert::TryInto;
+
 pub fn rhash64(data: &[u8]) -> u64 {
     let (a, b) = hashlittle2(data, 0, 0);
     ((a as u64) << 32u64) | (b as u64)
@@ -806,9 +808,62 @@ pub fn hashlittle2(key: &[u8], pc: u32, pb: u32) -> (u32, u32) {
     (c, b)
 }
 
+#[inline]
+fn sipround(v: &mut [u64; 4]) {
+    v[0] = v[0].wrapping_add(v[1]);
+    v[1] = v[1].rotate_left(13);
+    v[1] ^= v[0];
+    v[0] = v[0].rotate_left(32);
+    v[2] = v[2].wrapping_add(v[3]);
+    v[3] = v[3].rotate_left(16);
+    v[3] ^= v[2];
+    v[0] = v[0].wrapping_add(v[3]);
+    v[3] = v[3].rotate_left(21);
+    v[3] ^= v[0];
+    v[2] = v[2].wrapping_add(v[1]);
+    v[1] = v[1].rotate_left(17);
+    v[1] ^= v[2];
+    v[2] = v[2].rotate_left(32);
+}
+
+/// siphash24 computes SipHash-2-4 of `data` with a 128-bit `key`
+///
+/// Journals with the keyed hash flag hash their payloads with this, keyed
+/// with the file id, instead of `rhash64` so that payloads colliding in the
+/// hash tables cannot be crafted.
+pub fn siphash24(data: &[u8], key: &[u8; 16]) -> u64 {
+    let k0 = u64::from_le_bytes(key[..8].try_into().unwrap());
+    let k1 = u64::from_le_bytes(key[8..].try_into().unwrap());
+    let mut v = [k0 ^ 0x736f6d6570736575, k1 ^ 0x646f72616e646f6d, k0 ^ 0x6c7967656e657261, k1 ^ 0x7465646279746573];
+
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        let m = u64::from_le_bytes(chunk.try_into().unwrap());
+        v[3] ^= m;
+        sipround(&mut v);
+        sipround(&mut v);
+        v[0] ^= m;
+    }
+
+    // The last block holds the remaining bytes and the length in its top byte
+    let mut last = [0u8; 8];
+    last[..chunks.remainder().len()].copy_from_slice(chunks.remainder());
+    let m = u64::from_le_bytes(last) | (data.len() as u64) << 56;
+    v[3] ^= m;
+    sipround(&mut v);
+    sipround(&mut v);
+    v[0] ^= m;
+
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        sipround(&mut v);
+    }
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{rhash64, hashlittle, hashlittle2};
+    use super::{rhash64, hashlittle, hashlittle2, siphash24};
 
     #[test]
     fn test_hash_field_obj_payload() {
@@ -894,4 +949,16 @@ mod tests {
         h = hashlittle(&buf[0..0], h);
         assert_eq!(h, 0xf56df778);
     }
+
+    #[test]
+    fn test_siphash24() {
+        // Test vectors from the SipHash paper's reference implementation
+        let key: [u8; 16] = std::array::from_fn(|i| i as u8);
+        let data: Vec<u8> = (0..64).collect();
+        assert_eq!(siphash24(&data[..0], &key), 0x726fdb47dd0e0e31);
+        assert_eq!(siphash24(&data[..7], &key), 0xab0200f58b01d137);
+        assert_eq!(siphash24(&data[..8], &key), 0x93f5f5799a932462);
+        assert_eq!(siphash24(&data[..15], &key), 0xa129ca6149be45e5);
+        assert_eq!(siphash24(&data[..63], &key), 0x958a324ceb064572);
+    }
 }
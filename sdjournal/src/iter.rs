@@ -2,6 +2,7 @@ use byteorder::{LittleEndian, ReadBytesExt};
 use std::convert::TryInto;
 use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom};
 use std::collections::VecDeque;
+use std::ops::Range;
 
 pub use crate::journal::*;
 
@@ -187,12 +188,102 @@ where
     }
 }
 
+/// The offsets held by a chain of entry arrays, looked up by their index.
+/// Only the headers of the arrays are read up front; as each array holds
+/// twice as many offsets as the one before, there are few of them.
+pub(crate) struct EntryArrayChain<'a, T>
+where
+    &'a T: Read + Seek,
+{
+    buf: &'a T,
+    /// The offset of each array and the index of its first item
+    arrays: Vec<(ObjectOffset, u64)>,
+    len: u64,
+}
+
+impl<'a, T> EntryArrayChain<'a, T>
+where
+    &'a T: Read + Seek,
+{
+    /// The chain starting at `start` holding the first `n_items` offsets
+    pub(crate) fn new(mut buf: &'a T, start: ObjectOffset, n_items: u64) -> Result<EntryArrayChain<'a, T>> {
+        let mut arrays = Vec::new();
+        let mut index = 0;
+        let mut offset = start;
+        while offset != 0 && index < n_items {
+            buf.seek(SeekFrom::Start(offset))?;
+            let type_ = buf.read_u8()?;
+            buf.seek(SeekFrom::Start(offset + 8))?;
+            let size = buf.read_u64::<LittleEndian>()?;
+            let min_size = OBJECT_HEADER_SZ + ENTRY_ARRAY_OBJECT_HEADER_SZ;
+            // Empty arrays would let a damaged chain loop forever
+            if type_ != ObjectType::ObjectEntryArray as u8 || !is_valid_object_size(ObjectType::ObjectEntryArray, size)
+                || size == min_size {
+                return Err(Error::new(ErrorKind::InvalidData, format!("Invalid entry array at offset {:#x}", offset)));
+            }
+            arrays.push((offset, index));
+            index += (size - min_size) / 8;
+            offset = buf.read_u64::<LittleEndian>()?;
+        }
+        Ok(EntryArrayChain { buf, arrays, len: index.min(n_items) })
+    }
+
+    pub(crate) fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// The offset at `index`, which must be less than `len`
+    pub(crate) fn get(&self, index: u64) -> Result<ObjectOffset> {
+        let (offset, first) = self.arrays[self.arrays.partition_point(|&(_, first)| first <= index) - 1];
+        let mut buf = self.buf;
+        buf.seek(SeekFrom::Start(offset + OBJECT_HEADER_SZ + ENTRY_ARRAY_OBJECT_HEADER_SZ + (index - first) * 8))?;
+        buf.read_u64::<LittleEndian>()
+    }
+}
+
+/// The offsets of the entries an `EntryIter` walks, either listed or read
+/// from a range of an entry array chain as they are reached
+pub(crate) enum EntryOffsets<'a, T>
+where
+    &'a T: Read + Seek,
+{
+    Listed(VecDeque<ObjectOffset>),
+    Chain(EntryArrayChain<'a, T>, Range<u64>),
+}
+
+impl<'a, T> EntryOffsets<'a, T>
+where
+    &'a T: Read + Seek,
+{
+    fn pop_front(&mut self) -> Option<Result<ObjectOffset>> {
+        match self {
+            EntryOffsets::Listed(offsets) => offsets.pop_front().map(Ok),
+            EntryOffsets::Chain(chain, range) => range.next().map(|i| chain.get(i)),
+        }
+    }
+
+    fn pop_back(&mut self) -> Option<Result<ObjectOffset>> {
+        match self {
+            EntryOffsets::Listed(offsets) => offsets.pop_back().map(Ok),
+            EntryOffsets::Chain(chain, range) => range.next_back().map(|i| chain.get(i)),
+        }
+    }
+
+    /// All remaining offsets, in order
+    pub(crate) fn into_vec(self) -> Result<Vec<ObjectOffset>> {
+        match self {
+            EntryOffsets::Listed(offsets) => Ok(offsets.into()),
+            EntryOffsets::Chain(chain, range) => range.map(|i| chain.get(i)).collect(),
+        }
+    }
+}
+
 pub struct EntryIter<'a, T>
 where
     &'a T: Read + Seek,
 {
     buf: &'a T,
-    offsets: VecDeque<u64>,
+    offsets: EntryOffsets<'a, T>,
 }
 
 impl<'a, T> EntryIter<'a, T>
@@ -212,7 +303,18 @@ where
 
         EntryIter {
             buf: buf,
-            offsets: offsets,
+            offsets: EntryOffsets::Listed(offsets),
+        }
+    }
+
+    pub(crate) fn from_offsets(buf: &'a T, offsets: EntryOffsets<'a, T>) -> EntryIter<'a, T> {
+        EntryIter { buf, offsets }
+    }
+
+    fn read_entry(&mut self, offset: Result<u64>) -> Result<EntryObject> {
+        match get_obj_at_offset(&mut self.buf, offset?)? {
+            Object::Entry(e) => Ok(e),
+            _ => Err(Error::new(ErrorKind::InvalidData, "Entry array points to a non-entry object")),
         }
     }
 }
@@ -221,15 +323,148 @@ impl<'a, T> Iterator for EntryIter<'a, T>
 where
     &'a T: Read + Seek,
 {
-    type Item = EntryObject;
+    type Item = Result<EntryObject>;
 
-    fn next(&mut self) -> Option<EntryObject> {
+    fn next(&mut self) -> Option<Result<EntryObject>> {
         let offset = self.offsets.pop_front()?;
-        let entry = get_obj_at_offset(&mut self.buf, offset).unwrap();
-        if let Object::Entry(e) = entry {
-            return Some(e);
-        } else {
+        Some(self.read_entry(offset))
+    }
+}
+
+/// Walks the entries from the newest, reading only those it reaches
+impl<'a, T> DoubleEndedIterator for EntryIter<'a, T>
+where
+    &'a T: Read + Seek,
+{
+    fn next_back(&mut self) -> Option<Result<EntryObject>> {
+        let offset = self.offsets.pop_back()?;
+        Some(self.read_entry(offset))
+    }
+}
+
+/// What `SalvageIter` found at a position of the arena
+#[derive(Debug, PartialEq)]
+pub enum Salvaged {
+    /// An object that could be read, with its offset
+    Object(ObjectOffset, Object),
+    /// An ENTRY object with the offset and hash of each of its items. Their
+    /// DATA objects are not read, so one damaged object shared by many
+    /// entries does not hide them and the caller decides what to keep.
+    Entry(ObjectOffset, EntryObject, Vec<(ObjectOffset, u64)>),
+    /// Bytes from `start` up to `end` in which no readable object was found
+    Skipped { start: u64, end: u64 },
+}
+
+/// Walks all objects of the arena like `ObjectIter`, but instead of stopping
+/// at the first object that cannot be read it scans forward on 8 byte
+/// boundaries for the next plausible object header and carries on from
+/// there, reporting the bytes it skipped.
+pub struct SalvageIter<'a, T>
+where
+    &'a T: Read + Seek,
+{
+    buf: &'a T,
+    next_offset: u64,
+    end: u64,
+    /// Start of the damaged range being skipped, and whether it has been
+    /// all zeroes so far
+    skip: Option<(u64, bool)>,
+    /// An object found right after a damaged range, returned after the range
+    pending: Option<Salvaged>,
+}
+
+impl<'a, T> SalvageIter<'a, T>
+where
+    &'a T: Read + Seek,
+{
+    pub(crate) fn new(mut buf: &'a T, start: u64, end: u64) -> SalvageIter<'a, T> {
+        let file_size = buf.seek(SeekFrom::End(0)).unwrap_or(0);
+        let end = if end <= start || end > file_size { file_size } else { end };
+
+        SalvageIter {
+            buf,
+            next_offset: start,
+            end,
+            skip: None,
+            pending: None,
+        }
+    }
+
+    /// Reads the object at `offset` if its header is plausible and the whole
+    /// object can be parsed, returning it with its size
+    fn read_plausible(&mut self, offset: u64) -> Option<(Salvaged, u64)> {
+        let mut header = [0u8; OBJECT_HEADER_SZ as usize];
+        self.buf.seek(SeekFrom::Start(offset)).ok()?;
+        self.buf.read_exact(&mut header).ok()?;
+
+        let type_ = ObjectType::from_u8(header[0]);
+        let size = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        let valid_flags = match type_ {
+            ObjectType::ObjectData => header[1] & !OBJECT_COMPRESSED_MASK == 0,
+            _ => header[1] == 0,
+        };
+        // Stray bytes rarely have both the reserved bytes zeroed and a size
+        // that fits the type
+        if !valid_flags || header[2..8] != [0u8; 6] || !is_valid_object_size(type_, size)
+            || offset.checked_add(size).is_none_or(|end| end > self.end) {
             return None;
         }
+
+        let salvaged = match type_ {
+            ObjectType::ObjectEntry => {
+                let (entry, items) = get_entry_at_offset(&mut self.buf, offset).ok()?;
+                Salvaged::Entry(offset, entry, items)
+            }
+            _ => Salvaged::Object(offset, get_obj_at_offset(&mut self.buf, offset).ok()?),
+        };
+        Some((salvaged, size))
+    }
+
+    fn is_zero(&mut self, offset: u64) -> bool {
+        let mut word = [0u8; 8];
+        self.buf.seek(SeekFrom::Start(offset)).is_ok() && self.buf.read_exact(&mut word).is_ok() && word == [0u8; 8]
+    }
+}
+
+impl<'a, T> Iterator for SalvageIter<'a, T>
+where
+    &'a T: Read + Seek,
+{
+    type Item = Salvaged;
+
+    fn next(&mut self) -> Option<Salvaged> {
+        if let Some(salvaged) = self.pending.take() {
+            return Some(salvaged);
+        }
+
+        while self.next_offset < self.end {
+            let offset = self.next_offset;
+            match self.read_plausible(offset) {
+                Some((salvaged, size)) => {
+                    self.next_offset = align64(offset + size);
+                    match self.skip.take() {
+                        Some((start, _)) => {
+                            self.pending = Some(salvaged);
+                            return Some(Salvaged::Skipped { start, end: offset });
+                        }
+                        None => return Some(salvaged),
+                    }
+                }
+                None => {
+                    let zero = self.is_zero(offset);
+                    self.skip = match self.skip {
+                        Some((start, all_zero)) => Some((start, all_zero && zero)),
+                        None => Some((offset, zero)),
+                    };
+                    self.next_offset = offset + 8;
+                }
+            }
+        }
+
+        // Zeroes up to the end are space allocated for objects to come
+        match self.skip.take() {
+            Some((start, false)) => Some(Salvaged::Skipped { start, end: self.end }),
+            _ => None,
+        }
     }
 }
@@ -1,26 +1,53 @@
-use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::borrow::Cow;
+use std::cmp;
 use std::fmt;
 use std::str;
-use std::io::{Cursor, Error, ErrorKind, Read, Result, Seek, SeekFrom};
+use std::io::{Cursor, Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
 use std::convert::TryInto;
 
+use crate::boot::{self, Boot};
+use crate::compress;
+use crate::entry::Entry;
 use crate::iter::*;
 use crate::traits::{SizedObject, HashableObject};
-use crate::hash::rhash64;
+use crate::filter::{self, Filter};
+use crate::hash::{rhash64, siphash24};
+use crate::seal::{self, SealReport, VerificationKey};
+use crate::time::{format_timespan, format_timestamp, USEC_PER_MSEC};
+use crate::verify::{self, VerifyReport};
 
 // TODO: compression support
 // TODO: Result/Error type
-// TODO: work on entrt struct to allow for propper formatting of entries
 
 /// Code relating to the jounral structure goes here.
 
+pub const HEADER_SIGNATURE: &[u8; 8] = b"LPKSHHRH";
+pub const HEADER_SZ: u64 = 240;
+/// Size of the header with every field this crate knows, as systemd 254
+/// writes it
+pub const MAX_HEADER_SZ: u64 = 272;
+
 pub const OBJECT_HEADER_SZ: u64 = 16;
 pub const DATA_OBJECT_HEADER_SZ: u64 = 48;
 pub const FIELD_OBJECT_HEADER_SZ: u64 = 24;
+pub const ENTRY_OBJECT_HEADER_SZ: u64 = 48;
+pub const ENTRY_ARRAY_OBJECT_HEADER_SZ: u64 = 8;
+pub const ENTRY_ITEM_SZ: u64 = 16;
+pub const HASH_ITEM_SZ: u64 = 16;
+
+pub const HEADER_COMPATIBLE_SEALED: u32 = 1 << 0;
+
+pub const HEADER_INCOMPATIBLE_COMPRESSED_XZ: u32 = 1 << 0;
+pub const HEADER_INCOMPATIBLE_COMPRESSED_LZ4: u32 = 1 << 1;
+pub const HEADER_INCOMPATIBLE_KEYED_HASH: u32 = 1 << 2;
+pub const HEADER_INCOMPATIBLE_COMPRESSED_ZSTD: u32 = 1 << 3;
+pub const HEADER_INCOMPATIBLE_COMPACT: u32 = 1 << 4;
 
 pub const OBJECT_COMPRESSED_XZ: u8 = 1 << 0;
 pub const OBJECT_COMPRESSED_LZ4: u8 = 1 << 1;
-pub const OBJECT_COMPRESSED_MASK: u8 = OBJECT_COMPRESSED_XZ | OBJECT_COMPRESSED_LZ4;
+pub const OBJECT_COMPRESSED_ZSTD: u8 = 1 << 2;
+pub const OBJECT_COMPRESSED_MASK: u8 = OBJECT_COMPRESSED_XZ | OBJECT_COMPRESSED_LZ4 | OBJECT_COMPRESSED_ZSTD;
 
 pub const TAG_LENGTH: usize = (256 / 8);
 
@@ -36,6 +63,39 @@ pub(crate) fn align64(u: u64) -> u64 {
     (u + 7u64) & !7u64
 }
 
+/// Reads the bytes of the header at the current position of `file` that
+/// `JournalHeader::new` parses, including the fields newer headers go on with
+pub(crate) fn read_header_buf<R: Read>(file: &mut R) -> Result<Vec<u8>> {
+    let mut buf = vec![0; HEADER_SZ as usize];
+    file.read_exact(&mut buf)?;
+    let header_size = u64::from_le_bytes(buf[88..96].try_into().unwrap());
+    let size = cmp::min(header_size, MAX_HEADER_SZ);
+    if size > HEADER_SZ {
+        buf.resize(size as usize, 0);
+        file.read_exact(&mut buf[HEADER_SZ as usize..])?;
+    }
+    Ok(buf)
+}
+
+/// Names of the compatible flags as `journalctl --header` shows them
+const COMPATIBLE_FLAG_NAMES: &[(u32, &str)] = &[(HEADER_COMPATIBLE_SEALED, "SEALED")];
+
+/// Names of the incompatible flags as `journalctl --header` shows them
+const INCOMPATIBLE_FLAG_NAMES: &[(u32, &str)] = &[
+    (HEADER_INCOMPATIBLE_COMPRESSED_XZ, "COMPRESSED-XZ"),
+    (HEADER_INCOMPATIBLE_COMPRESSED_LZ4, "COMPRESSED-LZ4"),
+    (HEADER_INCOMPATIBLE_COMPRESSED_ZSTD, "COMPRESSED-ZSTD"),
+    (HEADER_INCOMPATIBLE_KEYED_HASH, "KEYED-HASH"),
+    (HEADER_INCOMPATIBLE_COMPACT, "COMPACT"),
+];
+
+/// Size of the header systemd 252 writes. Files with smaller headers lack
+/// newer features, so rotating them is suggested.
+const CURRENT_HEADER_SZ: u64 = 264;
+
+/// Hash chains longer than this suggest someone is forcing hash collisions
+const HASH_CHAIN_DEPTH_MAX: u64 = 100;
+
 #[derive(Debug)]
 pub struct Journal<T>
 where
@@ -51,9 +111,7 @@ where
     &'a T: Read + Seek,
 {
     pub fn new(mut bytes: T) -> Result<Journal<T>> {
-        let mut header_buf = vec![0; 240];
-        bytes.read_exact(&mut header_buf)?;
-        let header_buf = Cursor::new(header_buf);
+        let header_buf = Cursor::new(read_header_buf(&mut bytes)?);
 
         let header = JournalHeader::new(header_buf)?;
 
@@ -74,31 +132,237 @@ where
         ObjectHeaderIter::new(&self.file, start)
     }
 
-    /// Iterate over all entry objects in the journal
-    pub fn iter_entries(&'a self) -> EntryIter<'a, T> {
+    /// Iterate over all entry objects in the journal. Stops at the first
+    /// entry that cannot be read; `entries` returns the error instead.
+    pub fn iter_entries(&'a self) -> impl Iterator<Item = EntryObject> + 'a {
+        self.entry_objects().map_while(Result::ok)
+    }
+
+    fn entry_objects(&'a self) -> EntryIter<'a, T> {
         let start = self.header.entry_array_offset;
         let n_objects = self.header.n_objects;
         EntryIter::new(&self.file, start, n_objects)
     }
 
+    /// Iterate over all entries in the journal with their fields split for
+    /// lookup
+    pub fn entries(&'a self) -> impl Iterator<Item = Result<Entry>> + 'a {
+        self.entry_objects().map(move |e| e.and_then(|e| Entry::new(&self.header, e)))
+    }
+
+    /// Iterate over the entries selected by `filter`, in the same order as
+    /// `entries`. Reversed, it starts from the newest entry without reading
+    /// the others.
+    pub fn entries_matching(&'a self, filter: &Filter)
+        -> Result<impl DoubleEndedIterator<Item = Result<Entry>> + 'a> {
+        let offsets = filter::entry_offsets(self, filter)?;
+        Ok(EntryIter::from_offsets(&self.file, offsets).map(move |e| e.and_then(|e| Entry::new(&self.header, e))))
+    }
+
+    /// Walks the hash chain of the bucket for `hash` in the table at
+    /// `table_offset` and returns the first object for which `matches` is true
+    fn find_in_hash_table<F>(&'a self, table_offset: u64, table_size: u64, hash: u64, matches: F) -> Result<Option<Object>>
+    where
+        F: Fn(&Object) -> bool,
+    {
+        let n_buckets = table_size / HASH_ITEM_SZ;
+        if n_buckets == 0 {
+            return Ok(None);
+        }
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(table_offset + (hash % n_buckets) * HASH_ITEM_SZ))?;
+        let mut offset = file.read_u64::<LittleEndian>()?;
+        // A damaged chain could loop, but never legitimately has more links
+        // than there are objects
+        for _ in 0..self.header.n_objects {
+            if offset == 0 {
+                break;
+            }
+            let object = get_obj_at_offset(&mut file, offset)?;
+            if matches(&object) {
+                return Ok(Some(object));
+            }
+            offset = match object {
+                Object::Data(d) => d.next_hash_offset,
+                Object::Field(f) => f.next_hash_offset,
+                _ => return Err(Error::new(ErrorKind::InvalidData, "Unexpected object in hash chain")),
+            };
+        }
+        Ok(None)
+    }
+
+    /// The number of objects in the longest chain of the data hash table and
+    /// of the field hash table, which headers before systemd 246 do not record
+    pub fn hash_chain_depths(&'a self) -> Result<(u64, u64)> {
+        let header = &self.header;
+        let data = self.deepest_hash_chain(header.data_hash_table_offset, header.data_hash_table_size)?;
+        let field = self.deepest_hash_chain(header.field_hash_table_offset, header.field_hash_table_size)?;
+        Ok((data, field))
+    }
+
+    fn deepest_hash_chain(&'a self, table_offset: u64, table_size: u64) -> Result<u64> {
+        let mut file = &self.file;
+        let mut deepest = 0;
+        for bucket in 0..table_size / HASH_ITEM_SZ {
+            file.seek(SeekFrom::Start(table_offset + bucket * HASH_ITEM_SZ))?;
+            let mut offset = file.read_u64::<LittleEndian>()?;
+            let mut depth = 0;
+            // A damaged chain could loop, but never legitimately has more
+            // links than there are objects
+            while offset != 0 && depth < self.header.n_objects {
+                // DATA and FIELD objects both link the next object in the
+                // chain right after their hash
+                file.seek(SeekFrom::Start(offset + OBJECT_HEADER_SZ + 8))?;
+                offset = file.read_u64::<LittleEndian>()?;
+                depth += 1;
+            }
+            deepest = cmp::max(deepest, depth);
+        }
+        Ok(deepest)
+    }
+
+    /// Looks up the DATA object with exactly `payload` through the data hash
+    /// table
+    pub fn find_data(&'a self, payload: &[u8]) -> Result<Option<DataObject>> {
+        let hash = self.header.hash_payload(payload);
+        let table = (self.header.data_hash_table_offset, self.header.data_hash_table_size);
+        let found = self.find_in_hash_table(table.0, table.1, hash, |o| match o {
+            Object::Data(d) => d.hash == hash && d.decompressed_payload().is_ok_and(|p| *p == *payload),
+            _ => false,
+        })?;
+        match found {
+            Some(Object::Data(d)) => Ok(Some(d)),
+            _ => Ok(None),
+        }
+    }
+
+    /// The DATA objects of the field `field`, one for each distinct value it
+    /// has in the journal, found through the field hash table
+    pub fn unique_values(&'a self, field: &str) -> Result<Vec<DataObject>> {
+        let hash = self.header.hash_payload(field.as_bytes());
+        let table = (self.header.field_hash_table_offset, self.header.field_hash_table_size);
+        let found = self.find_in_hash_table(table.0, table.1, hash, |o| match o {
+            Object::Field(f) => f.hash == hash && f.payload == field.as_bytes(),
+            _ => false,
+        })?;
+        let mut offset = match found {
+            Some(Object::Field(f)) => f.head_data_offset,
+            _ => return Ok(Vec::new()),
+        };
+
+        let mut values = Vec::new();
+        let mut file = &self.file;
+        while offset != 0 && (values.len() as u64) < self.header.n_objects {
+            let data = match get_obj_at_offset(&mut file, offset)? {
+                Object::Data(d) => d,
+                _ => return Err(Error::new(ErrorKind::InvalidData, "Unexpected object in field data chain")),
+            };
+            offset = data.next_field_offset;
+            values.push(data);
+        }
+        Ok(values)
+    }
+
+    /// Offsets of all entries that reference `data`, in the order they were
+    /// written
+    pub fn data_entry_offsets(&'a self, data: &DataObject) -> Result<Vec<ObjectOffset>> {
+        if data.n_entries == 0 || data.entry_offset == 0 {
+            return Ok(Vec::new());
+        }
+        let mut offsets = vec![data.entry_offset];
+        // The first entry is linked directly, the others through the array chain
+        let rest = EntryArrayIter::new(&self.file, data.entry_array_offset)
+            .flat_map(|ea| ea.items)
+            .filter(|&offset| offset != 0)
+            .take((data.n_entries - 1) as usize);
+        offsets.extend(rest);
+        Ok(offsets)
+    }
+
     pub fn ea_iter(&'a self) -> EntryArrayIter<'a, T> {
         let start = self.header.entry_array_offset;
         EntryArrayIter::new(&self.file, start)
     }
 
-    // TODO: add more tests in verify
-    pub fn verify(&'a self) -> bool {
-        for obj in self.obj_iter() {
-            if let Object::Data(d) = obj {
-                let stored_hash = d.hash;
-                let calc_hash = d.hash();
-                if stored_hash != calc_hash {
-                    return false;
-                }
-            }
-        }
-        true
+    /// Iterate over all objects in the journal, skipping over damaged parts
+    /// instead of stopping at them
+    pub fn salvage_iter(&'a self) -> SalvageIter<'a, T> {
+        let start = if self.header.header_size >= HEADER_SZ && is_valid64(self.header.header_size) {
+            self.header.header_size
+        } else {
+            HEADER_SZ
+        };
+        let end = start.saturating_add(self.header.arena_size);
+        SalvageIter::new(&self.file, start, end)
+    }
+
+    /// The boots the journal has entries of, oldest first
+    pub fn boots(&'a self) -> Result<Vec<Boot>> {
+        Ok(boot::list_boots(boot::boot_spans(self)?))
+    }
+
+    /// Checks the journal for corruption and lists every problem found
+    pub fn verify(&'a self) -> VerifyReport {
+        let mut file = &self.file;
+        verify::verify(&mut file, &self.header)
     }
+
+    /// Verify the Forward Secure Sealing tags of the journal with the
+    /// verification key printed by `journalctl --setup-keys`
+    pub fn verify_seal(&'a self, key: &VerificationKey) -> Result<SealReport> {
+        let mut file = &self.file;
+        seal::verify_seal(&mut file, &self.header, key)
+    }
+}
+
+/// Reads the ENTRY object at `offset` without reading the DATA objects its
+/// items point to. The entry is returned without items, next to the offset
+/// and hash of each of them.
+pub fn get_entry_at_offset<T: Read + Seek>(file: &mut T, offset: u64) -> Result<(EntryObject, Vec<(ObjectOffset, u64)>)> {
+    if !is_valid64(offset) {
+        return Err(Error::new(ErrorKind::InvalidData, "Invalid offset"));
+    }
+
+    file.seek(SeekFrom::Start(offset))?;
+    let type_ = file.read_u8()?;
+    let flags = file.read_u8()?;
+    let mut reserved = [0u8; 6];
+    file.read_exact(&mut reserved)?;
+    let size = file.read_u64::<LittleEndian>()?;
+    if type_ != ObjectType::ObjectEntry as u8 {
+        return Err(Error::new(ErrorKind::InvalidData, "Not an entry object"));
+    }
+    if !is_valid_object_size(ObjectType::ObjectEntry, size) {
+        return Err(Error::new(ErrorKind::InvalidData, "Invalid object size"));
+    }
+    let object_header = ObjectHeader {
+        type_: ObjectType::ObjectEntry,
+        flags,
+        reserved,
+        size,
+    };
+    let seqnum = file.read_u64::<LittleEndian>()?;
+    let realtime = file.read_u64::<LittleEndian>()?;
+    let monotonic = file.read_u64::<LittleEndian>()?;
+    let boot_id = file.read_u128::<BigEndian>()?;
+    let xor_hash = file.read_u64::<LittleEndian>()?;
+    let n_items = (size - OBJECT_HEADER_SZ - ENTRY_OBJECT_HEADER_SZ) / ENTRY_ITEM_SZ;
+    let mut items = Vec::with_capacity(n_items.try_into().unwrap());
+    for _ in 0..n_items {
+        let object_offset = file.read_u64::<LittleEndian>()?;
+        let hash = file.read_u64::<LittleEndian>()?;
+        items.push((object_offset, hash));
+    }
+    let entry_object = EntryObject {
+        object: object_header,
+        seqnum,
+        realtime,
+        monotonic,
+        boot_id,
+        xor_hash,
+        items: Vec::with_capacity(items.len()),
+    };
+    Ok((entry_object, items))
 }
 
 pub fn get_obj_at_offset<T: Read + Seek>(file: &mut T, offset: u64) -> Result<Object> {
@@ -108,8 +372,15 @@ pub fn get_obj_at_offset<T: Read + Seek>(file: &mut T, offset: u64) -> Result<Ob
         return Err(Error::new(ErrorKind::Other, "Invalid offset"));
     }
 
+    file.seek(SeekFrom::Start(offset + 8))?;
+    let size = file.read_u64::<LittleEndian>()?;
     file.seek(SeekFrom::Start(offset))?;
     let type_ = file.read_u8()?;
+    // Damaged sizes would otherwise make the parsing below underflow or
+    // allocate absurd amounts of memory
+    if (1..=7).contains(&type_) && !is_valid_object_size(ObjectType::from_u8(type_), size) {
+        return Err(Error::new(ErrorKind::InvalidData, "Invalid object size"));
+    }
     match type_ {
         0 => return Err(Error::new(ErrorKind::Other, "Unused Object")),
         1 => {
@@ -171,45 +442,16 @@ pub fn get_obj_at_offset<T: Read + Seek>(file: &mut T, offset: u64) -> Result<Ob
             return Ok(Object::Field(field_object));
         }
         3 => {
-            let flags = file.read_u8()?;
-            let mut reserved = [0u8; 6];
-            file.read_exact(&mut reserved)?;
-            let size = file.read_u64::<LittleEndian>()?;
-            let object_header = ObjectHeader {
-                type_: ObjectType::ObjectEntry,
-                flags: flags,
-                reserved: reserved,
-                size: size,
-            };
-            let seqnum = file.read_u64::<LittleEndian>()?;
-            let realtime = file.read_u64::<LittleEndian>()?;
-            let monotonic = file.read_u64::<LittleEndian>()?;
-            let boot_id = file.read_u128::<LittleEndian>()?;
-            let xor_hash = file.read_u64::<LittleEndian>()?;
-            let mut items: Vec<EntryItem> =
-                Vec::with_capacity(((size - 48) / 16).try_into().unwrap());
-            for _ in 1..((size - 48) / 16) {
-                let object_offset = file.read_u64::<LittleEndian>()?;
-                let hash = file.read_u64::<LittleEndian>()?;
-                let saved_offset = file.seek(SeekFrom::Current(0))?;
+            let (mut entry_object, items) = get_entry_at_offset(file, offset)?;
+            for (object_offset, hash) in items {
                 let item_obj = get_obj_at_offset(file, object_offset)?;
-                file.seek(SeekFrom::Start(saved_offset))?;
                 let item = EntryItem {
-                    object_offset: object_offset,
-                    hash: hash,
+                    object_offset,
+                    hash,
                     item: item_obj,
                 };
-                items.push(item);
+                entry_object.items.push(item);
             }
-            let entry_object = EntryObject {
-                object: object_header,
-                seqnum: seqnum,
-                realtime: realtime,
-                monotonic: monotonic,
-                boot_id: boot_id,
-                xor_hash: xor_hash,
-                items: items,
-            };
             return Ok(Object::Entry(entry_object));
         }
         4 => {
@@ -224,8 +466,8 @@ pub fn get_obj_at_offset<T: Read + Seek>(file: &mut T, offset: u64) -> Result<Ob
                 size: size,
             };
             let mut items: Vec<HashItem> =
-                Vec::with_capacity(((size - 48) / 16).try_into().unwrap());
-            for _ in 0..((size - 48) / 16) {
+                Vec::with_capacity(((size - OBJECT_HEADER_SZ) / HASH_ITEM_SZ).try_into().unwrap());
+            for _ in 0..((size - OBJECT_HEADER_SZ) / HASH_ITEM_SZ) {
                 let hash_head_offset = file.read_u64::<LittleEndian>()?;
                 let tail_hash_offset = file.read_u64::<LittleEndian>()?;
                 let item = HashItem {
@@ -252,8 +494,8 @@ pub fn get_obj_at_offset<T: Read + Seek>(file: &mut T, offset: u64) -> Result<Ob
                 size: size,
             };
             let mut items: Vec<HashItem> =
-                Vec::with_capacity(((size - 48) / 16).try_into().unwrap());
-            for _ in 0..((size - 48) / 16) {
+                Vec::with_capacity(((size - OBJECT_HEADER_SZ) / HASH_ITEM_SZ).try_into().unwrap());
+            for _ in 0..((size - OBJECT_HEADER_SZ) / HASH_ITEM_SZ) {
                 let hash_head_offset = file.read_u64::<LittleEndian>()?;
                 let tail_hash_offset = file.read_u64::<LittleEndian>()?;
                 let item = HashItem {
@@ -322,7 +564,7 @@ pub fn get_obj_at_offset<T: Read + Seek>(file: &mut T, offset: u64) -> Result<Ob
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum JournalState {
     Offline,
     Online,
@@ -330,13 +572,25 @@ pub enum JournalState {
     StateMax,
 }
 
+impl JournalState {
+    /// The on-disk representation of the state
+    pub fn to_u8(&self) -> u8 {
+        match *self {
+            JournalState::Offline => 0,
+            JournalState::Online => 1,
+            JournalState::Archived => 2,
+            JournalState::StateMax => 3,
+        }
+    }
+}
+
 impl fmt::Display for JournalState {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             JournalState::Offline => write!(f, "OFFLINE"),
             JournalState::Online => write!(f, "ONLINE"),
             JournalState::Archived => write!(f, "ARCHIVED"),
-            JournalState::StateMax => write!(f, "MAX"),
+            JournalState::StateMax => write!(f, "UNKNOWN"),
         }
     }
 }
@@ -384,7 +638,7 @@ impl SizedObject for Object {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ObjectType {
     ObjectUnused = 0,
     ObjectData = 1,
@@ -397,6 +651,61 @@ pub enum ObjectType {
     ObjectTypeMax,
 }
 
+impl ObjectType {
+    pub fn from_u8(type_: u8) -> ObjectType {
+        match type_ {
+            0 => ObjectType::ObjectUnused,
+            1 => ObjectType::ObjectData,
+            2 => ObjectType::ObjectField,
+            3 => ObjectType::ObjectEntry,
+            4 => ObjectType::ObjectDataHashTable,
+            5 => ObjectType::ObjectFieldHashTable,
+            6 => ObjectType::ObjectEntryArray,
+            7 => ObjectType::ObjectTag,
+            _ => ObjectType::ObjectTypeMax,
+        }
+    }
+}
+
+impl fmt::Display for ObjectType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ObjectType::ObjectUnused => write!(f, "unused"),
+            ObjectType::ObjectData => write!(f, "data"),
+            ObjectType::ObjectField => write!(f, "field"),
+            ObjectType::ObjectEntry => write!(f, "entry"),
+            ObjectType::ObjectDataHashTable => write!(f, "data hash table"),
+            ObjectType::ObjectFieldHashTable => write!(f, "field hash table"),
+            ObjectType::ObjectEntryArray => write!(f, "entry array"),
+            ObjectType::ObjectTag => write!(f, "tag"),
+            ObjectType::ObjectTypeMax => write!(f, "unknown"),
+        }
+    }
+}
+
+/// The minimum size of an object of type `type_` and the size of the items
+/// following that minimum, or `None` for types that are never valid
+pub(crate) fn object_size_layout(type_: ObjectType) -> Option<(u64, u64)> {
+    match type_ {
+        ObjectType::ObjectData => Some((OBJECT_HEADER_SZ + DATA_OBJECT_HEADER_SZ, 1)),
+        ObjectType::ObjectField => Some((OBJECT_HEADER_SZ + FIELD_OBJECT_HEADER_SZ, 1)),
+        ObjectType::ObjectEntry => Some((OBJECT_HEADER_SZ + ENTRY_OBJECT_HEADER_SZ, ENTRY_ITEM_SZ)),
+        ObjectType::ObjectDataHashTable | ObjectType::ObjectFieldHashTable => Some((OBJECT_HEADER_SZ, HASH_ITEM_SZ)),
+        ObjectType::ObjectEntryArray => Some((OBJECT_HEADER_SZ + ENTRY_ARRAY_OBJECT_HEADER_SZ, 8)),
+        ObjectType::ObjectTag => Some((OBJECT_HEADER_SZ + 16 + TAG_LENGTH as u64, 0)),
+        _ => None,
+    }
+}
+
+/// Whether `size` is a possible size for an object of type `type_`
+pub(crate) fn is_valid_object_size(type_: ObjectType, size: u64) -> bool {
+    match object_size_layout(type_) {
+        Some((min, 0)) => size == min,
+        Some((min, item)) => size >= min && (size - min).is_multiple_of(item),
+        None => false,
+    }
+}
+
 /// The common object header for any object
 #[derive(Debug, PartialEq)]
 pub struct ObjectHeader {
@@ -446,6 +755,11 @@ impl DataObject {
     pub fn payload_is_trusted(&self) -> bool {
         0x5f == self.payload[0]
     }
+
+    /// The `FIELD=value` payload, decompressed if the object is compressed
+    pub fn decompressed_payload(&self) -> Result<Cow<'_, [u8]>> {
+        compress::decompress(self.object.flags, &self.payload)
+    }
 }
 
 impl HashableObject for DataObject {
@@ -494,16 +808,17 @@ pub struct EntryObject {
 }
 
 impl EntryObject {
-    pub fn get_data<T: Read + Seek>(&self, key: &str, buf: &mut T) -> Option<String> {
-        for item in self.items.iter() {
-            let obj = get_obj_at_offset(buf, item.object_offset).ok()?;
-            if let Object::Data(o) = obj {
-                if key.as_bytes() == &o.payload[..key.len()] {
-                    return Some(str::from_utf8(&o.payload[key.len()..]).unwrap().to_owned());
-                }
+    /// The value of the field `key`, which may end with `=`, if it is UTF-8
+    #[deprecated(note = "use `Entry::get_str`, which does not need to read the file")]
+    pub fn get_data<T: Read + Seek>(&self, key: &str, _buf: &mut T) -> Option<String> {
+        let name = key.strip_suffix('=').unwrap_or(key).as_bytes();
+        self.items.iter().find_map(|item| match item.item {
+            Object::Data(ref d) if d.payload.len() > name.len()
+                && d.payload.starts_with(name) && d.payload[name.len()] == b'=' => {
+                str::from_utf8(&d.payload[name.len() + 1..]).ok().map(str::to_owned)
             }
-        }
-        None
+            _ => None,
+        })
     }
 }
 
@@ -551,6 +866,7 @@ pub union SdId128 {
     pub qwords: [u64; 2],
 }
 
+#[derive(Debug, Clone)]
 pub struct JournalHeader {
     pub signature: [u8; 8],
     pub compatible_flags: u32,
@@ -582,6 +898,14 @@ pub struct JournalHeader {
     /* Added in 189 */
     pub n_tags: u64,
     pub n_entry_arrays: u64,
+    /* Added in 246, `None` in headers without room for them */
+    pub data_hash_chain_depth: Option<u64>,
+    pub field_hash_chain_depth: Option<u64>,
+    /* Added in 252 */
+    pub tail_entry_array_offset: Option<u32>,
+    pub tail_entry_array_n_entries: Option<u32>,
+    /* Added in 254 */
+    pub tail_entry_offset: Option<u64>,
 }
 
 impl JournalHeader {
@@ -624,6 +948,24 @@ impl JournalHeader {
         let n_fields = file.read_u64::<LittleEndian>()?;
         let n_tags = file.read_u64::<LittleEndian>()?;
         let n_entry_arrays = file.read_u64::<LittleEndian>()?;
+        /* Added in 246, only there if the header has room for them */
+        let (data_hash_chain_depth, field_hash_chain_depth) = if header_size >= HEADER_SZ + 16 {
+            (Some(file.read_u64::<LittleEndian>()?), Some(file.read_u64::<LittleEndian>()?))
+        } else {
+            (None, None)
+        };
+        /* Added in 252 */
+        let (tail_entry_array_offset, tail_entry_array_n_entries) = if header_size >= HEADER_SZ + 24 {
+            (Some(file.read_u32::<LittleEndian>()?), Some(file.read_u32::<LittleEndian>()?))
+        } else {
+            (None, None)
+        };
+        /* Added in 254 */
+        let tail_entry_offset = if header_size >= HEADER_SZ + 32 {
+            Some(file.read_u64::<LittleEndian>()?)
+        } else {
+            None
+        };
 
         Ok(JournalHeader {
             signature: signature,
@@ -656,44 +998,155 @@ impl JournalHeader {
             /* Added in 189 */
             n_tags: n_tags,
             n_entry_arrays: n_entry_arrays,
+            data_hash_chain_depth,
+            field_hash_chain_depth,
+            tail_entry_array_offset,
+            tail_entry_array_n_entries,
+            tail_entry_offset,
         })
     }
+
+    /// Whether journald would rotate the file rather than append to it,
+    /// because its header is outdated or its hash tables are too full or
+    /// have too long chains
+    pub fn rotate_suggested(&self) -> bool {
+        let too_full = |n: u64, table_size: u64| n * 4 > table_size / HASH_ITEM_SZ * 3;
+        let too_deep = |depth: Option<u64>| depth.is_some_and(|d| d > HASH_CHAIN_DEPTH_MAX);
+        self.header_size < CURRENT_HEADER_SZ
+            || too_full(self.n_data, self.data_hash_table_size)
+            || too_full(self.n_fields, self.field_hash_table_size)
+            || too_deep(self.data_hash_chain_depth)
+            || too_deep(self.field_hash_chain_depth)
+            // Data objects that no field object indexes
+            || (self.n_data > 0 && self.n_fields == 0)
+    }
+
+    /// Hashes a DATA or FIELD payload the way the hash tables of this file do
+    pub fn hash_payload(&self, payload: &[u8]) -> u64 {
+        if self.incompatible_flags & HEADER_INCOMPATIBLE_KEYED_HASH != 0 {
+            siphash24(payload, &self.file_id.to_be_bytes())
+        } else {
+            rhash64(payload)
+        }
+    }
+
+    /// Serializes the header in the same layout `new` parses it from. The
+    /// optional fields are written up to the first one that is `None`.
+    pub fn write<W: Write>(&self, mut file: W) -> Result<()> {
+        file.write_all(&self.signature)?;
+        file.write_u32::<LittleEndian>(self.compatible_flags)?;
+        file.write_u32::<LittleEndian>(self.incompatible_flags)?;
+        file.write_u8(self.state.to_u8())?;
+        file.write_all(&self.reserved)?;
+        file.write_u128::<BigEndian>(self.file_id)?;
+        file.write_u128::<BigEndian>(self.machine_id)?;
+        file.write_u128::<BigEndian>(self.boot_id)?;
+        file.write_u128::<BigEndian>(self.seqnum_id)?;
+        file.write_u64::<LittleEndian>(self.header_size)?;
+        file.write_u64::<LittleEndian>(self.arena_size)?;
+        file.write_u64::<LittleEndian>(self.data_hash_table_offset)?;
+        file.write_u64::<LittleEndian>(self.data_hash_table_size)?;
+        file.write_u64::<LittleEndian>(self.field_hash_table_offset)?;
+        file.write_u64::<LittleEndian>(self.field_hash_table_size)?;
+        file.write_u64::<LittleEndian>(self.tail_object_offset)?;
+        file.write_u64::<LittleEndian>(self.n_objects)?;
+        file.write_u64::<LittleEndian>(self.n_entries)?;
+        file.write_u64::<LittleEndian>(self.tail_entry_seqnum)?;
+        file.write_u64::<LittleEndian>(self.head_entry_seqnum)?;
+        file.write_u64::<LittleEndian>(self.entry_array_offset)?;
+        file.write_u64::<LittleEndian>(self.head_entry_realtime)?;
+        file.write_u64::<LittleEndian>(self.tail_entry_realtime)?;
+        file.write_u64::<LittleEndian>(self.tail_entry_monotonic)?;
+        file.write_u64::<LittleEndian>(self.n_data)?;
+        file.write_u64::<LittleEndian>(self.n_fields)?;
+        file.write_u64::<LittleEndian>(self.n_tags)?;
+        file.write_u64::<LittleEndian>(self.n_entry_arrays)?;
+        if let (Some(data), Some(field)) = (self.data_hash_chain_depth, self.field_hash_chain_depth) {
+            file.write_u64::<LittleEndian>(data)?;
+            file.write_u64::<LittleEndian>(field)?;
+        } else {
+            return Ok(());
+        }
+        if let (Some(offset), Some(n_entries)) = (self.tail_entry_array_offset, self.tail_entry_array_n_entries) {
+            file.write_u32::<LittleEndian>(offset)?;
+            file.write_u32::<LittleEndian>(n_entries)?;
+        } else {
+            return Ok(());
+        }
+        if let Some(offset) = self.tail_entry_offset {
+            file.write_u64::<LittleEndian>(offset)?;
+        }
+        Ok(())
+    }
 }
 
-impl<'a> fmt::Display for JournalHeader {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let out = format!(
-            "File ID: {:x}\nMachine ID: {:x}\nBoot ID: {:x}\nSequential Number ID: {:x}\n
-            State: {}\nCompatible Flags: {}\nIncompatible Flags: {}\nHeader size: {}\n\
-            Arena size: {}\nData Hash Table Size: {}\nField Hash Table Size: {}\n\
-            Head Sequential Number: {}\nTail Sequential Number: {}\nHead Realtime Timestamp: {}\n\
-            Tail Realtime Timestamp: {}\nTail Monotonic Timestamp: {}\nObjects: {}\nEntry Objects: {}\n\
-            Data Objects: {}\nField Objects: {}\nTag Objects: {}\nEntry Array Objects: {}",
-            self.file_id, self.machine_id, self.boot_id, self.seqnum_id, self.state, self.compatible_flags,
-            self.incompatible_flags, self.header_size, self.arena_size, self.data_hash_table_size,
-            self.field_hash_table_size, self.head_entry_seqnum, self.tail_entry_seqnum, self.head_entry_realtime,
-            self.tail_entry_realtime, self.tail_entry_monotonic, self.n_objects, self.n_entries, self.n_fields,
-            self.n_data, self.n_tags, self.n_entry_arrays
-        );
-        write!(f, "{}", out)
-    }
-}
-
-impl<'a> fmt::Debug for JournalHeader {
+/// The names of the `flags` set, each after a space, with ` ???` for unknown ones
+fn flag_names(flags: u32, names: &[(u32, &str)]) -> String {
+    let mut out = String::new();
+    for (flag, name) in names {
+        if flags & flag != 0 {
+            out.push(' ');
+            out.push_str(name);
+        }
+    }
+    if flags & !names.iter().fold(0, |all, (flag, _)| all | flag) != 0 {
+        out.push_str(" ???");
+    }
+    out
+}
+
+/// `id` the way journalctl shows ids, as a UUID like
+/// `c5611adb-f144-4d8b-9ffd-1120aa0c2faa`
+fn format_uuid(id: u128) -> String {
+    let hex = format!("{:032x}", id);
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+/// The percentage of the buckets of a hash table of `table_size` bytes that
+/// `n` objects would fill without collisions
+fn hash_table_fill(n: u64, table_size: u64) -> f64 {
+    match table_size / HASH_ITEM_SZ {
+        0 => 0.0,
+        buckets => 100.0 * n as f64 / buckets as f64,
+    }
+}
+
+impl fmt::Display for JournalHeader {
+    /// The header the way `journalctl --header` shows it, with timestamps in
+    /// the local time zone
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let out = format!(
-            "File ID: {:x}\nMachine ID: {:x}\nBoot ID: {:x}\nSequential Number ID: {:x}\n
-            State: {}\nCompatible Flags: {}\nIncompatible Flags: {}\nHeader size: {}\n\
-            Arena size: {}\nData Hash Table Size: {}\nField Hash Table Size: {}\n\
-            Head Sequential Number: {}\nTail Sequential Number: {}\nHead Realtime Timestamp: {}\n\
-            Tail Realtime Timestamp: {}\nTail Monotonic Timestamp: {}\nObjects: {}\nEntry Objects: {}\n\
-            Data Objects: {}\nField Objects: {}\nTag Objects: {}\nEntry Array Objects: {}",
-            self.file_id, self.machine_id, self.boot_id, self.seqnum_id, self.state, self.compatible_flags,
-            self.incompatible_flags, self.header_size, self.arena_size, self.data_hash_table_size,
-            self.field_hash_table_size, self.head_entry_seqnum, self.tail_entry_seqnum, self.head_entry_realtime,
-            self.tail_entry_realtime, self.tail_entry_monotonic, self.n_objects, self.n_entries, self.n_fields,
-            self.n_data, self.n_tags, self.n_entry_arrays
-        );
-        write!(f, "{}", out)
+        let timestamp = |usec: u64| match usec {
+            0 => "n/a".to_owned(),
+            usec => format_timestamp(usec, "%a %Y-%m-%d %H:%M:%S %Z", false),
+        };
+        writeln!(f, "File ID: {}", format_uuid(self.file_id))?;
+        writeln!(f, "Machine ID: {}", format_uuid(self.machine_id))?;
+        writeln!(f, "Boot ID: {}", format_uuid(self.boot_id))?;
+        writeln!(f, "Sequential number ID: {}", format_uuid(self.seqnum_id))?;
+        writeln!(f, "State: {}", self.state)?;
+        writeln!(f, "Compatible flags:{}", flag_names(self.compatible_flags, COMPATIBLE_FLAG_NAMES))?;
+        writeln!(f, "Incompatible flags:{}", flag_names(self.incompatible_flags, INCOMPATIBLE_FLAG_NAMES))?;
+        writeln!(f, "Header size: {}", self.header_size)?;
+        writeln!(f, "Arena size: {}", self.arena_size)?;
+        writeln!(f, "Data hash table size: {}", self.data_hash_table_size / HASH_ITEM_SZ)?;
+        writeln!(f, "Field hash table size: {}", self.field_hash_table_size / HASH_ITEM_SZ)?;
+        writeln!(f, "Rotate suggested: {}", if self.rotate_suggested() { "yes" } else { "no" })?;
+        writeln!(f, "Head sequential number: {} ({:x})", self.head_entry_seqnum, self.head_entry_seqnum)?;
+        writeln!(f, "Tail sequential number: {} ({:x})", self.tail_entry_seqnum, self.tail_entry_seqnum)?;
+        writeln!(f, "Head realtime timestamp: {} ({:x})", timestamp(self.head_entry_realtime), self.head_entry_realtime)?;
+        writeln!(f, "Tail realtime timestamp: {} ({:x})", timestamp(self.tail_entry_realtime), self.tail_entry_realtime)?;
+        writeln!(f, "Tail monotonic timestamp: {} ({:x})", format_timespan(self.tail_entry_monotonic, USEC_PER_MSEC),
+                 self.tail_entry_monotonic)?;
+        writeln!(f, "Objects: {}", self.n_objects)?;
+        writeln!(f, "Entry objects: {}", self.n_entries)?;
+        writeln!(f, "Data objects: {}", self.n_data)?;
+        writeln!(f, "Data hash table fill: {:.1}%", hash_table_fill(self.n_data, self.data_hash_table_size))?;
+        writeln!(f, "Field objects: {}", self.n_fields)?;
+        writeln!(f, "Field hash table fill: {:.1}%", hash_table_fill(self.n_fields, self.field_hash_table_size))?;
+        writeln!(f, "Tag objects: {}", self.n_tags)?;
+        writeln!(f, "Entry array objects: {}", self.n_entry_arrays)?;
+        let depth = |depth: Option<u64>| depth.map_or_else(|| "n/a".to_owned(), |d| d.to_string());
+        writeln!(f, "Deepest field hash chain: {}", depth(self.field_hash_chain_depth))?;
+        write!(f, "Deepest data hash chain: {}", depth(self.data_hash_chain_depth))
     }
 }
@@ -0,0 +1,143 @@
+//! JSON output of entries like `journalctl -o json` and its variants. Each
+//! entry is an object mapping field names to values. Values are strings if
+//! they are printable UTF-8 and arrays of bytes otherwise, and fields that
+//! occur more than once in an entry map to an array of their values.
+
+use std::borrow::Cow;
+use std::io::{Result, Write};
+use std::str;
+
+use crate::entry::Entry;
+use crate::export::is_printable;
+use crate::fields::BOOT_ID;
+
+/// Values at least this long are written as `null` unless `show_all` is set
+pub const JSON_THRESHOLD: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonMode {
+    /// One object per line
+    Json,
+    /// One indented object over several lines
+    Pretty,
+    /// Server-Sent Events: each object on a `data: ` line followed by an
+    /// empty line
+    Sse,
+    /// JSON text sequences (RFC 7464): each object preceded by a record
+    /// separator
+    Seq,
+}
+
+enum Value<'a> {
+    Null,
+    Number(u8),
+    String(Cow<'a, str>),
+    Array(Vec<Value<'a>>),
+}
+
+impl<'a> Value<'a> {
+    fn from_payload(value: &'a [u8], show_all: bool) -> Value<'a> {
+        if !show_all && value.len() >= JSON_THRESHOLD {
+            return Value::Null;
+        }
+        match str::from_utf8(value) {
+            Ok(s) if is_printable(value, true) => Value::String(Cow::Borrowed(s)),
+            _ => Value::Array(value.iter().map(|&b| Value::Number(b)).collect()),
+        }
+    }
+
+    /// Writes the value, indented for `Some(depth)` and compact for `None`
+    fn write<W: Write>(&self, out: &mut W, indent: Option<usize>) -> Result<()> {
+        match self {
+            Value::Null => out.write_all(b"null"),
+            Value::Number(n) => write!(out, "{}", n),
+            Value::String(s) => write_string(out, s),
+            Value::Array(items) => {
+                out.write_all(b"[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.write_all(b",")?;
+                    }
+                    if let Some(depth) = indent {
+                        write_newline(out, depth + 1)?;
+                    }
+                    item.write(out, indent.map(|d| d + 1))?;
+                }
+                if let Some(depth) = indent {
+                    write_newline(out, depth)?;
+                }
+                out.write_all(b"]")
+            }
+        }
+    }
+}
+
+fn write_newline<W: Write>(out: &mut W, depth: usize) -> Result<()> {
+    out.write_all(b"\n")?;
+    for _ in 0..depth {
+        out.write_all(b"\t")?;
+    }
+    Ok(())
+}
+
+fn write_string<W: Write>(out: &mut W, s: &str) -> Result<()> {
+    out.write_all(b"\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => out.write_all(b"\\\"")?,
+            '\\' => out.write_all(b"\\\\")?,
+            '\n' => out.write_all(b"\\n")?,
+            '\t' => out.write_all(b"\\t")?,
+            '\r' => out.write_all(b"\\r")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => write!(out, "{}", c)?,
+        }
+    }
+    out.write_all(b"\"")
+}
+
+/// Writes `entry` as a JSON object in the given mode, including the
+/// separators around it. Values of `JSON_THRESHOLD` bytes or more are only
+/// written if `show_all` is set.
+pub fn write_entry<W: Write>(out: &mut W, entry: &Entry, mode: JsonMode, show_all: bool) -> Result<()> {
+    let mut fields: Vec<(&str, Vec<Value>)> = vec![
+        ("__CURSOR", vec![Value::String(Cow::Owned(entry.cursor()))]),
+        ("__REALTIME_TIMESTAMP", vec![Value::String(Cow::Owned(entry.realtime.to_string()))]),
+        ("__MONOTONIC_TIMESTAMP", vec![Value::String(Cow::Owned(entry.monotonic.to_string()))]),
+        (BOOT_ID, vec![Value::String(Cow::Owned(format!("{:032x}", entry.boot_id)))]),
+    ];
+    for (name, value) in entry.fields() {
+        if name == BOOT_ID {
+            continue;
+        }
+        let value = Value::from_payload(value, show_all);
+        match fields.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, values)) => values.push(value),
+            None => fields.push((name, vec![value])),
+        }
+    }
+
+    match mode {
+        JsonMode::Sse => out.write_all(b"data: ")?,
+        JsonMode::Seq => out.write_all(b"\x1e")?,
+        JsonMode::Json | JsonMode::Pretty => {}
+    }
+    let indent = if mode == JsonMode::Pretty { Some(1) } else { None };
+    out.write_all(b"{")?;
+    for (i, (name, mut values)) in fields.into_iter().enumerate() {
+        if i > 0 {
+            out.write_all(b",")?;
+        }
+        if indent.is_some() {
+            write_newline(out, 1)?;
+        }
+        write_string(out, name)?;
+        out.write_all(if indent.is_some() { b" : " } else { b":" })?;
+        let value = if values.len() == 1 { values.pop().unwrap() } else { Value::Array(values) };
+        value.write(out, indent)?;
+    }
+    if indent.is_some() {
+        out.write_all(b"\n")?;
+    }
+    out.write_all(if mode == JsonMode::Sse { b"}\n\n" } else { b"}\n" })
+}
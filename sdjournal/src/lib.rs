@@ -1,4 +1,25 @@
 pub mod journal;
 pub mod hash;
+pub mod compress;
 pub mod traits;
 pub mod iter;
+pub mod writer;
+pub mod rotate;
+pub mod time;
+pub mod vacuum;
+pub mod fsprg;
+pub mod seal;
+pub mod verify;
+pub mod repair;
+pub mod export;
+pub mod json;
+pub mod output;
+pub mod entry;
+pub mod fields;
+pub mod filter;
+pub mod boot;
+pub mod multi;
+pub mod unit;
+pub mod grep;
+pub mod summary;
+pub mod catalog;
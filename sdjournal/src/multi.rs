@@ -0,0 +1,136 @@
+//! Reading several journal files as one, like journalctl does with the
+//! files of a journal directory. The entries of all files are interleaved
+//! in the order `EntryPosition::compare` gives.
+
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::Result;
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+
+use crate::boot::{self, Boot};
+use crate::entry::{Entry, EntryPosition};
+use crate::fields::parse_id128;
+use crate::filter::Filter;
+use crate::journal::Journal;
+
+pub struct MultiJournal {
+    /// The files with their paths, in the order they were opened
+    pub journals: Vec<(PathBuf, Journal<File>)>,
+}
+
+impl MultiJournal {
+    pub fn open<P: AsRef<Path>>(paths: &[P]) -> Result<MultiJournal> {
+        let mut journals = Vec::with_capacity(paths.len());
+        for path in paths {
+            let path = path.as_ref();
+            journals.push((path.to_path_buf(), Journal::new(File::open(path)?)?));
+        }
+        Ok(MultiJournal { journals })
+    }
+
+    /// Opens the active, archived and disposed journal files in `directory`
+    /// and in its subdirectories named after a machine id, like
+    /// `journalctl -D`
+    pub fn open_directory<P: AsRef<Path>>(directory: P) -> Result<MultiJournal> {
+        let mut paths = Vec::new();
+        for dir_entry in fs::read_dir(directory)? {
+            let path = dir_entry?.path();
+            let file_name = match path.file_name().and_then(|n| n.to_str()) {
+                Some(n) => n.to_owned(),
+                None => continue,
+            };
+            if path.is_dir() && file_name.len() == 32 && parse_id128(&file_name).is_some() {
+                for dir_entry in fs::read_dir(&path)? {
+                    paths.push(dir_entry?.path());
+                }
+            } else {
+                paths.push(path);
+            }
+        }
+        paths.retain(|p| {
+            let name = p.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            p.is_file() && (name.ends_with(".journal") || name.ends_with(".journal~"))
+        });
+        paths.sort();
+        MultiJournal::open(&paths)
+    }
+
+    /// Iterate over the entries of all files
+    pub fn entries(&self) -> Result<MergedEntries<'_>> {
+        self.entries_matching(&Filter::new())
+    }
+
+    /// Iterate over the entries of all files selected by `filter`
+    pub fn entries_matching(&self, filter: &Filter) -> Result<MergedEntries<'_>> {
+        let mut sources = Vec::with_capacity(self.journals.len());
+        for (_, journal) in self.journals.iter() {
+            let entries: Box<dyn Iterator<Item = Result<Entry>>> = Box::new(journal.entries_matching(filter)?);
+            sources.push(entries.peekable());
+        }
+        Ok(MergedEntries { sources, newest_first: false })
+    }
+
+    /// Iterate over the entries of all files selected by `filter` from the
+    /// newest to the oldest, e.g. to find the last few without reading the
+    /// others
+    pub fn entries_matching_rev(&self, filter: &Filter) -> Result<MergedEntries<'_>> {
+        let mut sources = Vec::with_capacity(self.journals.len());
+        for (_, journal) in self.journals.iter() {
+            let entries: Box<dyn Iterator<Item = Result<Entry>>> = Box::new(journal.entries_matching(filter)?.rev());
+            sources.push(entries.peekable());
+        }
+        Ok(MergedEntries { sources, newest_first: true })
+    }
+
+    /// The distinct values the field `field` has in any of the files, without
+    /// the `FIELD=` prefix. Values that fail to decompress are left out.
+    pub fn unique_values(&self, field: &str) -> Result<BTreeSet<Vec<u8>>> {
+        let mut values = BTreeSet::new();
+        for (_, journal) in self.journals.iter() {
+            for data in journal.unique_values(field)? {
+                if let Ok(payload) = data.decompressed_payload() {
+                    values.extend(payload.get(field.len() + 1..).map(|v| v.to_vec()));
+                }
+            }
+        }
+        Ok(values)
+    }
+
+    /// The boots any of the files has entries of, oldest first
+    pub fn boots(&self) -> Result<Vec<Boot>> {
+        let mut spans = Vec::new();
+        for (_, journal) in self.journals.iter() {
+            spans.extend(boot::boot_spans(journal)?);
+        }
+        Ok(boot::list_boots(spans))
+    }
+}
+
+/// The entries of several files interleaved into one sequence
+pub struct MergedEntries<'a> {
+    sources: Vec<Peekable<Box<dyn Iterator<Item = Result<Entry>> + 'a>>>,
+    /// Whether the sources yield their newest entry first
+    newest_first: bool,
+}
+
+impl<'a> Iterator for MergedEntries<'a> {
+    type Item = Result<Entry>;
+
+    fn next(&mut self) -> Option<Result<Entry>> {
+        let mut next: Option<(usize, EntryPosition)> = None;
+        for (i, source) in self.sources.iter_mut().enumerate() {
+            let position = match source.peek() {
+                Some(Ok(entry)) => entry.position(),
+                Some(Err(_)) => return source.next(),
+                None => continue,
+            };
+            let before = if self.newest_first { Ordering::Greater } else { Ordering::Less };
+            if next.is_none_or(|(_, p)| position.compare(&p) == before) {
+                next = Some((i, position));
+            }
+        }
+        self.sources[next?.0].next()
+    }
+}
@@ -0,0 +1,252 @@
+//! Text output of entries like journalctl's `short`, `verbose` and `cat`
+//! modes. The `short` modes print a syslog style line per entry and skip
+//! entries without a `MESSAGE` field.
+
+use std::io::{Error, ErrorKind, Result, Write};
+use std::str::FromStr;
+
+use crate::catalog::{is_locale_utf8, Catalog};
+use crate::entry::Entry;
+use crate::export::is_printable;
+use crate::fields::*;
+use crate::time::*;
+
+/// Fields longer than this are left out of the short modes and shown as blob
+/// data in verbose mode unless all fields are requested
+const PRINT_CHAR_THRESHOLD: usize = 300;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    /// `Oct 18 18:53:37 host ident[pid]: message`
+    Short,
+    /// Like `Short`, with the weekday, year and time zone
+    ShortFull,
+    /// Like `Short`, with an ISO 8601 timestamp
+    ShortIso,
+    /// Like `ShortIso`, with microseconds
+    ShortIsoPrecise,
+    /// Like `Short`, with microseconds
+    ShortPrecise,
+    /// Like `Short`, with the monotonic timestamp
+    ShortMonotonic,
+    /// Like `Short`, with seconds since the epoch
+    ShortUnix,
+    /// Like `ShortMonotonic`, with the time since the previous entry of the
+    /// same boot
+    ShortDelta,
+    /// The full timestamp and cursor followed by every field on its own line
+    Verbose,
+    /// Only the message
+    Cat,
+    /// Like `ShortFull`, with the unit instead of the identifier
+    WithUnit,
+}
+
+impl FromStr for OutputMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<OutputMode> {
+        Ok(match s {
+            "short" => OutputMode::Short,
+            "short-full" => OutputMode::ShortFull,
+            "short-iso" => OutputMode::ShortIso,
+            "short-iso-precise" => OutputMode::ShortIsoPrecise,
+            "short-precise" => OutputMode::ShortPrecise,
+            "short-monotonic" => OutputMode::ShortMonotonic,
+            "short-unix" => OutputMode::ShortUnix,
+            "short-delta" => OutputMode::ShortDelta,
+            "verbose" => OutputMode::Verbose,
+            "cat" => OutputMode::Cat,
+            "with-unit" => OutputMode::WithUnit,
+            _ => return Err(Error::new(ErrorKind::InvalidInput, format!("Unknown output mode: {}", s))),
+        })
+    }
+}
+
+/// Formats a byte count the way journalctl does, e.g. `8.0M`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [(&str, u64); 6] = [("E", 1 << 60), ("P", 1 << 50), ("T", 1 << 40),
+                                     ("G", 1 << 30), ("M", 1 << 20), ("K", 1 << 10)];
+    for (suffix, factor) in UNITS.iter() {
+        if bytes >= *factor {
+            return format!("{}.{}{}", bytes / factor, (bytes % factor) * 10 / factor, suffix);
+        }
+    }
+    format!("{}B", bytes)
+}
+
+/// Writes `value`, indenting every line after the first by `prefix` columns
+fn write_multiline<W: Write>(out: &mut W, prefix: usize, value: &[u8]) -> Result<()> {
+    // A trailing newline does not start another line
+    let value = value.strip_suffix(b"\n").unwrap_or(value);
+    for (i, line) in value.split(|&c| c == b'\n').enumerate() {
+        if i > 0 {
+            write!(out, "{:width$}", "", width = prefix)?;
+        }
+        out.write_all(line)?;
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Writes entries in one of the text modes. `ShortDelta` needs the previous
+/// entry, so the same formatter should be used for all entries of a listing.
+#[derive(Debug, Clone)]
+pub struct TextFormatter {
+    pub mode: OutputMode,
+    /// Show timestamps in UTC rather than the local time zone
+    pub utc: bool,
+    /// Show long and unprintable fields in full
+    pub show_all: bool,
+    /// The catalog to explain entries with, except in `Cat` mode, and the
+    /// mark that starts the lines of explanations
+    catalog: Option<(Catalog, &'static str)>,
+    /// Monotonic and realtime timestamps and boot id of the previous entry
+    previous: Option<(u64, u64, u128)>,
+}
+
+impl TextFormatter {
+    pub fn new(mode: OutputMode) -> TextFormatter {
+        TextFormatter { mode, utc: false, show_all: false, catalog: None, previous: None }
+    }
+
+    /// Explains entries with texts from `catalog`, except in `Cat` mode
+    pub fn set_catalog(&mut self, catalog: Catalog) {
+        let mark = if is_locale_utf8() { "\u{2591}\u{2591} " } else { "-- " };
+        self.catalog = Some((catalog, mark));
+    }
+
+    fn shall_print(&self, value: &[u8]) -> bool {
+        self.show_all || (value.len() < PRINT_CHAR_THRESHOLD && is_printable(value, true))
+    }
+
+    /// The timestamp the entry was logged with by its source, if known and
+    /// valid, and otherwise `received`
+    fn source_timestamp(source: Result<Option<u64>>, received: u64) -> u64 {
+        source.ok().flatten().filter(|&t| t > 0 && t != u64::MAX).unwrap_or(received)
+    }
+
+    fn timestamp(&mut self, entry: &Entry) -> String {
+        let realtime = TextFormatter::source_timestamp(entry.source_realtime(), entry.realtime);
+        let monotonic = TextFormatter::source_timestamp(entry.source_monotonic(), entry.monotonic);
+        let usec = realtime % USEC_PER_SEC;
+        let format = |f: &str| format_timestamp(realtime, f, self.utc);
+        match self.mode {
+            OutputMode::Short => format("%b %d %H:%M:%S"),
+            OutputMode::ShortPrecise => format(&format!("%b %d %H:%M:%S.{:06}", usec)),
+            OutputMode::ShortIso => format("%Y-%m-%dT%H:%M:%S%:z"),
+            OutputMode::ShortIsoPrecise => format(&format!("%Y-%m-%dT%H:%M:%S.{:06}%:z", usec)),
+            OutputMode::ShortFull | OutputMode::WithUnit => format("%a %Y-%m-%d %H:%M:%S %Z"),
+            OutputMode::ShortUnix => format!("{:10}.{:06}", realtime / USEC_PER_SEC, usec),
+            OutputMode::ShortMonotonic => {
+                format!("[{:5}.{:06}]", monotonic / USEC_PER_SEC, monotonic % USEC_PER_SEC)
+            }
+            OutputMode::ShortDelta => {
+                // Monotonic timestamps of different boots cannot be compared,
+                // so across boots the delta is of wall clock time, marked `*`
+                let delta = match self.previous {
+                    Some((previous, _, boot_id)) if previous > 0 && boot_id == entry.boot_id => {
+                        Some((monotonic.saturating_sub(previous), ' '))
+                    }
+                    Some((_, previous, _)) => Some((realtime.saturating_sub(previous), '*')),
+                    None => None,
+                };
+                let delta = match delta {
+                    Some((delta, mark)) => format!(" <{:5}.{:06}{}>", delta / USEC_PER_SEC, delta % USEC_PER_SEC, mark),
+                    None => format!("{:16}", ""),
+                };
+                self.previous = Some((monotonic, realtime, entry.boot_id));
+                format!("[{:5}.{:06}{}]", monotonic / USEC_PER_SEC, monotonic % USEC_PER_SEC, delta)
+            }
+            OutputMode::Verbose | OutputMode::Cat => unreachable!("not a short mode"),
+        }
+    }
+
+    fn write_short<W: Write>(&mut self, out: &mut W, entry: &Entry) -> Result<()> {
+        let message = match entry.get(MESSAGE) {
+            Some(m) => m,
+            None => return Ok(()),
+        };
+        let mut line = self.timestamp(entry).into_bytes();
+        let printable = |name: &str| entry.get(name).filter(|v| self.shall_print(v));
+        if let Some(hostname) = printable(HOSTNAME) {
+            line.push(b' ');
+            line.extend_from_slice(hostname);
+        }
+        let unit = printable(SYSTEMD_UNIT);
+        let user_unit = printable(SYSTEMD_USER_UNIT);
+        if self.mode == OutputMode::WithUnit && (unit.is_some() || user_unit.is_some()) {
+            if let Some(unit) = unit {
+                line.push(b' ');
+                line.extend_from_slice(unit);
+            }
+            if let Some(user_unit) = user_unit {
+                line.push(if unit.is_some() { b'/' } else { b' ' });
+                line.extend_from_slice(user_unit);
+            }
+        } else if let Some(identifier) = printable(SYSLOG_IDENTIFIER).or_else(|| printable(COMM)) {
+            line.push(b' ');
+            line.extend_from_slice(identifier);
+        } else {
+            line.extend_from_slice(b" unknown");
+        }
+        if let Some(pid) = printable(PID).or_else(|| printable(SYSLOG_PID)) {
+            line.push(b'[');
+            line.extend_from_slice(pid);
+            line.push(b']');
+        }
+        out.write_all(&line)?;
+
+        if !self.show_all && !is_printable(message, true) {
+            writeln!(out, ": [{} blob data]", format_bytes(message.len() as u64))?;
+        } else {
+            out.write_all(b": ")?;
+            write_multiline(out, line.len() + 2, message)?;
+        }
+        self.write_explanation(out, entry)
+    }
+
+    /// Writes the catalog's explanation of `entry`, if there is one, with
+    /// every line marked as not being part of the entry
+    fn write_explanation<W: Write>(&self, out: &mut W, entry: &Entry) -> Result<()> {
+        let (explanation, mark) = match &self.catalog {
+            Some((catalog, mark)) => match catalog.explain(entry) {
+                Some(explanation) => (explanation, *mark),
+                None => return Ok(()),
+            },
+            None => return Ok(()),
+        };
+        let explanation = explanation.trim_matches([' ', '\t', '\n', '\r']);
+        writeln!(out, "{}{}", mark, explanation.replace('\n', &format!("\n{}", mark)))
+    }
+
+    fn write_verbose<W: Write>(&self, out: &mut W, entry: &Entry) -> Result<()> {
+        let realtime = TextFormatter::source_timestamp(entry.source_realtime(), entry.realtime);
+        let format = format!("%a %Y-%m-%d %H:%M:%S.{:06} %Z", realtime % USEC_PER_SEC);
+        writeln!(out, "{} [{}]", format_timestamp(realtime, &format, self.utc), entry.cursor())?;
+        for (name, value) in entry.fields() {
+            write!(out, "    {}=", name)?;
+            if self.show_all || is_printable(value, true) {
+                write_multiline(out, 4 + name.len() + 1, value)?;
+            } else {
+                writeln!(out, "[{} blob data]", format_bytes(value.len() as u64))?;
+            }
+        }
+        self.write_explanation(out, entry)
+    }
+
+    /// Writes `entry` in the formatter's mode
+    pub fn write_entry<W: Write>(&mut self, out: &mut W, entry: &Entry) -> Result<()> {
+        match self.mode {
+            OutputMode::Verbose => self.write_verbose(out, entry),
+            OutputMode::Cat => match entry.get(MESSAGE) {
+                Some(message) => {
+                    out.write_all(message)?;
+                    out.write_all(b"\n")
+                }
+                None => Ok(()),
+            },
+            _ => self.write_short(out, entry),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! Rewrites a damaged journal into a clean one. Every entry that can still be
+//! read in full is copied, and the hash tables and entry arrays are rebuilt
+//! by the writer, so the result is as valid as any freshly written file.
+
+use std::cmp;
+use std::io::{Read, Result, Seek, Write};
+
+use crate::iter::Salvaged;
+use crate::journal::*;
+use crate::writer::*;
+
+/// Outcome of `repair`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RepairReport {
+    /// Entries copied to the new file
+    pub recovered: u64,
+    /// Entries found but left out because a DATA object they reference is
+    /// damaged or missing
+    pub rejected: u64,
+    /// Entries the header accounts for that were not found at all
+    pub missing: u64,
+    /// Byte ranges of the damaged file in which nothing could be read
+    pub skipped: Vec<(u64, u64)>,
+}
+
+impl RepairReport {
+    /// Entries of the damaged file that did not make it to the new one
+    pub fn lost(&self) -> u64 {
+        self.rejected + self.missing
+    }
+}
+
+/// The `FIELD=value` payloads of the DATA objects an entry's `items` point
+/// to, decompressed, or `None` if any of them cannot be read or is damaged
+fn entry_payloads<R: Read + Seek>(
+    file: &mut R,
+    items: &[(ObjectOffset, u64)],
+    header: &JournalHeader,
+) -> Option<Vec<Vec<u8>>> {
+    let mut payloads = Vec::with_capacity(items.len());
+    for &(offset, hash) in items {
+        let data = match get_obj_at_offset(file, offset) {
+            Ok(Object::Data(d)) => d,
+            _ => return None,
+        };
+        // The new file is written uncompressed, and the hash is of the
+        // uncompressed payload anyway
+        let payload = match data.decompressed_payload() {
+            Ok(payload) => payload.into_owned(),
+            Err(_) => return None,
+        };
+        if data.hash != hash || header.hash_payload(&payload) != data.hash {
+            return None;
+        }
+        match payload.iter().position(|&c| c == b'=') {
+            Some(eq) if eq > 0 => payloads.push(payload),
+            _ => return None,
+        }
+    }
+    if payloads.is_empty() {
+        return None;
+    }
+    Some(payloads)
+}
+
+/// Copies every intact entry of `journal` into a new journal written to
+/// `out`. The new file keeps the machine id, boot id, seqnum id and seqnums
+/// of the damaged one, but is never sealed.
+pub fn repair<'a, R, W>(journal: &'a Journal<R>, out: W) -> Result<RepairReport>
+where
+    R: 'a + Read + Seek,
+    &'a R: Read + Seek,
+    W: Read + Write + Seek,
+{
+    let header = &journal.header;
+    // Size the hash tables for what can actually be recovered
+    let n_data = journal.salvage_iter()
+        .filter(|s| matches!(s, Salvaged::Object(_, Object::Data(_))))
+        .count() as u64;
+    let params = JournalParams {
+        machine_id: header.machine_id,
+        boot_id: header.boot_id,
+        seqnum_id: header.seqnum_id,
+        data_hash_table_buckets: cmp::max(DEFAULT_DATA_HASH_TABLE_BUCKETS, n_data * 4 / 3),
+        field_hash_table_buckets: DEFAULT_FIELD_HASH_TABLE_BUCKETS,
+        keyed_hash: header.incompatible_flags & HEADER_INCOMPATIBLE_KEYED_HASH != 0,
+        header_size: HEADER_SZ,
+    };
+    let mut writer = JournalWriter::create(out, &params)?;
+
+    let mut report = RepairReport::default();
+    let mut found = 0;
+    for salvaged in journal.salvage_iter() {
+        let (entry, items) = match salvaged {
+            Salvaged::Entry(_, entry, items) => (entry, items),
+            Salvaged::Skipped { start, end } => {
+                report.skipped.push((start, end));
+                continue;
+            }
+            Salvaged::Object(..) => continue,
+        };
+        found += 1;
+
+        let payloads = match entry_payloads(&mut &journal.file, &items, header) {
+            Some(p) => p,
+            None => {
+                report.rejected += 1;
+                continue;
+            }
+        };
+        // Keep the original seqnums where they still increase
+        if entry.seqnum >= writer.next_seqnum() {
+            writer.set_next_seqnum(entry.seqnum)?;
+        }
+        let payloads: Vec<&[u8]> = payloads.iter().map(|p| &p[..]).collect();
+        writer.append_entry(entry.realtime, entry.monotonic, entry.boot_id, &payloads)?;
+        report.recovered += 1;
+    }
+
+    // Entries in skipped ranges are only known from the header count
+    report.missing = header.n_entries.saturating_sub(found);
+    writer.close()?;
+    Ok(report)
+}
@@ -0,0 +1,222 @@
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Cursor, Error, ErrorKind, Result, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::journal::*;
+use crate::writer::*;
+
+/// Limits after which the active file of a `RotatingWriter` gets archived.
+/// A limit of `None` is never reached.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RotationLimits {
+    /// Rotate once the file has grown to this many bytes
+    pub max_file_size: Option<u64>,
+    /// Rotate once an entry is more than this many microseconds newer than
+    /// the head entry of the file
+    pub max_file_usec: Option<u64>,
+}
+
+/// The parts of an archived journal file name, which systemd forms as
+/// `name@seqnum_id-head_seqnum-head_realtime.journal`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchivedName {
+    pub name: String,
+    pub seqnum_id: u128,
+    pub head_seqnum: u64,
+    pub head_realtime: u64,
+}
+
+impl ArchivedName {
+    pub fn new(name: &str, header: &JournalHeader) -> ArchivedName {
+        ArchivedName {
+            name: name.to_owned(),
+            seqnum_id: header.seqnum_id,
+            head_seqnum: header.head_entry_seqnum,
+            head_realtime: header.head_entry_realtime,
+        }
+    }
+
+    /// Parses an archived file name, returning `None` for anything else
+    /// such as active or disposed (`.journal~`) files
+    pub fn parse(file_name: &str) -> Option<ArchivedName> {
+        let stem = file_name.strip_suffix(".journal")?;
+        let at = stem.rfind('@')?;
+        let (name, ids) = (&stem[..at], &stem[at + 1..]);
+        let parts: Vec<&str> = ids.split('-').collect();
+        if name.is_empty() || parts.len() != 3 || parts[0].len() != 32 || parts[1].len() != 16 || parts[2].len() != 16 {
+            return None;
+        }
+
+        Some(ArchivedName {
+            name: name.to_owned(),
+            seqnum_id: u128::from_str_radix(parts[0], 16).ok()?,
+            head_seqnum: u64::from_str_radix(parts[1], 16).ok()?,
+            head_realtime: u64::from_str_radix(parts[2], 16).ok()?,
+        })
+    }
+}
+
+impl fmt::Display for ArchivedName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}@{:032x}-{:016x}-{:016x}.journal",
+               self.name, self.seqnum_id, self.head_seqnum, self.head_realtime)
+    }
+}
+
+/// Writes entries to `name.journal` in a directory, archiving the file and
+/// starting a fresh one whenever it exceeds the configured limits. Fresh
+/// files continue the seqnum_id and seqnum sequence of the archived ones.
+pub struct RotatingWriter {
+    directory: PathBuf,
+    name: String,
+    params: JournalParams,
+    limits: RotationLimits,
+    writer: Option<JournalWriter<File>>,
+}
+
+impl RotatingWriter {
+    /// Opens `name.journal` in `directory` for appending, creating it if it
+    /// does not exist. An intact file that cannot be appended to, because it
+    /// is sealed, archived or uses unsupported features, is archived and a
+    /// fresh one started. A damaged file or one that is still online is
+    /// moved aside as `name@realtime-random.journal~` like journald does.
+    pub fn open<P: AsRef<Path>>(
+        directory: P,
+        name: &str,
+        params: JournalParams,
+        limits: RotationLimits,
+    ) -> Result<RotatingWriter> {
+        let mut rotating = RotatingWriter {
+            directory: directory.as_ref().to_path_buf(),
+            name: name.to_owned(),
+            params,
+            limits,
+            writer: None,
+        };
+
+        let path = rotating.path();
+        let writer = if path.exists() {
+            match JournalWriter::open(open_rw(&path)?) {
+                Ok(w) => w,
+                Err(ref e) if e.kind() == ErrorKind::Unsupported => rotating.archive_aside()?,
+                Err(ref e) if e.kind() == ErrorKind::InvalidData || e.kind() == ErrorKind::UnexpectedEof => {
+                    rotating.dispose()?;
+                    JournalWriter::create(open_rw(&path)?, &rotating.params)?
+                }
+                Err(e) => return Err(e),
+            }
+        } else {
+            JournalWriter::create(open_rw(&path)?, &rotating.params)?
+        };
+        rotating.writer = Some(writer);
+        Ok(rotating)
+    }
+
+    /// Path of the active file
+    pub fn path(&self) -> PathBuf {
+        self.directory.join(format!("{}.journal", self.name))
+    }
+
+    /// Header of the active file
+    pub fn header(&self) -> Result<&JournalHeader> {
+        Ok(&self.writer.as_ref().ok_or_else(no_active_file)?.header)
+    }
+
+    /// Appends an entry, rotating first if the active file exceeds the limits
+    pub fn append_entry(
+        &mut self,
+        realtime: u64,
+        monotonic: u64,
+        boot_id: u128,
+        items: &[&[u8]],
+    ) -> Result<ObjectOffset> {
+        if self.should_rotate(realtime)? {
+            self.rotate()?;
+        }
+        self.writer.as_mut().ok_or_else(no_active_file)?.append_entry(realtime, monotonic, boot_id, items)
+    }
+
+    /// Archives the active file and starts a fresh one. Returns the path the
+    /// archived file was renamed to.
+    pub fn rotate(&mut self) -> Result<PathBuf> {
+        let writer = self.writer.take().ok_or_else(no_active_file)?;
+        let archived = ArchivedName::new(&self.name, &writer.header);
+        let next_seqnum = writer.next_seqnum();
+        self.params.seqnum_id = writer.header.seqnum_id;
+        writer.archive()?;
+
+        let path = self.path();
+        let archived_path = self.directory.join(archived.to_string());
+        fs::rename(&path, &archived_path)?;
+
+        let mut writer = JournalWriter::create(open_rw(&path)?, &self.params)?;
+        writer.set_next_seqnum(next_seqnum)?;
+        self.writer = Some(writer);
+        Ok(archived_path)
+    }
+
+    /// Marks the active file `Offline`
+    pub fn close(mut self) -> Result<()> {
+        self.writer.take().ok_or_else(no_active_file)?.close()?;
+        Ok(())
+    }
+
+    fn should_rotate(&self, realtime: u64) -> Result<bool> {
+        let writer = self.writer.as_ref().ok_or_else(no_active_file)?;
+        if writer.header.n_entries == 0 {
+            return Ok(false);
+        }
+        if let Some(max) = self.limits.max_file_size {
+            if writer.file_size() >= max {
+                return Ok(true);
+            }
+        }
+        if let Some(max) = self.limits.max_file_usec {
+            if realtime.saturating_sub(writer.header.head_entry_realtime) > max {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Archives an active file the writer refused to append to and creates
+    /// a fresh one continuing its seqnums
+    fn archive_aside(&mut self) -> Result<JournalWriter<File>> {
+        let path = self.path();
+        let mut file = open_rw(&path)?;
+        let mut header = JournalHeader::new(Cursor::new(read_header_buf(&mut file)?))?;
+        if header.state != JournalState::Archived {
+            header.state = JournalState::Archived;
+            let mut buf = Vec::with_capacity(MAX_HEADER_SZ as usize);
+            header.write(&mut buf)?;
+            file.seek(SeekFrom::Start(0))?;
+            file.write_all(&buf)?;
+        }
+        drop(file);
+        let archived = ArchivedName::new(&self.name, &header);
+        fs::rename(&path, self.directory.join(archived.to_string()))?;
+
+        self.params.seqnum_id = header.seqnum_id;
+        let mut writer = JournalWriter::create(open_rw(&path)?, &self.params)?;
+        writer.set_next_seqnum(header.tail_entry_seqnum + 1)?;
+        Ok(writer)
+    }
+
+    fn dispose(&self) -> Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let disposed = format!("{}@{:016x}-{:016x}.journal~",
+                               self.name, now.as_micros() as u64, random_id()? as u64);
+        fs::rename(self.path(), self.directory.join(disposed))
+    }
+}
+
+fn open_rw(path: &Path) -> Result<File> {
+    OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)
+}
+
+fn no_active_file() -> Error {
+    Error::new(ErrorKind::NotFound, "No active journal file")
+}
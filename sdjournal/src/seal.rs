@@ -0,0 +1,370 @@
+//! Forward Secure Sealing. Every TAG object holds an HMAC-SHA256 over the
+//! immutable parts of all objects since the previous tag, keyed from the
+//! FSPRG state of the tag's epoch.
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fmt;
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+use std::str::FromStr;
+
+use crate::fsprg::*;
+use crate::journal::*;
+use crate::writer::fill_random;
+
+pub(crate) type HmacSha256 = Hmac<Sha256>;
+
+pub const TAG_OBJECT_SZ: u64 = OBJECT_HEADER_SZ + 16 + TAG_LENGTH as u64;
+
+pub const FSS_HEADER_SIGNATURE: &[u8; 8] = b"KSHHRHLP";
+pub const FSS_HEADER_SZ: u64 = 88;
+
+/// The verification key printed by `journalctl --setup-keys`, in the form
+/// `seed/start-interval`. The seed is hex, optionally grouped with dashes,
+/// and start and interval are hex with start counted in intervals.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationKey {
+    pub seed: Vec<u8>,
+    /// Realtime at which epoch 0 begins
+    pub start_usec: u64,
+    /// Length of an epoch in microseconds
+    pub interval_usec: u64,
+}
+
+impl VerificationKey {
+    /// The epoch `realtime` falls into
+    pub fn epoch(&self, realtime: u64) -> u64 {
+        realtime.saturating_sub(self.start_usec) / self.interval_usec
+    }
+}
+
+impl FromStr for VerificationKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<VerificationKey> {
+        let invalid = || Error::new(ErrorKind::InvalidInput, "Invalid verification key");
+        let slash = s.find('/').ok_or_else(invalid)?;
+        let seed_hex: String = s[..slash].chars().filter(|&c| c != '-').collect();
+        if seed_hex.len() != 2 * FSPRG_RECOMMENDED_SEEDLEN || !seed_hex.is_ascii() {
+            return Err(invalid());
+        }
+        let seed = (0..FSPRG_RECOMMENDED_SEEDLEN)
+            .map(|i| u8::from_str_radix(&seed_hex[2 * i..2 * i + 2], 16))
+            .collect::<std::result::Result<Vec<u8>, _>>()
+            .map_err(|_| invalid())?;
+
+        let mut times = s[slash + 1..].splitn(2, '-');
+        let start = u64::from_str_radix(times.next().ok_or_else(invalid)?, 16).map_err(|_| invalid())?;
+        let interval = u64::from_str_radix(times.next().ok_or_else(invalid)?, 16).map_err(|_| invalid())?;
+        if interval == 0 {
+            return Err(invalid());
+        }
+
+        Ok(VerificationKey {
+            seed,
+            start_usec: start.checked_mul(interval).ok_or_else(invalid)?,
+            interval_usec: interval,
+        })
+    }
+}
+
+impl fmt::Display for VerificationKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, b) in self.seed.iter().enumerate() {
+            if i > 0 && i % 3 == 0 {
+                write!(f, "-")?;
+            }
+            write!(f, "{:02x}", b)?;
+        }
+        write!(f, "/{:x}-{:x}", self.start_usec / self.interval_usec, self.interval_usec)
+    }
+}
+
+/// The sealing key systemd keeps in `/var/log/journal/MACHINE_ID/fss`: the
+/// FSPRG state of the current epoch, from which the keys of later epochs
+/// but not earlier ones can be derived
+#[derive(Debug, Clone, PartialEq)]
+pub struct SealingKey {
+    pub machine_id: u128,
+    /// Boot id of the last writer
+    pub boot_id: u128,
+    /// Realtime at which epoch 0 begins
+    pub start_usec: u64,
+    /// Length of an epoch in microseconds
+    pub interval_usec: u64,
+    pub state: FsprgState,
+}
+
+impl SealingKey {
+    /// Generates a new key pair whose first epoch contains `now`, like
+    /// `journalctl --setup-keys` does
+    pub fn generate(machine_id: u128, boot_id: u128, interval_usec: u64, now: u64) -> Result<(SealingKey, VerificationKey)> {
+        if interval_usec == 0 {
+            return Err(Error::new(ErrorKind::InvalidInput, "Sealing interval must not be zero"));
+        }
+        let mut seed = vec![0u8; FSPRG_RECOMMENDED_SEEDLEN];
+        fill_random(&mut seed)?;
+        let (_, mpk) = gen_mk(&seed, FSPRG_RECOMMENDED_SECPAR);
+        let start_usec = now / interval_usec * interval_usec;
+
+        let sealing = SealingKey {
+            machine_id,
+            boot_id,
+            start_usec,
+            interval_usec,
+            state: FsprgState::new(&mpk, &seed),
+        };
+        let verification = VerificationKey {
+            seed,
+            start_usec,
+            interval_usec,
+        };
+        Ok((sealing, verification))
+    }
+
+    /// Reads a key in the `fss` file format
+    pub fn read<R: Read>(mut reader: R) -> Result<SealingKey> {
+        let mut header = [0u8; FSS_HEADER_SZ as usize];
+        reader.read_exact(&mut header)?;
+        if &header[..8] != FSS_HEADER_SIGNATURE {
+            return Err(Error::new(ErrorKind::InvalidData, "Not a sealing key file"));
+        }
+        if LittleEndian::read_u32(&header[12..16]) != 0 {
+            return Err(Error::new(ErrorKind::InvalidData, "Sealing key uses unsupported incompatible features"));
+        }
+        let header_size = LittleEndian::read_u64(&header[48..56]);
+        let secpar = LittleEndian::read_u16(&header[72..74]);
+        let fsprg_state_size = LittleEndian::read_u64(&header[80..88]);
+        if header_size < FSS_HEADER_SZ || !is_valid_secpar(secpar) || fsprg_state_size != state_size(secpar) as u64 {
+            return Err(Error::new(ErrorKind::InvalidData, "Invalid sealing key header"));
+        }
+
+        let mut state = vec![0u8; (header_size - FSS_HEADER_SZ + fsprg_state_size) as usize];
+        reader.read_exact(&mut state)?;
+        let state = FsprgState::from_bytes(&state[(header_size - FSS_HEADER_SZ) as usize..])
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Invalid sealing key state"))?;
+        let interval_usec = LittleEndian::read_u64(&header[64..72]);
+        if interval_usec == 0 {
+            return Err(Error::new(ErrorKind::InvalidData, "Invalid sealing key interval"));
+        }
+
+        Ok(SealingKey {
+            machine_id: BigEndian::read_u128(&header[16..32]),
+            boot_id: BigEndian::read_u128(&header[32..48]),
+            start_usec: LittleEndian::read_u64(&header[56..64]),
+            interval_usec,
+            state,
+        })
+    }
+
+    /// Writes the key in the `fss` file format. The file should be rewritten
+    /// whenever the state evolves, so that earlier epochs cannot be resealed.
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<()> {
+        writer.write_all(FSS_HEADER_SIGNATURE)?;
+        writer.write_u32::<LittleEndian>(0)?;
+        writer.write_u32::<LittleEndian>(0)?;
+        writer.write_u128::<BigEndian>(self.machine_id)?;
+        writer.write_u128::<BigEndian>(self.boot_id)?;
+        writer.write_u64::<LittleEndian>(FSS_HEADER_SZ)?;
+        writer.write_u64::<LittleEndian>(self.start_usec)?;
+        writer.write_u64::<LittleEndian>(self.interval_usec)?;
+        writer.write_u16::<LittleEndian>(self.state.secpar())?;
+        writer.write_all(&[0u8; 6])?;
+        writer.write_u64::<LittleEndian>(self.state.as_bytes().len() as u64)?;
+        writer.write_all(self.state.as_bytes())
+    }
+
+    /// The epoch `realtime` falls into, or `None` before epoch 0
+    pub fn epoch(&self, realtime: u64) -> Option<u64> {
+        realtime.checked_sub(self.start_usec).map(|usec| usec / self.interval_usec)
+    }
+}
+
+/// Outcome of a successful seal verification, in realtime microseconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct SealReport {
+    /// Timestamp of the first entry in the file
+    pub first_contained: u64,
+    /// Timestamp of the last entry covered by a valid tag. Entries after it
+    /// may have been tampered with.
+    pub last_validated: u64,
+    /// Timestamp of the last entry in the file
+    pub last_contained: u64,
+    pub n_tags: u64,
+}
+
+/// Feeds the immutable header fields into the HMAC
+pub(crate) fn hmac_put_header(mac: &mut HmacSha256, header: &[u8]) {
+    // signature and flags, file and machine id, seqnum id and header size,
+    // hash table offsets and sizes
+    mac.update(&header[0..16]);
+    mac.update(&header[24..56]);
+    mac.update(&header[72..96]);
+    mac.update(&header[104..136]);
+}
+
+/// Feeds the immutable parts of a serialized object into the HMAC
+pub(crate) fn hmac_put_object(mac: &mut HmacSha256, object: &[u8]) {
+    mac.update(&object[..OBJECT_HEADER_SZ as usize]);
+    let size = LittleEndian::read_u64(&object[8..16]) as usize;
+    match object[0] {
+        1 => {
+            mac.update(&object[16..24]);
+            mac.update(&object[(OBJECT_HEADER_SZ + DATA_OBJECT_HEADER_SZ) as usize..size]);
+        }
+        2 => {
+            mac.update(&object[16..24]);
+            mac.update(&object[(OBJECT_HEADER_SZ + FIELD_OBJECT_HEADER_SZ) as usize..size]);
+        }
+        3 => mac.update(&object[16..size]),
+        7 => mac.update(&object[16..32]),
+        // Hash tables and entry arrays are entirely mutable
+        _ => {}
+    }
+}
+
+/// Starts the HMAC for the epoch `state` is at
+pub(crate) fn hmac_start(state: &FsprgState) -> HmacSha256 {
+    let key = state.key(TAG_LENGTH, 0);
+    HmacSha256::new_from_slice(&key).expect("HMAC takes keys of any size")
+}
+
+/// Checks the size of the object at `offset` against the minimum of its type
+/// and the end of the arena, returning the offset the object ends at
+fn object_end(header: &JournalHeader, offset: u64, type_: u8, size: u64) -> Result<u64> {
+    let min_size = match type_ {
+        1 => OBJECT_HEADER_SZ + DATA_OBJECT_HEADER_SZ,
+        2 => OBJECT_HEADER_SZ + FIELD_OBJECT_HEADER_SZ,
+        3 => OBJECT_HEADER_SZ + ENTRY_OBJECT_HEADER_SZ,
+        4..=6 => OBJECT_HEADER_SZ,
+        7 => TAG_OBJECT_SZ,
+        _ => return Err(Error::new(ErrorKind::InvalidData, format!("Invalid object at offset {:#x}", offset))),
+    };
+    if size < min_size {
+        return Err(Error::new(ErrorKind::InvalidData, format!("Object at offset {:#x} is too small", offset)));
+    }
+    // Capped so aligning the end of the last object cannot overflow
+    let arena_end = header.header_size.saturating_add(header.arena_size).min(u64::MAX - 7);
+    match offset.checked_add(size) {
+        Some(end) if end <= arena_end => Ok(end),
+        _ => Err(Error::new(ErrorKind::InvalidData, format!("Object at offset {:#x} extends past the arena", offset))),
+    }
+}
+
+/// Reads the object at `offset` in full, or just its header for hash tables
+/// and entry arrays, which contribute nothing else to the HMAC. Returns the
+/// object and the offset it ends at.
+fn read_sealed_object<R: Read + Seek>(file: &mut R, header: &JournalHeader, offset: u64) -> Result<(Vec<u8>, u64)> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut object = vec![0u8; OBJECT_HEADER_SZ as usize];
+    file.read_exact(&mut object)?;
+    let size = LittleEndian::read_u64(&object[8..16]);
+    let end = object_end(header, offset, object[0], size)?;
+    if let 1 | 2 | 3 | 7 = object[0] {
+        object.resize(size as usize, 0);
+        file.read_exact(&mut object[OBJECT_HEADER_SZ as usize..])?;
+    }
+    Ok((object, end))
+}
+
+/// Checks every TAG object of a sealed journal against `key`. Fails at the
+/// first tag that does not match, as anything after it cannot be trusted.
+pub(crate) fn verify_seal<R: Read + Seek>(
+    file: &mut R,
+    header: &JournalHeader,
+    key: &VerificationKey,
+) -> Result<SealReport> {
+    if header.compatible_flags & HEADER_COMPATIBLE_SEALED == 0 {
+        return Err(Error::new(ErrorKind::InvalidData, "Journal is not sealed"));
+    }
+
+    let mut raw_header = vec![0u8; HEADER_SZ as usize];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut raw_header)?;
+
+    let (msk, mpk) = gen_mk(&key.seed, FSPRG_RECOMMENDED_SECPAR);
+    let mut state = FsprgState::new(&mpk, &key.seed);
+
+    let mut n_tags = 0;
+    let mut last_tag = 0;
+    let mut last_epoch = 0;
+    let mut entry_realtime = None;
+    let mut last_validated = 0;
+
+    let mut offset = header.header_size;
+    while offset != 0 && offset <= header.tail_object_offset {
+        file.seek(SeekFrom::Start(offset))?;
+        let type_ = file.read_u8()?;
+        file.seek(SeekFrom::Start(offset + 8))?;
+        let size = file.read_u64::<LittleEndian>()?;
+        let end = object_end(header, offset, type_, size)?;
+
+        match type_ {
+            3 => {
+                file.seek(SeekFrom::Start(offset + OBJECT_HEADER_SZ + 8))?;
+                entry_realtime = Some(file.read_u64::<LittleEndian>()?);
+            }
+            7 => {
+                let (tag, _) = read_sealed_object(file, header, offset)?;
+                let seqnum = LittleEndian::read_u64(&tag[16..24]);
+                let epoch = LittleEndian::read_u64(&tag[24..32]);
+                if seqnum != n_tags + 1 {
+                    return Err(Error::new(ErrorKind::InvalidData,
+                        format!("Tag sequence number out of synchronization at offset {:#x}", offset)));
+                }
+                if epoch < last_epoch {
+                    return Err(Error::new(ErrorKind::InvalidData,
+                        format!("Epoch sequence out of synchronization at offset {:#x}", offset)));
+                }
+                let tag_realtime = key.start_usec.saturating_add(epoch.saturating_mul(key.interval_usec));
+                if let Some(realtime) = entry_realtime {
+                    if realtime >= tag_realtime.saturating_add(key.interval_usec) {
+                        return Err(Error::new(ErrorKind::InvalidData,
+                            format!("Tag/entry realtime timestamp out of synchronization at offset {:#x}", offset)));
+                    }
+                }
+
+                if epoch == state.epoch() + 1 {
+                    state.evolve();
+                } else if epoch != state.epoch() {
+                    state = FsprgState::seek(epoch, &msk, &key.seed);
+                }
+
+                let mut mac = hmac_start(&state);
+                let mut q = if last_tag == 0 {
+                    hmac_put_header(&mut mac, &raw_header);
+                    header.header_size
+                } else {
+                    last_tag
+                };
+                while q <= offset {
+                    let (object, object_end) = read_sealed_object(file, header, q)?;
+                    hmac_put_object(&mut mac, &object);
+                    q = align64(object_end);
+                }
+                if mac.verify_slice(&tag[32..32 + TAG_LENGTH]).is_err() {
+                    return Err(Error::new(ErrorKind::InvalidData,
+                        format!("Tag failed verification at offset {:#x}", offset)));
+                }
+
+                n_tags += 1;
+                last_tag = align64(end);
+                last_epoch = epoch;
+                if let Some(realtime) = entry_realtime {
+                    last_validated = realtime;
+                }
+            }
+            _ => {}
+        }
+
+        offset = align64(end);
+    }
+
+    Ok(SealReport {
+        first_contained: header.head_entry_realtime,
+        last_validated,
+        last_contained: header.tail_entry_realtime,
+        n_tags,
+    })
+}
@@ -0,0 +1,111 @@
+//! Per-file summaries of journal files taken from their headers, like the
+//! figures `journalctl --header` shows and `--disk-usage` adds up. No entry
+//! is read, so summarizing stays cheap however large the files are.
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::fs::File;
+use std::io::{Result, Seek, SeekFrom, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use crate::journal::{align64, Journal, JournalState, OBJECT_HEADER_SZ};
+use crate::multi::MultiJournal;
+use crate::output::format_bytes;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileSummary {
+    pub path: PathBuf,
+    pub state: JournalState,
+    /// Bytes allocated on disk for the file
+    pub disk_usage: u64,
+    /// Bytes of the arena, the part of the file after the header that holds
+    /// the objects
+    pub arena_size: u64,
+    /// Bytes of the arena taken up by objects, or `None` if the size of the
+    /// tail object is implausible
+    pub arena_used: Option<u64>,
+    pub n_objects: u64,
+    pub n_entries: u64,
+    pub seqnum_id: u128,
+    /// Sequence numbers of the oldest and newest entry, 0 without entries
+    pub head_seqnum: u64,
+    pub tail_seqnum: u64,
+    /// Realtime timestamps of the oldest and newest entry, 0 without entries
+    pub head_realtime: u64,
+    pub tail_realtime: u64,
+}
+
+impl FileSummary {
+    /// Summarizes the file at `path` that `journal` was opened from
+    pub fn new(path: &Path, journal: &Journal<File>) -> Result<FileSummary> {
+        let header = &journal.header;
+        // The arena is used up to the end of the tail object, whose size
+        // follows its type, flags and reserved bytes
+        let arena_used = match header.tail_object_offset {
+            0 => Some(0),
+            offset => {
+                let mut file = &journal.file;
+                file.seek(SeekFrom::Start(offset + 8))?;
+                let size = file.read_u64::<LittleEndian>()?;
+                let arena_end = header.header_size.saturating_add(header.arena_size);
+                offset.checked_add(size)
+                    .filter(|&end| size >= OBJECT_HEADER_SZ && end <= arena_end)
+                    .map(|end| align64(end).saturating_sub(header.header_size))
+            }
+        };
+        Ok(FileSummary {
+            path: path.to_path_buf(),
+            state: header.state,
+            disk_usage: journal.file.metadata()?.blocks() * 512,
+            arena_size: header.arena_size,
+            arena_used,
+            n_objects: header.n_objects,
+            n_entries: header.n_entries,
+            seqnum_id: header.seqnum_id,
+            head_seqnum: header.head_entry_seqnum,
+            tail_seqnum: header.tail_entry_seqnum,
+            head_realtime: header.head_entry_realtime,
+            tail_realtime: header.tail_entry_realtime,
+        })
+    }
+}
+
+/// Writes the header of the file at `path` that `journal` was opened from
+/// the way `journalctl --header` does, followed by how much of the arena
+/// objects take up
+pub fn write_header_report<W: Write>(out: &mut W, path: &Path, journal: &Journal<File>) -> Result<()> {
+    let summary = FileSummary::new(path, journal)?;
+    writeln!(out, "File path: {}", path.display())?;
+    // Measure the deepest hash chains where the header does not record them,
+    // unless the tables are too damaged to walk
+    let mut header = journal.header.clone();
+    if header.data_hash_chain_depth.is_none() || header.field_hash_chain_depth.is_none() {
+        if let Ok((data, field)) = journal.hash_chain_depths() {
+            header.data_hash_chain_depth.get_or_insert(data);
+            header.field_hash_chain_depth.get_or_insert(field);
+        }
+    }
+    writeln!(out, "{}", header)?;
+    writeln!(out, "Disk usage: {}", format_bytes(summary.disk_usage))?;
+    match (summary.arena_used, summary.arena_size) {
+        (None, _) => writeln!(out, "Arena used: unknown"),
+        (Some(used), 0) => writeln!(out, "Arena used: {} (0.0%)", format_bytes(used)),
+        (Some(used), size) => writeln!(out, "Arena used: {} ({:.1}%)", format_bytes(used), 100.0 * used as f64 / size as f64),
+    }
+}
+
+impl MultiJournal {
+    /// Summaries of all files, in the order they were opened
+    pub fn summaries(&self) -> Result<Vec<FileSummary>> {
+        self.journals.iter().map(|(path, journal)| FileSummary::new(path, journal)).collect()
+    }
+
+    /// Bytes allocated on disk for all files
+    pub fn disk_usage(&self) -> Result<u64> {
+        let mut total = 0;
+        for (_, journal) in self.journals.iter() {
+            total += journal.file.metadata()?.blocks() * 512;
+        }
+        Ok(total)
+    }
+}
@@ -0,0 +1,310 @@
+//! Code relating to parsing and converting journal timestamps goes here.
+//! Journal timestamps are microseconds, either since the epoch (realtime) or
+//! since boot (monotonic).
+
+use std::ffi::CString;
+use std::mem;
+
+pub const USEC_PER_MSEC: u64 = 1_000;
+pub const USEC_PER_SEC: u64 = 1_000_000;
+pub const USEC_PER_MINUTE: u64 = 60 * USEC_PER_SEC;
+pub const USEC_PER_HOUR: u64 = 60 * USEC_PER_MINUTE;
+pub const USEC_PER_DAY: u64 = 24 * USEC_PER_HOUR;
+pub const USEC_PER_WEEK: u64 = 7 * USEC_PER_DAY;
+pub const USEC_PER_MONTH: u64 = 2_629_800 * USEC_PER_SEC;
+pub const USEC_PER_YEAR: u64 = 31_557_600 * USEC_PER_SEC;
+
+const TIMESPAN_UNITS: &[(&str, u64)] = &[
+    ("seconds", USEC_PER_SEC),
+    ("second", USEC_PER_SEC),
+    ("sec", USEC_PER_SEC),
+    ("s", USEC_PER_SEC),
+    ("minutes", USEC_PER_MINUTE),
+    ("minute", USEC_PER_MINUTE),
+    ("min", USEC_PER_MINUTE),
+    ("months", USEC_PER_MONTH),
+    ("month", USEC_PER_MONTH),
+    ("M", USEC_PER_MONTH),
+    ("msec", 1000),
+    ("ms", 1000),
+    ("m", USEC_PER_MINUTE),
+    ("hours", USEC_PER_HOUR),
+    ("hour", USEC_PER_HOUR),
+    ("hr", USEC_PER_HOUR),
+    ("h", USEC_PER_HOUR),
+    ("days", USEC_PER_DAY),
+    ("day", USEC_PER_DAY),
+    ("d", USEC_PER_DAY),
+    ("weeks", USEC_PER_WEEK),
+    ("week", USEC_PER_WEEK),
+    ("w", USEC_PER_WEEK),
+    ("years", USEC_PER_YEAR),
+    ("year", USEC_PER_YEAR),
+    ("y", USEC_PER_YEAR),
+    ("usec", 1),
+    ("us", 1),
+];
+
+/// Parses a systemd time span such as `2weeks`, `1h 30min` or `90`, returning
+/// microseconds. Numbers without a unit are seconds.
+pub fn parse_timespan(s: &str) -> Option<u64> {
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return None;
+    }
+
+    let mut total: u64 = 0;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        if digits == 0 {
+            return None;
+        }
+        let value: u64 = rest[..digits].parse().ok()?;
+        rest = rest[digits..].trim_start();
+
+        let unit_len = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+        let unit = &rest[..unit_len];
+        let factor = if unit.is_empty() {
+            USEC_PER_SEC
+        } else {
+            TIMESPAN_UNITS.iter().find(|(name, _)| *name == unit)?.1
+        };
+        total = total.checked_add(value.checked_mul(factor)?)?;
+        rest = rest[unit_len..].trim_start();
+    }
+    Some(total)
+}
+
+/// The units `format_timespan` writes, largest first
+const TIMESPAN_FORMAT_UNITS: &[(&str, u64)] = &[
+    ("y", USEC_PER_YEAR),
+    ("month", USEC_PER_MONTH),
+    ("w", USEC_PER_WEEK),
+    ("d", USEC_PER_DAY),
+    ("h", USEC_PER_HOUR),
+    ("min", USEC_PER_MINUTE),
+    ("s", USEC_PER_SEC),
+    ("ms", USEC_PER_MSEC),
+    ("us", 1),
+];
+
+/// Formats the time span `usec` like systemd does, e.g. `2h 10min 4.205s`,
+/// leaving out what is smaller than `accuracy` microseconds. Spans shorter
+/// than a minute get their fraction after a dot rather than smaller units.
+pub fn format_timespan(usec: u64, accuracy: u64) -> String {
+    if usec == 0 {
+        return "0".to_owned();
+    }
+    let mut out = String::new();
+    let mut t = usec;
+    for &(suffix, unit) in TIMESPAN_FORMAT_UNITS {
+        if t == 0 || (t < accuracy && !out.is_empty()) {
+            break;
+        }
+        if t < unit {
+            continue;
+        }
+        let sep = if out.is_empty() { "" } else { " " };
+        let (a, mut b) = (t / unit, t % unit);
+        let mut digits = unit.ilog10() as i32;
+        if t < USEC_PER_MINUTE && b > 0 {
+            let mut cc = accuracy;
+            while cc > 1 {
+                b /= 10;
+                digits -= 1;
+                cc /= 10;
+            }
+            if digits > 0 {
+                out.push_str(&format!("{}{}.{:0width$}{}", sep, a, b, suffix, width = digits as usize));
+                break;
+            }
+        }
+        out.push_str(&format!("{}{}{}", sep, a, suffix));
+        t = b;
+    }
+    out
+}
+
+const WEEKDAYS: [(&str, &str); 7] = [
+    ("Sun", "Sunday"),
+    ("Mon", "Monday"),
+    ("Tue", "Tuesday"),
+    ("Wed", "Wednesday"),
+    ("Thu", "Thursday"),
+    ("Fri", "Friday"),
+    ("Sat", "Saturday"),
+];
+
+/// Parses a point in time the way journalctl's `--since` and `--until` do,
+/// returning realtime microseconds. Accepted are `now`, `today`,
+/// `yesterday` and `tomorrow`, time spans relative to `now` such as `-1h`,
+/// `+30min` or `2 days ago`, seconds since the epoch as `@1700000000`, and
+/// dates and times such as `2026-10-17 08:00:00`, `2026-10-17` or `08:00`
+/// with an optional weekday in front and `UTC` after them. Dates and times
+/// are in the local time zone unless `UTC` is given.
+pub fn parse_timestamp(s: &str, now: u64) -> Option<u64> {
+    let s = s.trim();
+    match s {
+        "now" => return Some(now),
+        "today" => return start_of_day(now, 0),
+        "yesterday" => return start_of_day(now, -1),
+        "tomorrow" => return start_of_day(now, 1),
+        _ => {}
+    }
+    if let Some(span) = s.strip_prefix('+') {
+        return now.checked_add(parse_timespan(span)?);
+    }
+    if let Some(span) = s.strip_prefix('-').or_else(|| s.strip_suffix(" ago")) {
+        return now.checked_sub(parse_timespan(span)?);
+    }
+    if let Some(span) = s.strip_suffix(" left") {
+        return now.checked_add(parse_timespan(span)?);
+    }
+    if let Some(epoch) = s.strip_prefix('@') {
+        let (secs, usec) = parse_seconds(epoch)?;
+        return secs.checked_mul(USEC_PER_SEC)?.checked_add(usec);
+    }
+
+    let (s, utc) = match s.strip_suffix(" UTC") {
+        Some(rest) => (rest.trim_end(), true),
+        None => (s, false),
+    };
+    let mut words: Vec<&str> = s.split(' ').filter(|w| !w.is_empty()).collect();
+    let weekday = match words.first().and_then(|w| WEEKDAYS.iter().position(|(short, long)| w == short || w == long)) {
+        Some(day) => {
+            words.remove(0);
+            Some(day as i32)
+        }
+        None => None,
+    };
+
+    let mut tm = broken_down(now / USEC_PER_SEC, utc)?;
+    let (date, time) = match words.as_slice() {
+        [date, time] => (Some(*date), Some(*time)),
+        [word] if word.contains('-') => (Some(*word), None),
+        [word] => (None, Some(*word)),
+        _ => return None,
+    };
+    if let Some(date) = date {
+        let fields: Vec<&str> = date.split('-').collect();
+        let (year, month, day): (i32, i32, i32) = match fields.as_slice() {
+            [y, m, d] if y.len() == 4 => (y.parse().ok()?, m.parse().ok()?, d.parse().ok()?),
+            _ => return None,
+        };
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return None;
+        }
+        tm.tm_year = year - 1900;
+        tm.tm_mon = month - 1;
+        tm.tm_mday = day;
+    }
+    let mut usec = 0;
+    (tm.tm_hour, tm.tm_min, tm.tm_sec) = (0, 0, 0);
+    if let Some(time) = time {
+        let fields: Vec<&str> = time.split(':').collect();
+        let (hour, minute, second) = match fields.as_slice() {
+            [h, m] => (h, m, "0"),
+            [h, m, s] => (h, m, *s),
+            _ => return None,
+        };
+        let (second, fraction) = parse_seconds(second)?;
+        tm.tm_hour = hour.parse().ok().filter(|h| (0..24).contains(h))?;
+        tm.tm_min = minute.parse().ok().filter(|m| (0..60).contains(m))?;
+        tm.tm_sec = Some(second as i32).filter(|s| (0..=60).contains(s))?;
+        usec = fraction;
+    }
+
+    let secs = to_seconds(&mut tm, utc)?;
+    if weekday.is_some_and(|day| day != tm.tm_wday) {
+        return None;
+    }
+    secs.checked_mul(USEC_PER_SEC)?.checked_add(usec)
+}
+
+/// Parses seconds with an optional fraction such as `12.5`, returning the
+/// whole seconds and the fraction in microseconds
+fn parse_seconds(s: &str) -> Option<(u64, u64)> {
+    let (whole, fraction) = match s.find('.') {
+        Some(dot) => (&s[..dot], &s[dot + 1..]),
+        None => (s, ""),
+    };
+    if whole.is_empty() || !whole.bytes().all(|c| c.is_ascii_digit()) || fraction.len() > 6
+        || !fraction.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let usec = format!("{:0<6}", fraction).parse().ok()?;
+    Some((whole.parse().ok()?, usec))
+}
+
+/// `secs` since the epoch as broken-down time, local or UTC
+fn broken_down(secs: u64, utc: bool) -> Option<libc::tm> {
+    let secs = secs as libc::time_t;
+    // Safety: `tm` is plain data that gmtime_r/localtime_r fill in
+    unsafe {
+        let mut tm: libc::tm = mem::zeroed();
+        let converted = if utc {
+            libc::gmtime_r(&secs, &mut tm)
+        } else {
+            libc::localtime_r(&secs, &mut tm)
+        };
+        if converted.is_null() {
+            None
+        } else {
+            Some(tm)
+        }
+    }
+}
+
+/// Converts broken-down time back to seconds since the epoch, normalizing
+/// `tm` and filling in its weekday
+fn to_seconds(tm: &mut libc::tm, utc: bool) -> Option<u64> {
+    // Let the C library find out whether daylight saving time applies
+    tm.tm_isdst = -1;
+    // Safety: `tm` is a valid, initialized struct tm
+    let secs = unsafe {
+        if utc {
+            libc::timegm(tm)
+        } else {
+            libc::mktime(tm)
+        }
+    };
+    if secs < 0 {
+        return None;
+    }
+    Some(secs as u64)
+}
+
+/// Midnight in the local time zone `days` days from the day of `now`
+fn start_of_day(now: u64, days: i32) -> Option<u64> {
+    let mut tm = broken_down(now / USEC_PER_SEC, false)?;
+    (tm.tm_hour, tm.tm_min, tm.tm_sec) = (0, 0, 0);
+    tm.tm_mday += days;
+    to_seconds(&mut tm, false)?.checked_mul(USEC_PER_SEC)
+}
+
+/// Formats the realtime timestamp `usec` with the `strftime` format `format`,
+/// in the local time zone, or in UTC if `utc` is set. `%Z` is `UTC` for the
+/// latter, where the C library would say `GMT`. `%:z` is the UTC offset with
+/// a colon, e.g. `+01:00`, which the C library does not have.
+pub fn format_timestamp(usec: u64, format: &str, utc: bool) -> String {
+    let tm = match broken_down(usec / USEC_PER_SEC, utc) {
+        Some(tm) => tm,
+        None => return String::new(),
+    };
+    let mut format = if utc { format.replace("%Z", "UTC") } else { format.to_owned() };
+    if format.contains("%:z") {
+        let sign = if tm.tm_gmtoff < 0 { '-' } else { '+' };
+        let minutes = tm.tm_gmtoff.abs() / 60;
+        format = format.replace("%:z", &format!("{}{:02}:{:02}", sign, minutes / 60, minutes % 60));
+    }
+    let format = match CString::new(format) {
+        Ok(f) => f,
+        Err(_) => return String::new(),
+    };
+    let mut buf = [0u8; 256];
+    // Safety: strftime writes at most `buf.len()` bytes into `buf`
+    let len = unsafe {
+        libc::strftime(buf.as_mut_ptr() as *mut libc::c_char, buf.len(), format.as_ptr(), &tm)
+    };
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
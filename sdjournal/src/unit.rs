@@ -0,0 +1,134 @@
+//! Selecting the entries about a systemd unit like `journalctl -u` and
+//! `--user-unit`. Besides the messages of the unit itself these are the ones
+//! systemd, systemd-coredump and other privileged daemons logged about it.
+//! Unit names may be globs, which are expanded against the units the journal
+//! has entries of.
+
+use std::collections::BTreeSet;
+use std::ffi::CString;
+use std::io::Result;
+use std::str;
+
+use crate::fields::*;
+use crate::filter::Filter;
+use crate::multi::MultiJournal;
+
+/// The fields naming the system unit an entry is about
+pub const SYSTEM_UNIT_FIELDS: [&str; 5] = [SYSTEMD_UNIT, COREDUMP_UNIT, UNIT, OBJECT_SYSTEMD_UNIT, SYSTEMD_SLICE];
+/// The fields naming the user unit an entry is about
+pub const USER_UNIT_FIELDS: [&str; 5] = [SYSTEMD_USER_UNIT, USER_UNIT, COREDUMP_USER_UNIT, OBJECT_SYSTEMD_USER_UNIT,
+                                         SYSTEMD_USER_SLICE];
+
+/// The message id of the coredumps systemd-coredump logs
+const COREDUMP_MESSAGE_ID: &str = "fc2e22bc6ee647b6b90729ab34a250b1";
+
+const UNIT_TYPES: [&str; 11] = ["service", "mount", "swap", "socket", "target", "device", "automount", "timer",
+                                "path", "slice", "scope"];
+
+/// Whether the unit name `name` is a glob pattern
+pub fn is_glob(name: &str) -> bool {
+    name.contains(['*', '?', '['])
+}
+
+/// Turns what the user typed into a unit name like systemd does: slashes
+/// become dashes, other characters not allowed in unit names are escaped
+/// as `\xNN`, and names without a unit type get `.service` appended unless
+/// they are globs. Unlike systemd, paths are not turned into mount or
+/// device units.
+pub fn mangle_unit_name(name: &str) -> String {
+    let glob = is_glob(name);
+    let mut mangled = String::with_capacity(name.len());
+    for c in name.bytes() {
+        match c {
+            b'/' => mangled.push('-'),
+            c if c.is_ascii_alphanumeric() || b":-_.\\@".contains(&c) => mangled.push(c as char),
+            c if glob && b"[]!*?".contains(&c) => mangled.push(c as char),
+            c => mangled.push_str(&format!("\\x{:02x}", c)),
+        }
+    }
+    let typed = match mangled.rfind('.') {
+        Some(dot) => UNIT_TYPES.contains(&&mangled[dot + 1..]),
+        None => false,
+    };
+    if !typed && !is_glob(&mangled) {
+        mangled.push_str(".service");
+    }
+    mangled
+}
+
+/// A filter of `FIELD=value` matches that must all hold
+fn all_of(payloads: &[&str]) -> Filter {
+    let mut filter = Filter::new();
+    for payload in payloads {
+        filter.add_match(payload.as_bytes()).expect("valid unit match");
+    }
+    filter
+}
+
+/// The alternative filters that together select the entries about the
+/// system unit `unit`, to be passed to `Filter::add_any`
+pub fn unit_matches(unit: &str) -> Vec<Filter> {
+    let root = format!("{}=0", UID);
+    let mut alternatives = vec![
+        // Messages of the unit itself
+        all_of(&[&format!("{}={}", SYSTEMD_UNIT, unit)]),
+        // Its coredumps
+        all_of(&[&format!("{}={}", MESSAGE_ID, COREDUMP_MESSAGE_ID), &root,
+                 &format!("{}={}", COREDUMP_UNIT, unit)]),
+        // Messages of systemd about it
+        all_of(&[&format!("{}=1", PID), &format!("{}={}", UNIT, unit)]),
+        // Messages of privileged daemons about it
+        all_of(&[&root, &format!("{}={}", OBJECT_SYSTEMD_UNIT, unit)]),
+    ];
+    if unit.ends_with(".slice") {
+        alternatives.push(all_of(&[&format!("{}={}", SYSTEMD_SLICE, unit)]));
+    }
+    alternatives
+}
+
+/// The alternative filters that together select the entries about the
+/// unit `unit` of the user `uid`'s service manager
+pub fn user_unit_matches(unit: &str, uid: u32) -> Vec<Filter> {
+    let (root, user) = (format!("{}=0", UID), format!("{}={}", UID, uid));
+    // Like in `sd_journal_add_match(3)`, two `_UID` matches mean either uid
+    let mut alternatives = vec![
+        // Messages of the unit itself
+        all_of(&[&format!("{}={}", SYSTEMD_USER_UNIT, unit), &user]),
+        // Messages of the user's systemd about it
+        all_of(&[&format!("{}={}", USER_UNIT, unit), &user]),
+        // Its coredumps
+        all_of(&[&format!("{}={}", COREDUMP_USER_UNIT, unit), &user, &root]),
+        // Messages of privileged daemons about it
+        all_of(&[&format!("{}={}", OBJECT_SYSTEMD_USER_UNIT, unit), &user, &root]),
+    ];
+    if unit.ends_with(".slice") {
+        alternatives.push(all_of(&[&format!("{}={}", SYSTEMD_USER_SLICE, unit), &user]));
+    }
+    alternatives
+}
+
+/// Whether `name` matches the glob `pattern`, like `fnmatch(3)` without
+/// backslash escapes
+fn glob_matches(pattern: &str, name: &[u8]) -> bool {
+    match (CString::new(pattern), CString::new(name)) {
+        // Safety: both are NUL-terminated strings that outlive the call
+        (Ok(pattern), Ok(name)) => unsafe { libc::fnmatch(pattern.as_ptr(), name.as_ptr(), libc::FNM_NOESCAPE) == 0 },
+        _ => false,
+    }
+}
+
+/// The units that any of the `fields`, e.g. `SYSTEM_UNIT_FIELDS`, names in
+/// `journals` and that match one of the glob `patterns`
+pub fn expand_unit_globs(journals: &MultiJournal, fields: &[&str], patterns: &[String]) -> Result<BTreeSet<String>> {
+    let mut units = BTreeSet::new();
+    for field in fields {
+        for value in journals.unique_values(field)? {
+            if patterns.iter().any(|p| glob_matches(p, &value)) {
+                if let Ok(unit) = str::from_utf8(&value) {
+                    units.insert(unit.to_owned());
+                }
+            }
+        }
+    }
+    Ok(units)
+}
@@ -0,0 +1,154 @@
+use std::fs::{self, File};
+use std::io::Result;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use crate::journal::*;
+use crate::rotate::ArchivedName;
+
+/// Limits a journal directory is vacuumed down to. A limit of `None` is
+/// never exceeded.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VacuumLimits {
+    /// Total disk usage of all journal files in bytes
+    pub max_disk_usage: Option<u64>,
+    /// Number of journal files, active ones included
+    pub max_files: Option<usize>,
+    /// Archived files whose newest entry is older than this realtime
+    /// timestamp are removed
+    pub min_realtime: Option<u64>,
+}
+
+/// A journal file vacuuming may remove
+#[derive(Debug, Clone, PartialEq)]
+pub struct VacuumCandidate {
+    pub path: PathBuf,
+    /// Bytes allocated on disk for the file
+    pub disk_usage: u64,
+    pub seqnum_id: u128,
+    pub head_seqnum: u64,
+    pub head_realtime: u64,
+    pub tail_realtime: u64,
+}
+
+/// Parses a size like `500M` or `1G` with base 1024 suffixes, returning bytes
+pub fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let (number, factor) = match s.chars().last()? {
+        'B' => (&s[..s.len() - 1], 1),
+        'K' => (&s[..s.len() - 1], 1 << 10),
+        'M' => (&s[..s.len() - 1], 1 << 20),
+        'G' => (&s[..s.len() - 1], 1 << 30),
+        'T' => (&s[..s.len() - 1], 1 << 40),
+        'P' => (&s[..s.len() - 1], 1 << 50),
+        'E' => (&s[..s.len() - 1], 1 << 60),
+        _ => (s, 1),
+    };
+    number.trim().parse::<u64>().ok()?.checked_mul(factor)
+}
+
+/// Lists the journal files in `directory` that vacuuming down to `limits`
+/// removes, oldest first. Active files, and archived files that are still
+/// marked online, are never included.
+pub fn vacuum_plan<P: AsRef<Path>>(directory: P, limits: &VacuumLimits) -> Result<Vec<VacuumCandidate>> {
+    let mut candidates = Vec::new();
+    let mut total_usage = 0;
+    let mut total_files = 0;
+
+    for dir_entry in fs::read_dir(directory)? {
+        let dir_entry = dir_entry?;
+        let metadata = dir_entry.metadata()?;
+        let path = dir_entry.path();
+        let file_name = match path.file_name().and_then(|n| n.to_str()) {
+            Some(n) => n.to_owned(),
+            None => continue,
+        };
+        if !metadata.is_file() || !(file_name.ends_with(".journal") || file_name.ends_with(".journal~")) {
+            continue;
+        }
+
+        let disk_usage = metadata.blocks() * 512;
+        total_usage += disk_usage;
+        total_files += 1;
+
+        if let Some(candidate) = load_candidate(&path, &file_name, disk_usage) {
+            candidates.push(candidate);
+        }
+    }
+
+    candidates.sort_by_key(|c| (c.head_realtime, c.head_seqnum));
+
+    let mut plan = Vec::new();
+    for candidate in candidates {
+        let too_large = limits.max_disk_usage.is_some_and(|max| total_usage > max);
+        let too_many = limits.max_files.is_some_and(|max| total_files > max);
+        let too_old = limits.min_realtime.is_some_and(|min| candidate.tail_realtime < min);
+        if !(too_large || too_many || too_old) {
+            continue;
+        }
+        total_usage -= candidate.disk_usage;
+        total_files -= 1;
+        plan.push(candidate);
+    }
+    Ok(plan)
+}
+
+/// Removes the journal files `vacuum_plan` selects and returns them
+pub fn vacuum<P: AsRef<Path>>(directory: P, limits: &VacuumLimits) -> Result<Vec<VacuumCandidate>> {
+    let plan = vacuum_plan(directory, limits)?;
+    for candidate in plan.iter() {
+        fs::remove_file(&candidate.path)?;
+    }
+    Ok(plan)
+}
+
+/// Builds a candidate from an archived (`name@...journal`) or disposed
+/// (`name@...journal~`) file, or returns `None` for files that must be kept
+fn load_candidate(path: &Path, file_name: &str, disk_usage: u64) -> Option<VacuumCandidate> {
+    let header = File::open(path).ok().and_then(|f| Journal::new(f).ok()).map(|j| j.header);
+
+    if let Some(stripped) = file_name.strip_suffix('~') {
+        // Disposed files are named name@realtime-random.journal~ and may be
+        // too damaged to read, so fall back to the name
+        let at = stripped.rfind('@')?;
+        let realtime = u64::from_str_radix(stripped[at + 1..].split('-').next()?, 16).ok()?;
+        return Some(match header {
+            Some(ref h) if &h.signature == HEADER_SIGNATURE && h.state != JournalState::Online => {
+                candidate_from_header(path, disk_usage, h)
+            }
+            _ => VacuumCandidate {
+                path: path.to_path_buf(),
+                disk_usage,
+                seqnum_id: 0,
+                head_seqnum: 0,
+                head_realtime: realtime,
+                tail_realtime: realtime,
+            },
+        });
+    }
+
+    let name = ArchivedName::parse(file_name)?;
+    match header {
+        Some(ref h) if h.state == JournalState::Online => None,
+        Some(ref h) if &h.signature == HEADER_SIGNATURE => Some(candidate_from_header(path, disk_usage, h)),
+        _ => Some(VacuumCandidate {
+            path: path.to_path_buf(),
+            disk_usage,
+            seqnum_id: name.seqnum_id,
+            head_seqnum: name.head_seqnum,
+            head_realtime: name.head_realtime,
+            tail_realtime: name.head_realtime,
+        }),
+    }
+}
+
+fn candidate_from_header(path: &Path, disk_usage: u64, header: &JournalHeader) -> VacuumCandidate {
+    VacuumCandidate {
+        path: path.to_path_buf(),
+        disk_usage,
+        seqnum_id: header.seqnum_id,
+        head_seqnum: header.head_entry_seqnum,
+        head_realtime: header.head_entry_realtime,
+        tail_realtime: header.tail_entry_realtime,
+    }
+}
@@ -0,0 +1,637 @@
+//! Consistency checks of journal files. Unlike the iterators, verification
+//! does not stop at the first problem but reports everything it finds.
+
+use byteorder::{ByteOrder, LittleEndian};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::io::{Read, Result, Seek, SeekFrom};
+
+use crate::hash::rhash64;
+use crate::journal::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// An inconsistency readers work around, such as a file that was not
+    /// closed cleanly
+    Warning,
+    /// Corruption that loses or falsifies entries
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A single problem found by `Journal::verify`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    /// Offset of the offending object, or 0 for the file header
+    pub offset: u64,
+    /// Type of the offending object, `None` for the file header or objects
+    /// whose header cannot be read
+    pub object_type: Option<ObjectType>,
+    pub severity: Severity,
+    pub description: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.object_type {
+            Some(t) => write!(f, "{:08x}: {}: {} object: {}", self.offset, self.severity, t, self.description),
+            None if self.offset == 0 => write!(f, "{:08x}: {}: header: {}", self.offset, self.severity, self.description),
+            None => write!(f, "{:08x}: {}: {}", self.offset, self.severity, self.description),
+        }
+    }
+}
+
+/// Overall outcome of a verification
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VerifyStatus {
+    /// No problems at all
+    Clean,
+    /// Only problems readers work around
+    Repaired,
+    /// At least one problem that loses or falsifies entries
+    Corrupt,
+}
+
+/// Everything `Journal::verify` found, with counts of the objects it checked
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VerifyReport {
+    pub problems: Vec<Problem>,
+    pub n_objects: u64,
+    pub n_data: u64,
+    pub n_fields: u64,
+    pub n_entries: u64,
+    pub n_entry_arrays: u64,
+    pub n_hash_tables: u64,
+    pub n_tags: u64,
+}
+
+impl VerifyReport {
+    pub fn status(&self) -> VerifyStatus {
+        match self.problems.iter().map(|p| p.severity).max() {
+            None => VerifyStatus::Clean,
+            Some(Severity::Warning) => VerifyStatus::Repaired,
+            Some(Severity::Error) => VerifyStatus::Corrupt,
+        }
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    pub fn is_corrupt(&self) -> bool {
+        self.status() == VerifyStatus::Corrupt
+    }
+
+    fn warning<S: Into<String>>(&mut self, offset: u64, object_type: Option<ObjectType>, description: S) {
+        self.problems.push(Problem {
+            offset,
+            object_type,
+            severity: Severity::Warning,
+            description: description.into(),
+        });
+    }
+
+    fn error<S: Into<String>>(&mut self, offset: u64, object_type: Option<ObjectType>, description: S) {
+        self.problems.push(Problem {
+            offset,
+            object_type,
+            severity: Severity::Error,
+            description: description.into(),
+        });
+    }
+
+    fn count(&mut self, object_type: ObjectType) {
+        self.n_objects += 1;
+        match object_type {
+            ObjectType::ObjectData => self.n_data += 1,
+            ObjectType::ObjectField => self.n_fields += 1,
+            ObjectType::ObjectEntry => self.n_entries += 1,
+            ObjectType::ObjectEntryArray => self.n_entry_arrays += 1,
+            ObjectType::ObjectDataHashTable | ObjectType::ObjectFieldHashTable => self.n_hash_tables += 1,
+            ObjectType::ObjectTag => self.n_tags += 1,
+            _ => {}
+        }
+    }
+}
+
+/// What verification remembers of a DATA object for the cross checks
+struct DataInfo {
+    hash: u64,
+    /// What the object adds to the XOR hash of its entries, which is always
+    /// made of Jenkins hashes so that it can be compared across files. `None`
+    /// if it cannot be computed for a compressed payload.
+    xor_hash: Option<u64>,
+    entry_offset: u64,
+    entry_array_offset: u64,
+    n_entries: u64,
+}
+
+/// What verification remembers of an ENTRY object for the cross checks
+struct EntryInfo {
+    seqnum: u64,
+    realtime: u64,
+    /// Offsets of the DATA objects of the entry
+    items: Vec<u64>,
+}
+
+struct Verifier<'a, R: Read + Seek> {
+    file: &'a mut R,
+    header: &'a JournalHeader,
+    report: VerifyReport,
+    /// End of the arena, or of the file if that is shorter
+    end: u64,
+    /// Type of every object found walking the arena
+    objects: BTreeMap<u64, ObjectType>,
+    data: HashMap<u64, DataInfo>,
+    fields: HashMap<u64, u64>,
+    entries: HashMap<u64, EntryInfo>,
+}
+
+/// Checks the header, every object of the arena and the links between them,
+/// following systemd's `journal-verify.c`
+pub(crate) fn verify<R: Read + Seek>(file: &mut R, header: &JournalHeader) -> VerifyReport {
+    let file_size = match file.seek(SeekFrom::End(0)) {
+        Ok(size) => size,
+        Err(e) => {
+            let mut report = VerifyReport::default();
+            report.error(0, None, format!("Cannot determine file size: {}", e));
+            return report;
+        }
+    };
+
+    let mut verifier = Verifier {
+        file,
+        header,
+        report: VerifyReport::default(),
+        end: header.header_size.saturating_add(header.arena_size).min(file_size),
+        objects: BTreeMap::new(),
+        data: HashMap::new(),
+        fields: HashMap::new(),
+        entries: HashMap::new(),
+    };
+
+    if !verifier.verify_header(file_size) {
+        return verifier.report;
+    }
+    if header.incompatible_flags & HEADER_INCOMPATIBLE_COMPACT != 0 {
+        verifier.report.warning(0, None, "Compact journal files are not supported, objects were not checked");
+        return verifier.report;
+    }
+
+    verifier.verify_objects();
+    verifier.verify_counters();
+    verifier.verify_hash_table(header.data_hash_table_offset, header.data_hash_table_size, ObjectType::ObjectData);
+    verifier.verify_hash_table(header.field_hash_table_offset, header.field_hash_table_size, ObjectType::ObjectField);
+    verifier.verify_main_entry_array();
+    verifier.verify_data_entries();
+    verifier.report
+}
+
+impl<'a, R: Read + Seek> Verifier<'a, R> {
+    /// Checks the header fields everything else relies on. Returns false if
+    /// the objects cannot be located.
+    fn verify_header(&mut self, file_size: u64) -> bool {
+        let header = self.header;
+        if &header.signature != HEADER_SIGNATURE {
+            self.report.error(0, None, "Invalid signature");
+            return false;
+        }
+        if header.header_size < HEADER_SZ || !is_valid64(header.header_size) {
+            self.report.error(0, None, format!("Invalid header size {}", header.header_size));
+            return false;
+        }
+        if header.header_size.saturating_add(header.arena_size) > file_size {
+            self.report.error(0, None, format!("Arena of {} bytes extends beyond the end of the file", header.arena_size));
+        }
+        if header.state == JournalState::Online {
+            self.report.warning(0, None, "Journal is online or was not closed cleanly");
+        }
+
+        let mut valid = true;
+        for &(name, offset, size) in &[("data", header.data_hash_table_offset, header.data_hash_table_size),
+                                       ("field", header.field_hash_table_offset, header.field_hash_table_size)] {
+            if size == 0 || size % HASH_ITEM_SZ != 0 || !is_valid64(offset)
+                || offset < header.header_size + OBJECT_HEADER_SZ || offset.saturating_add(size) > self.end {
+                self.report.error(0, None, format!("Invalid {} hash table at {:#x} of {} bytes", name, offset, size));
+                valid = false;
+            }
+        }
+        if !is_valid64(header.tail_object_offset) || header.tail_object_offset < header.header_size
+            || header.tail_object_offset >= self.end {
+            self.report.error(0, None, format!("Invalid tail object offset {:#x}", header.tail_object_offset));
+            valid = false;
+        }
+        if !is_valid64(header.entry_array_offset) || header.entry_array_offset >= self.end {
+            self.report.error(0, None, format!("Invalid entry array offset {:#x}", header.entry_array_offset));
+        }
+        valid
+    }
+
+    /// Walks the arena object by object up to the tail object
+    fn verify_objects(&mut self) {
+        let mut offset = self.header.header_size;
+        let mut last = 0;
+        while offset <= self.header.tail_object_offset {
+            let object = match read_object(self.file, offset, self.end) {
+                Ok(o) => o,
+                Err(e) => {
+                    self.report.error(offset, None, format!("Cannot read object: {}", e));
+                    return;
+                }
+            };
+            let type_ = ObjectType::from_u8(object[0]);
+            let size = LittleEndian::read_u64(&object[8..16]);
+            if !self.verify_object_size(offset, type_, size) {
+                // Without a trustworthy size there is no way to find the
+                // next object
+                return;
+            }
+
+            self.report.count(type_);
+            self.objects.insert(offset, type_);
+            match type_ {
+                ObjectType::ObjectData => self.verify_data(offset, &object),
+                ObjectType::ObjectField => self.verify_field(offset, &object),
+                ObjectType::ObjectEntry => self.verify_entry(offset, &object),
+                ObjectType::ObjectEntryArray => self.verify_entry_array(offset, &object),
+                _ => {}
+            }
+            last = offset;
+            offset = align64(offset + size);
+        }
+
+        if last != self.header.tail_object_offset {
+            self.report.warning(0, None, format!("Tail object offset {:#x} is not the offset of an object, the last one is at {:#x}",
+                                                 self.header.tail_object_offset, last));
+        }
+    }
+
+    /// Checks the type and size of an object. Returns false if the size
+    /// cannot be trusted.
+    fn verify_object_size(&mut self, offset: u64, type_: ObjectType, size: u64) -> bool {
+        if object_size_layout(type_).is_none() {
+            self.report.error(offset, None, format!("Invalid object type {}", type_));
+            return false;
+        }
+        if !is_valid_object_size(type_, size) {
+            self.report.error(offset, Some(type_), format!("Invalid object size {}", size));
+            return false;
+        }
+        if offset.saturating_add(size) > self.end {
+            self.report.error(offset, Some(type_), "Object extends beyond the end of the arena");
+            return false;
+        }
+        true
+    }
+
+    fn verify_data(&mut self, offset: u64, object: &[u8]) {
+        let type_ = Some(ObjectType::ObjectData);
+        let hash = LittleEndian::read_u64(&object[16..24]);
+        let payload = &object[64..];
+        let compressed = object[1] & OBJECT_COMPRESSED_MASK != 0;
+        let keyed = self.header.incompatible_flags & HEADER_INCOMPATIBLE_KEYED_HASH != 0;
+        let info = DataInfo {
+            hash,
+            xor_hash: match (keyed, compressed) {
+                (false, _) => Some(hash),
+                (true, false) => Some(rhash64(payload)),
+                (true, true) => None,
+            },
+            entry_offset: LittleEndian::read_u64(&object[40..48]),
+            entry_array_offset: LittleEndian::read_u64(&object[48..56]),
+            n_entries: LittleEndian::read_u64(&object[56..64]),
+        };
+
+        // The hash and the FIELD=value form are of the uncompressed payload
+        if !compressed {
+            let computed = self.header.hash_payload(payload);
+            if computed != hash {
+                self.report.error(offset, type_, format!("Hash mismatch: stored {:016x}, computed {:016x}", hash, computed));
+            }
+            match payload.iter().position(|&c| c == b'=') {
+                Some(eq) if eq > 0 => {}
+                _ => self.report.error(offset, type_, "Payload is not FIELD=value"),
+            }
+        }
+        for &(name, link) in &[("next hash", LittleEndian::read_u64(&object[24..32])),
+                               ("next field", LittleEndian::read_u64(&object[32..40])),
+                               ("entry", info.entry_offset),
+                               ("entry array", info.entry_array_offset)] {
+            if !is_valid64(link) || link >= self.end {
+                self.report.error(offset, type_, format!("Invalid {} offset {:#x}", name, link));
+            }
+        }
+        if (info.entry_offset == 0) != (info.n_entries == 0) || (info.entry_array_offset != 0 && info.n_entries < 2) {
+            self.report.error(offset, type_, format!("Entry links inconsistent with {} entries", info.n_entries));
+        } else if info.n_entries == 0 {
+            self.report.warning(offset, type_, "Data object is not referenced by any entry");
+        }
+        self.data.insert(offset, info);
+    }
+
+    fn verify_field(&mut self, offset: u64, object: &[u8]) {
+        let type_ = Some(ObjectType::ObjectField);
+        let hash = LittleEndian::read_u64(&object[16..24]);
+        let payload = &object[40..];
+        if payload.is_empty() {
+            self.report.error(offset, type_, "Empty field name");
+        }
+        let computed = self.header.hash_payload(payload);
+        if computed != hash {
+            self.report.error(offset, type_, format!("Hash mismatch: stored {:016x}, computed {:016x}", hash, computed));
+        }
+        self.fields.insert(offset, hash);
+    }
+
+    fn verify_entry(&mut self, offset: u64, object: &[u8]) {
+        let type_ = Some(ObjectType::ObjectEntry);
+        let seqnum = LittleEndian::read_u64(&object[16..24]);
+        let realtime = LittleEndian::read_u64(&object[24..32]);
+        let xor_hash = LittleEndian::read_u64(&object[56..64]);
+        if seqnum == 0 {
+            self.report.error(offset, type_, "Invalid seqnum 0");
+        }
+        if realtime == 0 {
+            self.report.error(offset, type_, "Invalid realtime timestamp 0");
+        }
+
+        let mut items = Vec::new();
+        let mut computed_xor = Some(0);
+        for item in object[64..].chunks(ENTRY_ITEM_SZ as usize) {
+            let item_offset = LittleEndian::read_u64(&item[0..8]);
+            let item_hash = LittleEndian::read_u64(&item[8..16]);
+            let item_xor = self.data.get(&item_offset).map_or(Some(item_hash), |d| d.xor_hash);
+            computed_xor = computed_xor.zip(item_xor).map(|(a, b)| a ^ b);
+            match self.data.get(&item_offset) {
+                Some(data) if data.hash != item_hash => {
+                    self.report.error(offset, type_, format!("Item hash does not match data object at {:#x}", item_offset));
+                }
+                Some(_) => {}
+                // DATA objects always precede the entries referencing them
+                None => self.report.error(offset, type_, format!("Item does not point to a data object: {:#x}", item_offset)),
+            }
+            items.push(item_offset);
+        }
+        if items.is_empty() {
+            self.report.error(offset, type_, "Entry has no items");
+        }
+        match computed_xor {
+            Some(computed) if computed != xor_hash => {
+                self.report.error(offset, type_, format!("XOR hash mismatch: stored {:016x}, computed {:016x}", xor_hash, computed));
+            }
+            _ => {}
+        }
+        self.entries.insert(offset, EntryInfo { seqnum, realtime, items });
+    }
+
+    fn verify_entry_array(&mut self, offset: u64, object: &[u8]) {
+        let next = LittleEndian::read_u64(&object[16..24]);
+        if !is_valid64(next) || next >= self.end || (next != 0 && next <= offset) {
+            self.report.error(offset, Some(ObjectType::ObjectEntryArray), format!("Invalid next entry array offset {:#x}", next));
+        }
+    }
+
+    /// Compares the header counters with the objects actually found
+    fn verify_counters(&mut self) {
+        let header = self.header;
+        let report = &self.report;
+        let mut mismatches = Vec::new();
+        for &(name, stored, found) in &[("objects", header.n_objects, report.n_objects),
+                                        ("entries", header.n_entries, report.n_entries),
+                                        ("data objects", header.n_data, report.n_data),
+                                        ("field objects", header.n_fields, report.n_fields),
+                                        ("tags", header.n_tags, report.n_tags),
+                                        ("entry arrays", header.n_entry_arrays, report.n_entry_arrays)] {
+            if stored != found {
+                mismatches.push(format!("Header claims {} {} but {} were found", stored, name, found));
+            }
+        }
+        for m in mismatches {
+            self.report.warning(0, None, m);
+        }
+    }
+
+    /// Follows every chain of a hash table, checking that each object is of
+    /// `type_`, sits in the bucket of its hash and is listed exactly once
+    fn verify_hash_table(&mut self, table: u64, table_size: u64, type_: ObjectType) {
+        let table_type = if type_ == ObjectType::ObjectData {
+            ObjectType::ObjectDataHashTable
+        } else {
+            ObjectType::ObjectFieldHashTable
+        };
+        let table_object = table - OBJECT_HEADER_SZ;
+        if self.objects.get(&table_object) != Some(&table_type) {
+            self.report.error(table_object, None, format!("Header does not point to a {}", table_type));
+            return;
+        }
+
+        let mut buckets = vec![0u8; table_size as usize];
+        if let Err(e) = self.file.seek(SeekFrom::Start(table)).and_then(|_| self.file.read_exact(&mut buckets)) {
+            self.report.error(table_object, Some(table_type), format!("Cannot read hash table: {}", e));
+            return;
+        }
+
+        let n_buckets = table_size / HASH_ITEM_SZ;
+        let mut seen = HashSet::new();
+        for (bucket, item) in buckets.chunks(HASH_ITEM_SZ as usize).enumerate() {
+            let head = LittleEndian::read_u64(&item[0..8]);
+            let tail = LittleEndian::read_u64(&item[8..16]);
+            let mut offset = head;
+            let mut last = 0;
+            while offset != 0 {
+                let hash = match type_ {
+                    ObjectType::ObjectData => self.data.get(&offset).map(|d| d.hash),
+                    _ => self.fields.get(&offset).cloned(),
+                };
+                let hash = match hash {
+                    Some(h) => h,
+                    None => {
+                        self.report.error(table_object, Some(table_type),
+                                          format!("Bucket {} links to {:#x}, which is not a {} object", bucket, offset, type_));
+                        break;
+                    }
+                };
+                if !seen.insert(offset) {
+                    self.report.error(offset, Some(type_), "Object is linked into the hash table more than once");
+                    break;
+                }
+                if hash % n_buckets != bucket as u64 {
+                    self.report.error(offset, Some(type_), format!("Object is in hash bucket {} instead of {}", bucket, hash % n_buckets));
+                }
+                last = offset;
+                // next_hash_offset has the same position in DATA and FIELD objects
+                offset = match self.read_u64_at(offset + OBJECT_HEADER_SZ + 8) {
+                    Ok(next) => next,
+                    Err(_) => break,
+                };
+            }
+            if last != tail {
+                self.report.error(table_object, Some(table_type),
+                                  format!("Tail of bucket {} is {:#x} but the chain ends at {:#x}", bucket, tail, last));
+            }
+        }
+
+        let all: Vec<u64> = match type_ {
+            ObjectType::ObjectData => self.data.keys().cloned().collect(),
+            _ => self.fields.keys().cloned().collect(),
+        };
+        let mut missing: Vec<u64> = all.into_iter().filter(|o| !seen.contains(o)).collect();
+        missing.sort_unstable();
+        for offset in missing {
+            self.report.error(offset, Some(type_), "Object is not linked into the hash table");
+        }
+    }
+
+    /// Checks that the main entry array lists every entry exactly once, in
+    /// order. Realtime timestamps going backwards only get a warning, as
+    /// clocks do get set back.
+    fn verify_main_entry_array(&mut self) {
+        let entries = self.walk_entry_arrays(self.header.entry_array_offset, self.header.n_entries);
+
+        let mut referenced = HashSet::new();
+        let mut last: Option<(u64, u64, u64)> = None;
+        for &offset in &entries {
+            let (seqnum, realtime) = match self.entries.get(&offset) {
+                Some(e) => (e.seqnum, e.realtime),
+                None => {
+                    self.report.error(offset, None, "Main entry array links to something that is not an entry");
+                    continue;
+                }
+            };
+            if !referenced.insert(offset) {
+                self.report.error(offset, Some(ObjectType::ObjectEntry), "Entry is listed in the main entry array more than once");
+                continue;
+            }
+            if let Some((last_offset, last_seqnum, last_realtime)) = last {
+                if offset <= last_offset || seqnum <= last_seqnum {
+                    self.report.error(offset, Some(ObjectType::ObjectEntry), format!("Entry out of order, seqnum {} follows {}", seqnum, last_seqnum));
+                } else if realtime < last_realtime {
+                    self.report.warning(offset, Some(ObjectType::ObjectEntry), "Realtime timestamp goes backwards");
+                }
+            }
+            last = Some((offset, seqnum, realtime));
+        }
+
+        let mut unreferenced: Vec<u64> = self.entries.keys().filter(|o| !referenced.contains(o)).cloned().collect();
+        unreferenced.sort_unstable();
+        for offset in unreferenced {
+            self.report.error(offset, Some(ObjectType::ObjectEntry), "Entry is not listed in the main entry array");
+        }
+
+        let header = self.header;
+        let first = entries.first().and_then(|o| self.entries.get(o)).map(|e| (e.seqnum, e.realtime));
+        let tail = entries.last().and_then(|o| self.entries.get(o)).map(|e| (e.seqnum, e.realtime));
+        if let (Some(first), Some(tail)) = (first, tail) {
+            if first != (header.head_entry_seqnum, header.head_entry_realtime) {
+                self.report.warning(0, None, "Head entry seqnum or realtime timestamp incorrect");
+            }
+            if tail != (header.tail_entry_seqnum, header.tail_entry_realtime) {
+                self.report.warning(0, None, "Tail entry seqnum or realtime timestamp incorrect");
+            }
+        }
+    }
+
+    /// Checks that the entries listed by every DATA object match its
+    /// `n_entries` and actually reference it, and that every entry is listed
+    /// by the DATA objects it references
+    fn verify_data_entries(&mut self) {
+        let mut links = HashSet::new();
+        let mut offsets: Vec<u64> = self.data.keys().cloned().collect();
+        offsets.sort_unstable();
+        for offset in offsets {
+            let (entry_offset, entry_array_offset, n_entries) = {
+                let d = &self.data[&offset];
+                (d.entry_offset, d.entry_array_offset, d.n_entries)
+            };
+            if n_entries == 0 {
+                continue;
+            }
+
+            let mut entries = vec![entry_offset];
+            entries.extend(self.walk_entry_arrays(entry_array_offset, n_entries - 1));
+            if entries.len() as u64 != n_entries {
+                self.report.error(offset, Some(ObjectType::ObjectData),
+                                  format!("Data object claims {} entries but links to {}", n_entries, entries.len()));
+            }
+            for entry in entries {
+                links.insert((offset, entry));
+                match self.entries.get(&entry) {
+                    Some(e) if e.items.contains(&offset) => {}
+                    Some(_) => self.report.error(offset, Some(ObjectType::ObjectData),
+                                                 format!("Linked entry at {:#x} does not reference the data object", entry)),
+                    None => self.report.error(offset, Some(ObjectType::ObjectData),
+                                              format!("Links to {:#x}, which is not an entry", entry)),
+                }
+            }
+        }
+
+        let mut entries: Vec<(&u64, &EntryInfo)> = self.entries.iter().collect();
+        entries.sort_unstable_by_key(|&(offset, _)| *offset);
+        let mut unlisted = Vec::new();
+        for (&offset, entry) in entries {
+            for &data in &entry.items {
+                if self.data.contains_key(&data) && !links.contains(&(data, offset)) {
+                    unlisted.push((offset, data));
+                }
+            }
+        }
+        for (offset, data) in unlisted {
+            self.report.error(offset, Some(ObjectType::ObjectEntry), format!("Entry is not listed by its data object at {:#x}", data));
+        }
+    }
+
+    /// Collects up to `n` non-zero items of the entry array chain at `head`
+    fn walk_entry_arrays(&mut self, head: u64, n: u64) -> Vec<u64> {
+        let mut items = Vec::new();
+        let mut offset = head;
+        let mut visited = HashSet::new();
+        while offset != 0 && (items.len() as u64) < n {
+            if self.objects.get(&offset) != Some(&ObjectType::ObjectEntryArray) || !visited.insert(offset) {
+                self.report.error(offset, None, "Entry array chain links to something that is not an entry array");
+                break;
+            }
+            let object = match read_object(self.file, offset, self.end) {
+                Ok(o) => o,
+                Err(_) => break,
+            };
+            for item in object[24..].chunks(8) {
+                let item = LittleEndian::read_u64(item);
+                if item != 0 && (items.len() as u64) < n {
+                    items.push(item);
+                }
+            }
+            offset = LittleEndian::read_u64(&object[16..24]);
+        }
+        items
+    }
+
+    fn read_u64_at(&mut self, offset: u64) -> Result<u64> {
+        let mut buf = [0u8; 8];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut buf)?;
+        Ok(LittleEndian::read_u64(&buf))
+    }
+}
+
+/// Reads the object at `offset` in full. Hash tables are large and checked
+/// separately, so only their header is read.
+fn read_object<R: Read + Seek>(file: &mut R, offset: u64, end: u64) -> Result<Vec<u8>> {
+    let mut object = vec![0u8; OBJECT_HEADER_SZ as usize];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut object)?;
+    let size = LittleEndian::read_u64(&object[8..16]);
+    let is_hash_table = object[0] == ObjectType::ObjectDataHashTable as u8 || object[0] == ObjectType::ObjectFieldHashTable as u8;
+    if size > OBJECT_HEADER_SZ && !is_hash_table && offset.saturating_add(size) <= end {
+        object.resize(size as usize, 0);
+        file.read_exact(&mut object[OBJECT_HEADER_SZ as usize..])?;
+    }
+    Ok(object)
+}
@@ -0,0 +1,762 @@
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::cmp;
+use std::fs::File;
+use std::io::{Cursor, Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+
+use hmac::Mac;
+
+use crate::hash::rhash64;
+use crate::journal::*;
+use crate::seal::*;
+
+pub const DEFAULT_DATA_HASH_TABLE_BUCKETS: u64 = 2047;
+pub const DEFAULT_FIELD_HASH_TABLE_BUCKETS: u64 = 333;
+
+/// Incompatible flags the writer knows how to preserve when appending
+const APPENDABLE_INCOMPATIBLE_FLAGS: u32 = HEADER_INCOMPATIBLE_COMPRESSED_XZ
+    | HEADER_INCOMPATIBLE_COMPRESSED_LZ4
+    | HEADER_INCOMPATIBLE_COMPRESSED_ZSTD
+    | HEADER_INCOMPATIBLE_KEYED_HASH;
+
+pub(crate) fn fill_random(buf: &mut [u8]) -> Result<()> {
+    File::open("/dev/urandom")?.read_exact(buf)
+}
+
+/// Generates a random 128-bit id, formatted as a v4 UUID like systemd's ids
+pub fn random_id() -> Result<u128> {
+    let mut bytes = [0u8; 16];
+    fill_random(&mut bytes)?;
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    Ok(u128::from_be_bytes(bytes))
+}
+
+/// Identity and sizing of a newly created journal file
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalParams {
+    pub machine_id: u128,
+    /// Boot id of the last writer, stored in the header
+    pub boot_id: u128,
+    /// Identifies the sequence the seqnums of the file belong to
+    pub seqnum_id: u128,
+    pub data_hash_table_buckets: u64,
+    pub field_hash_table_buckets: u64,
+    /// Hash payloads with SipHash keyed by the file id instead of Jenkins
+    pub keyed_hash: bool,
+    /// Size of the header: `HEADER_SZ`, or 256, 264 or `MAX_HEADER_SZ` for
+    /// the fields systemd 246, 252 and 254 added
+    pub header_size: u64,
+}
+
+impl JournalParams {
+    /// Parameters for a file starting a new seqnum sequence
+    pub fn new(machine_id: u128, boot_id: u128) -> Result<JournalParams> {
+        Ok(JournalParams {
+            machine_id,
+            boot_id,
+            seqnum_id: random_id()?,
+            data_hash_table_buckets: DEFAULT_DATA_HASH_TABLE_BUCKETS,
+            field_hash_table_buckets: DEFAULT_FIELD_HASH_TABLE_BUCKETS,
+            keyed_hash: false,
+            header_size: HEADER_SZ,
+        })
+    }
+}
+
+/// Appends entries to a journal file.
+///
+/// The file is marked `Online` for as long as the writer is open and set back
+/// to `Offline` by `close`. A writer that is dropped without being closed
+/// leaves the file `Online`, just like a crashed journald would.
+///
+/// Writers created with `create_sealed` seal the file with Forward Secure
+/// Sealing: a TAG object is appended whenever an entry falls into a later
+/// epoch than the previous one, and on close.
+pub struct JournalWriter<T>
+where
+    T: Read + Write + Seek,
+{
+    file: T,
+    pub header: JournalHeader,
+    next_seqnum: u64,
+    /// Where the next object will be placed
+    next_offset: u64,
+    sealing_key: Option<SealingKey>,
+    /// HMAC over the objects appended since the last tag
+    hmac: Option<HmacSha256>,
+    /// The last array of the main entry array chain and the index of its
+    /// first item, or `(0, 0)` while there is none
+    tail_entry_array: (ObjectOffset, u64),
+}
+
+impl<T> JournalWriter<T>
+where
+    T: Read + Write + Seek,
+{
+    /// Creates a new, empty journal in `file`, discarding anything it contains
+    pub fn create(mut file: T, params: &JournalParams) -> Result<JournalWriter<T>> {
+        if params.data_hash_table_buckets == 0 || params.field_hash_table_buckets == 0 {
+            return Err(Error::new(ErrorKind::InvalidInput, "Hash tables need at least one bucket"));
+        }
+
+        // The field hash table comes first, like in files written by journald
+        let field_hash_table_size = params.field_hash_table_buckets * HASH_ITEM_SZ;
+        let data_hash_table_size = params.data_hash_table_buckets * HASH_ITEM_SZ;
+        let field_hash_table_offset = params.header_size + OBJECT_HEADER_SZ;
+        let data_hash_table_object = align64(field_hash_table_offset + field_hash_table_size);
+        let data_hash_table_offset = data_hash_table_object + OBJECT_HEADER_SZ;
+        let end = data_hash_table_offset + data_hash_table_size;
+
+        let mut header = JournalHeader {
+            signature: *HEADER_SIGNATURE,
+            compatible_flags: 0,
+            incompatible_flags: if params.keyed_hash { HEADER_INCOMPATIBLE_KEYED_HASH } else { 0 },
+            state: JournalState::Online,
+            reserved: [0u8; 7],
+            file_id: random_id()?,
+            machine_id: params.machine_id,
+            boot_id: params.boot_id,
+            seqnum_id: params.seqnum_id,
+            header_size: params.header_size,
+            arena_size: end - params.header_size,
+            data_hash_table_offset,
+            data_hash_table_size,
+            field_hash_table_offset,
+            field_hash_table_size,
+            tail_object_offset: data_hash_table_object,
+            n_objects: 2,
+            n_entries: 0,
+            tail_entry_seqnum: 0,
+            head_entry_seqnum: 0,
+            entry_array_offset: 0,
+            head_entry_realtime: 0,
+            tail_entry_realtime: 0,
+            tail_entry_monotonic: 0,
+            n_data: 0,
+            n_fields: 0,
+            n_tags: 0,
+            n_entry_arrays: 0,
+            data_hash_chain_depth: None,
+            field_hash_chain_depth: None,
+            tail_entry_array_offset: None,
+            tail_entry_array_n_entries: None,
+            tail_entry_offset: None,
+        };
+        let has = |size: u64| params.header_size >= size;
+        if has(HEADER_SZ + 16) {
+            header.data_hash_chain_depth = Some(0);
+            header.field_hash_chain_depth = Some(0);
+        }
+        if has(HEADER_SZ + 24) {
+            header.tail_entry_array_offset = Some(0);
+            header.tail_entry_array_n_entries = Some(0);
+        }
+        if has(HEADER_SZ + 32) {
+            header.tail_entry_offset = Some(0);
+        }
+
+        let mut buf = Vec::with_capacity(end as usize);
+        header.write(&mut buf)?;
+        if buf.len() as u64 != params.header_size {
+            return Err(Error::new(ErrorKind::InvalidInput, "Unsupported header size"));
+        }
+        write_object_header(&mut buf, ObjectType::ObjectFieldHashTable, OBJECT_HEADER_SZ + field_hash_table_size)?;
+        buf.resize(data_hash_table_object as usize, 0);
+        write_object_header(&mut buf, ObjectType::ObjectDataHashTable, OBJECT_HEADER_SZ + data_hash_table_size)?;
+        buf.resize(end as usize, 0);
+
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&buf)?;
+
+        Ok(JournalWriter {
+            file,
+            header,
+            next_seqnum: 1,
+            next_offset: align64(end),
+            sealing_key: None,
+            hmac: None,
+            tail_entry_array: (0, 0),
+        })
+    }
+
+    /// Creates a new, empty journal sealed with `key`, which must belong to
+    /// the same machine
+    pub fn create_sealed(file: T, params: &JournalParams, key: SealingKey) -> Result<JournalWriter<T>> {
+        if key.machine_id != params.machine_id {
+            return Err(Error::new(ErrorKind::InvalidInput, "Sealing key belongs to a different machine"));
+        }
+        let mut writer = JournalWriter::create(file, params)?;
+        writer.header.compatible_flags |= HEADER_COMPATIBLE_SEALED;
+        writer.write_header()?;
+        writer.sealing_key = Some(key);
+        writer.append_first_tag()?;
+        Ok(writer)
+    }
+
+    /// Opens an existing `Offline` journal to append entries to it.
+    ///
+    /// DATA and FIELD objects already in the file are reused through the hash
+    /// tables, and new entries are chained onto the existing entry arrays.
+    /// Sealed journals need `open_sealed`.
+    ///
+    /// Intact files the writer cannot append to, because they are sealed,
+    /// archived or use features it does not know, are refused with
+    /// `ErrorKind::Unsupported`. Damaged files and files that are still
+    /// `Online` give `ErrorKind::InvalidData`.
+    pub fn open(file: T) -> Result<JournalWriter<T>> {
+        JournalWriter::open_with_key(file, None)
+    }
+
+    /// Opens an existing sealed `Offline` journal to append entries to it,
+    /// continuing the seal with `key`. The state of `key` must not be behind
+    /// the last tag in the file.
+    pub fn open_sealed(file: T, key: SealingKey) -> Result<JournalWriter<T>> {
+        JournalWriter::open_with_key(file, Some(key))
+    }
+
+    fn open_with_key(mut file: T, sealing_key: Option<SealingKey>) -> Result<JournalWriter<T>> {
+        file.seek(SeekFrom::Start(0))?;
+        let mut header = JournalHeader::new(Cursor::new(read_header_buf(&mut file)?))?;
+
+        if &header.signature != HEADER_SIGNATURE {
+            return Err(Error::new(ErrorKind::InvalidData, "Not a journal file"));
+        }
+        if header.incompatible_flags & !APPENDABLE_INCOMPATIBLE_FLAGS != 0 {
+            return Err(Error::new(ErrorKind::Unsupported, "Journal uses unsupported incompatible features"));
+        }
+        if header.header_size < HEADER_SZ {
+            return Err(Error::new(ErrorKind::InvalidData, "Journal header too small"));
+        }
+        // Fields the writer does not know would go stale
+        let mut buf = Vec::with_capacity(MAX_HEADER_SZ as usize);
+        header.write(&mut buf)?;
+        if buf.len() as u64 != header.header_size {
+            return Err(Error::new(ErrorKind::Unsupported, "Journal header has fields the writer cannot maintain"));
+        }
+        match header.state {
+            JournalState::Offline => {}
+            JournalState::Online => {
+                return Err(Error::new(ErrorKind::InvalidData, "Journal is online or was not closed cleanly"));
+            }
+            _ => {
+                return Err(Error::new(ErrorKind::Unsupported, "Journal is archived"));
+            }
+        }
+
+        let tail = header.tail_object_offset;
+        file.seek(SeekFrom::Start(tail))?;
+        let tail_type = file.read_u8()?;
+        file.seek(SeekFrom::Start(tail + 8))?;
+        let tail_size = file.read_u64::<LittleEndian>()?;
+        let next_offset = align64(tail + tail_size);
+
+        let sealed = header.compatible_flags & HEADER_COMPATIBLE_SEALED != 0;
+        match sealing_key {
+            None if sealed => {
+                return Err(Error::new(ErrorKind::Unsupported, "Journal is sealed"));
+            }
+            None => {}
+            Some(_) if !sealed => {
+                return Err(Error::new(ErrorKind::InvalidData, "Journal is not sealed"));
+            }
+            Some(ref key) => {
+                if key.machine_id != header.machine_id {
+                    return Err(Error::new(ErrorKind::InvalidInput, "Sealing key belongs to a different machine"));
+                }
+                // The next tag covers everything since the previous one, which
+                // is only known if the file ends with a tag
+                if tail_type != ObjectType::ObjectTag as u8 {
+                    return Err(Error::new(ErrorKind::InvalidData, "Sealed journal does not end with a tag"));
+                }
+                file.seek(SeekFrom::Start(tail + OBJECT_HEADER_SZ + 8))?;
+                if file.read_u64::<LittleEndian>()? > key.state.epoch() {
+                    return Err(Error::new(ErrorKind::InvalidInput, "Sealing key is older than the journal"));
+                }
+            }
+        }
+
+        header.state = JournalState::Online;
+        let mut writer = JournalWriter {
+            file,
+            next_seqnum: header.tail_entry_seqnum + 1,
+            header,
+            next_offset,
+            sealing_key,
+            hmac: None,
+            tail_entry_array: (0, 0),
+        };
+        writer.tail_entry_array = writer.find_tail_entry_array()?;
+        if writer.header.n_entries > 0 {
+            let (array, first) = writer.tail_entry_array;
+            let last = writer.read_u64_at(entry_array_item(array, writer.header.n_entries - 1 - first))?;
+            writer.update_tail_fields(last)?;
+        }
+        writer.write_header()?;
+        Ok(writer)
+    }
+
+    /// The seqnum the next appended entry will get
+    pub fn next_seqnum(&self) -> u64 {
+        self.next_seqnum
+    }
+
+    /// Sets the seqnum of the next appended entry. Seqnums must keep increasing
+    /// within a file.
+    pub fn set_next_seqnum(&mut self, seqnum: u64) -> Result<()> {
+        if seqnum == 0 || (self.header.n_entries > 0 && seqnum <= self.header.tail_entry_seqnum) {
+            return Err(Error::new(ErrorKind::InvalidInput, "Seqnum must be larger than the tail seqnum"));
+        }
+        self.next_seqnum = seqnum;
+        Ok(())
+    }
+
+    /// Appends an entry made of `FIELD=value` items and returns its offset
+    pub fn append_entry(
+        &mut self,
+        realtime: u64,
+        monotonic: u64,
+        boot_id: u128,
+        items: &[&[u8]],
+    ) -> Result<ObjectOffset> {
+        if items.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "Entry has no items"));
+        }
+        for item in items {
+            match item.iter().position(|&c| c == b'=') {
+                Some(eq) if eq > 0 => {}
+                _ => return Err(Error::new(ErrorKind::InvalidInput, "Entry item is not FIELD=value")),
+            }
+        }
+        self.maybe_append_tag(realtime)?;
+
+        let mut entry_items = Vec::with_capacity(items.len());
+        let keyed = self.header.incompatible_flags & HEADER_INCOMPATIBLE_KEYED_HASH != 0;
+        for item in items {
+            let (offset, hash) = self.append_data(item)?;
+            // Keyed files still XOR the Jenkins hashes of the payloads into the entry
+            let xor = if keyed { rhash64(item) } else { hash };
+            entry_items.push((offset, hash, xor));
+        }
+        entry_items.sort_unstable();
+        entry_items.dedup();
+
+        let seqnum = self.next_seqnum;
+        let xor_hash = entry_items.iter().fold(0, |acc, &(_, _, xor)| acc ^ xor);
+        let size = OBJECT_HEADER_SZ + ENTRY_OBJECT_HEADER_SZ + ENTRY_ITEM_SZ * entry_items.len() as u64;
+        let mut buf = Vec::with_capacity(size as usize);
+        write_object_header(&mut buf, ObjectType::ObjectEntry, size)?;
+        buf.write_u64::<LittleEndian>(seqnum)?;
+        buf.write_u64::<LittleEndian>(realtime)?;
+        buf.write_u64::<LittleEndian>(monotonic)?;
+        buf.write_u128::<BigEndian>(boot_id)?;
+        buf.write_u64::<LittleEndian>(xor_hash)?;
+        for &(offset, hash, _) in &entry_items {
+            buf.write_u64::<LittleEndian>(offset)?;
+            buf.write_u64::<LittleEndian>(hash)?;
+        }
+        let offset = self.append_object(&buf)?;
+
+        // Link into the main entry array chain, starting at its tail
+        let head = self.header.entry_array_offset;
+        let (head, tail) = self.link_into_entry_arrays(head, self.header.n_entries, offset, self.tail_entry_array)?;
+        self.header.entry_array_offset = head;
+        self.tail_entry_array = tail;
+        self.header.n_entries += 1;
+        self.update_tail_fields(offset)?;
+        if self.header.head_entry_seqnum == 0 {
+            self.header.head_entry_seqnum = seqnum;
+        }
+        if self.header.head_entry_realtime == 0 {
+            self.header.head_entry_realtime = realtime;
+        }
+        self.header.tail_entry_seqnum = seqnum;
+        self.header.tail_entry_realtime = realtime;
+        self.header.tail_entry_monotonic = monotonic;
+        self.next_seqnum = seqnum + 1;
+
+        // Link into the entry arrays of each data object
+        for &(data_offset, _, _) in &entry_items {
+            self.link_entry_into_data(data_offset, offset)?;
+        }
+
+        self.write_header()?;
+        Ok(offset)
+    }
+
+    /// Size of the file up to the end of the last object
+    pub fn file_size(&self) -> u64 {
+        self.next_offset
+    }
+
+    /// The sealing key in its current state. As the state only moves
+    /// forward, it should be saved back to where it was loaded from.
+    pub fn sealing_key(&self) -> Option<&SealingKey> {
+        self.sealing_key.as_ref()
+    }
+
+    /// Marks the file `Offline` and flushes the header
+    pub fn close(self) -> Result<T> {
+        self.finish(JournalState::Offline)
+    }
+
+    /// Marks the file `Archived` so nothing will be appended to it anymore
+    pub fn archive(self) -> Result<T> {
+        self.finish(JournalState::Archived)
+    }
+
+    fn finish(mut self, state: JournalState) -> Result<T> {
+        // Seal whatever was appended since the last tag
+        if self.hmac.is_some() {
+            self.append_tag()?;
+        }
+        self.header.state = state;
+        self.write_header()?;
+        self.file.flush()?;
+        Ok(self.file)
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        let mut buf = Vec::with_capacity(HEADER_SZ as usize);
+        self.header.write(&mut buf)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&buf)
+    }
+
+    fn read_u64_at(&mut self, offset: u64) -> Result<u64> {
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_u64::<LittleEndian>()
+    }
+
+    fn write_u64_at(&mut self, offset: u64, value: u64) -> Result<()> {
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_u64::<LittleEndian>(value)
+    }
+
+    /// Writes a serialized object at the end of the arena, adding it to the
+    /// HMAC of the next tag if the file is sealed
+    fn append_object(&mut self, object: &[u8]) -> Result<ObjectOffset> {
+        if let Some(hmac) = self.running_hmac() {
+            hmac_put_object(hmac, object);
+        }
+        self.write_object(object)
+    }
+
+    fn write_object(&mut self, object: &[u8]) -> Result<ObjectOffset> {
+        let offset = self.next_offset;
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(object)?;
+
+        let end = offset + object.len() as u64;
+        self.next_offset = align64(end);
+        self.header.tail_object_offset = offset;
+        self.header.n_objects += 1;
+        self.header.arena_size = cmp::max(self.header.arena_size, end - self.header.header_size);
+        Ok(offset)
+    }
+
+    /// The running HMAC, started with the key of the current epoch if this
+    /// is the first object since the last tag. `None` if the file is not sealed.
+    fn running_hmac(&mut self) -> Option<&mut HmacSha256> {
+        let key = self.sealing_key.as_ref()?;
+        Some(self.hmac.get_or_insert_with(|| hmac_start(&key.state)))
+    }
+
+    /// Seals the header and the hash tables, which must be the only objects
+    /// in the file
+    fn append_first_tag(&mut self) -> Result<()> {
+        let mut header = Vec::with_capacity(HEADER_SZ as usize);
+        self.header.write(&mut header)?;
+        let field_hash_table = self.header.field_hash_table_offset - OBJECT_HEADER_SZ;
+        let data_hash_table = self.header.data_hash_table_offset - OBJECT_HEADER_SZ;
+        let mut objects = Vec::new();
+        for &offset in &[field_hash_table, data_hash_table] {
+            let mut object = vec![0u8; OBJECT_HEADER_SZ as usize];
+            self.file.seek(SeekFrom::Start(offset))?;
+            self.file.read_exact(&mut object)?;
+            objects.push(object);
+        }
+
+        if let Some(hmac) = self.running_hmac() {
+            hmac_put_header(hmac, &header);
+            for object in &objects {
+                hmac_put_object(hmac, object);
+            }
+        }
+        self.append_tag()
+    }
+
+    /// Appends a TAG object holding the HMAC of everything since the last tag
+    fn append_tag(&mut self) -> Result<()> {
+        let epoch = match self.sealing_key {
+            Some(ref key) => key.state.epoch(),
+            None => return Ok(()),
+        };
+        let mut tag = Vec::with_capacity(TAG_OBJECT_SZ as usize);
+        write_object_header(&mut tag, ObjectType::ObjectTag, TAG_OBJECT_SZ)?;
+        tag.write_u64::<LittleEndian>(self.header.n_tags + 1)?;
+        tag.write_u64::<LittleEndian>(epoch)?;
+        tag.resize(TAG_OBJECT_SZ as usize, 0);
+
+        let mut hmac = match self.running_hmac() {
+            Some(hmac) => hmac.clone(),
+            None => return Ok(()),
+        };
+        hmac_put_object(&mut hmac, &tag);
+        tag[32..].copy_from_slice(&hmac.finalize().into_bytes());
+        self.hmac = None;
+
+        self.write_object(&tag)?;
+        self.header.n_tags += 1;
+        Ok(())
+    }
+
+    /// Appends tags for the epochs between the current one and the one of
+    /// `realtime`, evolving the sealing key on the way. Realtimes before the
+    /// start of the key are left to the current epoch, like journald does.
+    fn maybe_append_tag(&mut self, realtime: u64) -> Result<()> {
+        let goal = match self.sealing_key {
+            Some(ref key) => match key.epoch(realtime) {
+                Some(goal) if goal > key.state.epoch() => goal,
+                _ => return Ok(()),
+            },
+            None => return Ok(()),
+        };
+
+        self.append_tag()?;
+        loop {
+            let key = self.sealing_key.as_mut().expect("sealed writer has a key");
+            key.state.evolve();
+            if key.state.epoch() >= goal {
+                return Ok(());
+            }
+            self.append_tag()?;
+        }
+    }
+
+    /// Appends `object` to the hash chain of its bucket. `next_hash_offset` of
+    /// DATA and FIELD objects share the same position.
+    fn link_into_hash_table(&mut self, table: u64, table_size: u64, hash: u64, object: ObjectOffset) -> Result<()> {
+        let bucket = table + (hash % (table_size / HASH_ITEM_SZ)) * HASH_ITEM_SZ;
+        let tail = self.read_u64_at(bucket + 8)?;
+        if tail == 0 {
+            self.write_u64_at(bucket, object)?;
+        } else {
+            self.write_u64_at(tail + OBJECT_HEADER_SZ + 8, object)?;
+        }
+        self.write_u64_at(bucket + 8, object)
+    }
+
+    /// Walks the hash chain of a bucket and returns the first object for
+    /// which `matches` is true, and the number of objects before it or in
+    /// the whole chain if there is none
+    fn find_in_hash_table<F>(
+        &mut self,
+        table: u64,
+        table_size: u64,
+        hash: u64,
+        matches: F,
+    ) -> Result<(Option<ObjectOffset>, u64)>
+    where
+        F: Fn(&Object) -> bool,
+    {
+        let bucket = table + (hash % (table_size / HASH_ITEM_SZ)) * HASH_ITEM_SZ;
+        let mut offset = self.read_u64_at(bucket)?;
+        let mut depth = 0;
+        while offset != 0 {
+            let object = get_obj_at_offset(&mut self.file, offset)?;
+            if matches(&object) {
+                return Ok((Some(offset), depth));
+            }
+            depth += 1;
+            offset = match object {
+                Object::Data(d) => d.next_hash_offset,
+                Object::Field(f) => f.next_hash_offset,
+                _ => return Err(Error::new(ErrorKind::InvalidData, "Unexpected object in hash chain")),
+            };
+        }
+        Ok((None, depth))
+    }
+
+    /// Finds or creates the DATA object for `payload`, returning its offset and hash
+    fn append_data(&mut self, payload: &[u8]) -> Result<(ObjectOffset, u64)> {
+        let hash = self.header.hash_payload(payload);
+        let table = self.header.data_hash_table_offset;
+        let table_size = self.header.data_hash_table_size;
+        let (existing, depth) = self.find_in_hash_table(table, table_size, hash, |o| match o {
+            Object::Data(d) => d.hash == hash && !d.object.is_compressed() && d.payload == payload,
+            _ => false,
+        })?;
+        if let Some(offset) = existing {
+            return Ok((offset, hash));
+        }
+
+        let size = OBJECT_HEADER_SZ + DATA_OBJECT_HEADER_SZ + payload.len() as u64;
+        let mut buf = Vec::with_capacity(size as usize);
+        write_object_header(&mut buf, ObjectType::ObjectData, size)?;
+        buf.write_u64::<LittleEndian>(hash)?;
+        // next_hash_offset, next_field_offset, entry_offset, entry_array_offset, n_entries
+        buf.resize(buf.len() + 40, 0);
+        buf.write_all(payload)?;
+        let offset = self.append_object(&buf)?;
+        self.link_into_hash_table(table, table_size, hash, offset)?;
+        self.header.n_data += 1;
+        if let Some(ref mut deepest) = self.header.data_hash_chain_depth {
+            *deepest = cmp::max(*deepest, depth + 1);
+        }
+
+        // Prepend the data object to the list of values of its field
+        let eq = payload.iter().position(|&c| c == b'=').unwrap_or(payload.len());
+        let field = self.append_field(&payload[..eq])?;
+        let head = self.read_u64_at(field + OBJECT_HEADER_SZ + 16)?;
+        self.write_u64_at(offset + OBJECT_HEADER_SZ + 16, head)?;
+        self.write_u64_at(field + OBJECT_HEADER_SZ + 16, offset)?;
+
+        Ok((offset, hash))
+    }
+
+    /// Finds or creates the FIELD object for `name`
+    fn append_field(&mut self, name: &[u8]) -> Result<ObjectOffset> {
+        let hash = self.header.hash_payload(name);
+        let table = self.header.field_hash_table_offset;
+        let table_size = self.header.field_hash_table_size;
+        let (existing, depth) = self.find_in_hash_table(table, table_size, hash, |o| match o {
+            Object::Field(f) => f.hash == hash && f.payload == name,
+            _ => false,
+        })?;
+        if let Some(offset) = existing {
+            return Ok(offset);
+        }
+
+        let size = OBJECT_HEADER_SZ + FIELD_OBJECT_HEADER_SZ + name.len() as u64;
+        let mut buf = Vec::with_capacity(size as usize);
+        write_object_header(&mut buf, ObjectType::ObjectField, size)?;
+        buf.write_u64::<LittleEndian>(hash)?;
+        // next_hash_offset, head_data_offset
+        buf.resize(buf.len() + 16, 0);
+        buf.write_all(name)?;
+        let offset = self.append_object(&buf)?;
+        self.link_into_hash_table(table, table_size, hash, offset)?;
+        self.header.n_fields += 1;
+        if let Some(ref mut deepest) = self.header.field_hash_chain_depth {
+            *deepest = cmp::max(*deepest, depth + 1);
+        }
+        Ok(offset)
+    }
+
+    fn append_entry_array(&mut self, capacity: u64) -> Result<ObjectOffset> {
+        // The header keeps only 32 bits of the offset of the tail array
+        if self.header.tail_entry_array_offset.is_some() && self.next_offset > u64::from(u32::MAX) {
+            return Err(Error::new(ErrorKind::InvalidInput, "Journal is too large for the offsets in its header"));
+        }
+        let size = OBJECT_HEADER_SZ + ENTRY_ARRAY_OBJECT_HEADER_SZ + capacity * 8;
+        let mut buf = Vec::with_capacity(size as usize);
+        write_object_header(&mut buf, ObjectType::ObjectEntryArray, size)?;
+        buf.resize(size as usize, 0);
+        let offset = self.append_object(&buf)?;
+        self.header.n_entry_arrays += 1;
+        Ok(offset)
+    }
+
+    fn entry_array_capacity(&mut self, array: ObjectOffset) -> Result<u64> {
+        let size = self.read_u64_at(array + 8)?;
+        Ok(size.saturating_sub(OBJECT_HEADER_SZ + ENTRY_ARRAY_OBJECT_HEADER_SZ) / 8)
+    }
+
+    /// Stores `entry` at position `index` of the entry array chain starting
+    /// at `head`, growing the chain when every array is full. The walk starts
+    /// at `tail`, an array of the chain and the index of its first item, or
+    /// at the head for `(0, 0)`. Returns the (possibly new) head of the chain
+    /// and its last array in the form of `tail`.
+    fn link_into_entry_arrays(
+        &mut self,
+        head: ObjectOffset,
+        index: u64,
+        entry: ObjectOffset,
+        tail: (ObjectOffset, u64),
+    ) -> Result<(ObjectOffset, (ObjectOffset, u64))> {
+        let (mut offset, mut first) = if tail.0 == 0 { (head, 0) } else { tail };
+        let mut last = 0;
+        while offset != 0 {
+            let capacity = self.entry_array_capacity(offset)?;
+            if index < first + capacity {
+                self.write_u64_at(entry_array_item(offset, index - first), entry)?;
+                return Ok((head, (offset, first)));
+            }
+            first += capacity;
+            last = offset;
+            offset = self.read_u64_at(offset + OBJECT_HEADER_SZ)?;
+        }
+
+        // Every array is full, so the new array starts with our entry
+        let array = self.append_entry_array(cmp::max(4, first * 2))?;
+        self.write_u64_at(entry_array_item(array, 0), entry)?;
+        if last == 0 {
+            Ok((array, (array, first)))
+        } else {
+            self.write_u64_at(last + OBJECT_HEADER_SZ, array)?;
+            Ok((head, (array, first)))
+        }
+    }
+
+    /// Walks the main entry array chain to its last array, in the form of
+    /// `tail_entry_array`
+    fn find_tail_entry_array(&mut self) -> Result<(ObjectOffset, u64)> {
+        let mut tail = (0, 0);
+        let mut offset = self.header.entry_array_offset;
+        let mut first = 0;
+        for _ in 0..self.header.n_entry_arrays {
+            if offset == 0 {
+                return Ok(tail);
+            }
+            tail = (offset, first);
+            first += self.entry_array_capacity(offset)?;
+            offset = self.read_u64_at(offset + OBJECT_HEADER_SZ)?;
+        }
+        if offset != 0 {
+            return Err(Error::new(ErrorKind::InvalidData, "Entry array chain is longer than the header says"));
+        }
+        Ok(tail)
+    }
+
+    /// Records `entry` as the last one, in the header fields newer systemd
+    /// versions keep to find the end of the file quickly
+    fn update_tail_fields(&mut self, entry: ObjectOffset) -> Result<()> {
+        let (array, first) = self.tail_entry_array;
+        if self.header.tail_entry_array_offset.is_some() {
+            // append_entry_array keeps offsets of arrays in these files in range
+            self.header.tail_entry_array_offset = Some(array as u32);
+            self.header.tail_entry_array_n_entries = Some((self.header.n_entries - first) as u32);
+        }
+        if self.header.tail_entry_offset.is_some() {
+            self.header.tail_entry_offset = Some(entry);
+        }
+        Ok(())
+    }
+
+    /// Records that `entry` references the DATA object at `data`
+    fn link_entry_into_data(&mut self, data: ObjectOffset, entry: ObjectOffset) -> Result<()> {
+        let n_entries_pos = data + OBJECT_HEADER_SZ + 40;
+        let n_entries = self.read_u64_at(n_entries_pos)?;
+        if n_entries == 0 {
+            self.write_u64_at(data + OBJECT_HEADER_SZ + 24, entry)?;
+        } else {
+            let array_pos = data + OBJECT_HEADER_SZ + 32;
+            let head = self.read_u64_at(array_pos)?;
+            let (head, _) = self.link_into_entry_arrays(head, n_entries - 1, entry, (0, 0))?;
+            self.write_u64_at(array_pos, head)?;
+        }
+        self.write_u64_at(n_entries_pos, n_entries + 1)
+    }
+}
+
+/// Position of item `index` of the entry array at `array`
+fn entry_array_item(array: ObjectOffset, index: u64) -> u64 {
+    array + OBJECT_HEADER_SZ + ENTRY_ARRAY_OBJECT_HEADER_SZ + index * 8
+}
+
+fn write_object_header<W: Write>(mut buf: W, type_: ObjectType, size: u64) -> Result<()> {
+    buf.write_u8(type_ as u8)?;
+    buf.write_u8(0)?;
+    buf.write_all(&[0u8; 6])?;
+    buf.write_u64::<LittleEndian>(size)
+}
@@ -0,0 +1,125 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::*;
+    use sdjournal::boot::{Boot, BootDescriptor};
+    use sdjournal::filter::Filter;
+    use sdjournal::journal::*;
+    use sdjournal::multi::MultiJournal;
+    use std::fs::{self, File};
+    use std::path::Path;
+    use pretty_assertions::assert_eq;
+
+    /// Writes `(realtime, monotonic, boot_id)` entries with their `_BOOT_ID`
+    /// field, and the realtime as message
+    fn write_journal(path: &Path, entries: &[(u64, u64, u128)]) {
+        let builder = entries.iter().fold(JournalBuilder::new(path), |builder, &(realtime, monotonic, boot_id)| {
+            let boot = format!("_BOOT_ID={:032x}", boot_id);
+            let message = format!("MESSAGE={}", realtime);
+            builder.entry_at(realtime, monotonic, boot_id, &[boot.as_bytes(), message.as_bytes()])
+        });
+        builder.close();
+    }
+
+    fn boot(index: i64, boot_id: u128, first_realtime: u64, last_realtime: u64) -> Boot {
+        Boot { index, boot_id, first_realtime, last_realtime }
+    }
+
+    #[test]
+    fn test_boots() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path, &[(100, 1, 0xa), (110, 2, 0xa), (200, 1, 0xb), (300, 1, 0xc), (320, 5, 0xc), (330, 6, 0xc)]);
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(journal.boots().unwrap(), vec![boot(-2, 0xa, 100, 110), boot(-1, 0xb, 200, 200), boot(0, 0xc, 300, 330)]);
+        assert_eq!(journal.unique_values("_BOOT_ID").unwrap().len(), 3);
+        assert!(journal.unique_values("NOPE").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_no_boot_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        JournalBuilder::new(&path)
+            .entry_at(100, 1, 0xa, &[b"MESSAGE=x"])
+            .entry_at(110, 2, 0xa, &[b"MESSAGE=x"])
+            .entry_at(105, 1, 0xb, &[b"MESSAGE=x"])
+            .entry_at(200, 3, 0xb, &[b"MESSAGE=x"])
+            .close();
+
+        // The boot ids of the entry headers stand in for _BOOT_ID
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(journal.boots().unwrap(), vec![boot(-1, 0xa, 100, 110), boot(0, 0xb, 105, 200)]);
+    }
+
+    #[test]
+    fn test_multi_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        // Each file has its own seqnum id, so entries of the same boot are
+        // interleaved by monotonic time even when the clock jumped back
+        write_journal(&dir.path().join("system.journal"), &[(100, 1, 0xa), (103, 4, 0xa), (300, 1, 0xc)]);
+        write_journal(&dir.path().join("user-1000.journal"), &[(102, 2, 0xa), (101, 3, 0xa), (200, 1, 0xb), (301, 2, 0xc)]);
+        fs::write(dir.path().join("notes.txt"), "not a journal").unwrap();
+
+        let journals = MultiJournal::open_directory(dir.path()).unwrap();
+        assert_eq!(journals.journals.len(), 2);
+        let realtimes: Vec<u64> = journals.entries().unwrap().map(|e| e.unwrap().realtime).collect();
+        assert_eq!(realtimes, vec![100, 102, 101, 103, 200, 300, 301]);
+        let newest: Vec<u64> = journals.entries_matching_rev(&Filter::new()).unwrap().map(|e| e.unwrap().realtime).collect();
+        assert_eq!(newest, vec![301, 300, 200, 103, 101, 102, 100]);
+        let mut filter = Filter::new();
+        filter.add_boot(0xa);
+        let newest: Vec<u64> = journals.entries_matching_rev(&filter).unwrap().take(2).map(|e| e.unwrap().realtime).collect();
+        assert_eq!(newest, vec![103, 101]);
+        assert_eq!(journals.boots().unwrap(), vec![boot(-2, 0xa, 100, 103), boot(-1, 0xb, 200, 200), boot(0, 0xc, 300, 301)]);
+
+        // Within a file, entries are ordered by seqnum, so the first entry of
+        // a boot can have a later realtime than its last, as journalctl shows
+        let only = MultiJournal::open(&[dir.path().join("user-1000.journal")]).unwrap();
+        assert_eq!(only.boots().unwrap(), vec![boot(-2, 0xa, 102, 101), boot(-1, 0xb, 200, 200), boot(0, 0xc, 301, 301)]);
+    }
+
+    #[test]
+    fn test_boot_descriptor() {
+        let id = "0000000000000000000000000000000b";
+        let parse = |s: &str| s.parse::<BootDescriptor>().unwrap();
+        assert_eq!(parse(""), BootDescriptor { boot_id: None, offset: 0 });
+        assert_eq!(parse("-1"), BootDescriptor { boot_id: None, offset: -1 });
+        assert_eq!(parse("3"), BootDescriptor { boot_id: None, offset: 3 });
+        assert_eq!(parse(id), BootDescriptor { boot_id: Some(0xb), offset: 0 });
+        assert_eq!(parse(&format!("{}+2", id)), BootDescriptor { boot_id: Some(0xb), offset: 2 });
+        assert_eq!(parse(&format!("{}-1", id)), BootDescriptor { boot_id: Some(0xb), offset: -1 });
+        assert!(format!("{}1", id).parse::<BootDescriptor>().is_err());
+        assert!("last".parse::<BootDescriptor>().is_err());
+        assert_eq!(parse(&format!("{}-1", id)).to_string(), format!("{}-1", id));
+        assert_eq!(parse("3").to_string(), "+3");
+
+        let boots = [boot(-2, 0xa, 100, 110), boot(-1, 0xb, 200, 200), boot(0, 0xc, 300, 330)];
+        let resolve = |s: &str| parse(s).resolve(&boots).map(|b| b.boot_id);
+        assert_eq!(resolve(""), Some(0xc));
+        assert_eq!(resolve("-2"), Some(0xa));
+        assert_eq!(resolve("-3"), None);
+        assert_eq!(resolve("1"), Some(0xa));
+        assert_eq!(resolve("3"), Some(0xc));
+        assert_eq!(resolve("4"), None);
+        assert_eq!(resolve(id), Some(0xb));
+        assert_eq!(resolve(&format!("{}+1", id)), Some(0xc));
+        assert_eq!(resolve(&format!("{}-2", id)), None);
+        assert_eq!(resolve("0000000000000000000000000000000d"), None);
+    }
+
+    #[test]
+    fn test_boot_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path, &[(100, 1, 0xa), (110, 2, 0xa), (200, 1, 0xb), (300, 1, 0xc), (320, 5, 0xc)]);
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let mut filter = Filter::new();
+        filter.add_boot(0xc);
+        let realtimes: Vec<u64> = journal.entries_matching(&filter).unwrap().map(|e| e.unwrap().realtime).collect();
+        assert_eq!(realtimes, vec![300, 320]);
+    }
+}
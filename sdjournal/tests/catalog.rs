@@ -0,0 +1,149 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::*;
+    use sdjournal::catalog::*;
+    use sdjournal::entry::Entry;
+    use sdjournal::journal::*;
+    use std::fs::{self, File};
+    use std::io::ErrorKind;
+    use std::os::unix::fs::symlink;
+    use std::path::Path;
+    use pretty_assertions::assert_eq;
+
+    const ID: u128 = 0x0123456789abcdef0123456789abcdef;
+    const OTHER_ID: u128 = 0xfedcba9876543210fedcba9876543210;
+
+    const SOURCE: &str = "\
+# A comment
+-- 0123456789abcdef0123456789abcdef
+Subject: Unit @UNIT@ started
+Defined-By: test
+
+The unit @UNIT@ has begun with @RESULT@.
+# Comments are left out of texts
+
+Not replaced: @lower@ @A1@ @@.
+
+-- 0123456789abcdef0123456789abcdef de
+Subject: Unit @UNIT@ gestartet
+
+Die Unit @UNIT@.
+
+-- fedcba9876543210fedcba9876543210 fr
+Subject: Autre
+   indented line
+";
+
+    /// A catalog of `SOURCE` looking up texts in `language`
+    fn catalog(language: Option<&str>) -> Catalog {
+        let mut catalog = Catalog::new();
+        catalog.import(SOURCE, None, "test.catalog").unwrap();
+        catalog.language = language.map(str::to_owned);
+        catalog
+    }
+
+    #[test]
+    fn test_import() {
+        let catalog = catalog(None);
+        assert_eq!(catalog.ids().collect::<Vec<_>>(), vec![ID, OTHER_ID]);
+        assert_eq!(catalog.get(ID).unwrap(), "Subject: Unit @UNIT@ started\nDefined-By: test\n\n\
+                                              The unit @UNIT@ has begun with @RESULT@.\n\n\
+                                              Not replaced: @lower@ @A1@ @@.\n");
+        assert_eq!(header(catalog.get(ID).unwrap(), "Subject"), Some("Unit @UNIT@ started"));
+        assert_eq!(header(catalog.get(ID).unwrap(), "Support"), None);
+        // Ids without a text in the default language fall back to another one
+        assert_eq!(catalog.get(OTHER_ID).unwrap(), "Subject: Autre\n   indented line\n");
+        assert_eq!(catalog.get(1), None);
+
+        let german = "Subject: Unit @UNIT@ gestartet\n\nDie Unit @UNIT@.\n";
+        assert_eq!(self::catalog(Some("de")).get(ID).unwrap(), german);
+        assert_eq!(self::catalog(Some("de_AT")).get(ID).unwrap(), german);
+        assert!(self::catalog(Some("fr")).get(ID).unwrap().starts_with("Subject: Unit @UNIT@ started"));
+    }
+
+    #[test]
+    fn test_import_merges() {
+        let mut catalog = catalog(None);
+        // New headers go first, the body is only replaced by a non-empty one
+        catalog.import("-- 0123456789abcdef0123456789abcdef\nX-Extra: merged\n", None, "more.catalog").unwrap();
+        assert!(catalog.get(ID).unwrap().starts_with("X-Extra: merged\nSubject: Unit @UNIT@ started\n\
+                                                      Defined-By: test\n\nThe unit"));
+        catalog.import("-- 0123456789abcdef0123456789abcdef\nSubject: New\n\nNew body\n", None, "more.catalog")
+            .unwrap();
+        assert_eq!(catalog.get(ID).unwrap(), "Subject: New\nX-Extra: merged\nSubject: Unit @UNIT@ started\n\
+                                              Defined-By: test\n\nNew body\n");
+        assert_eq!(header(catalog.get(ID).unwrap(), "Subject"), Some("New"));
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let error = |source: &str| Catalog::new().import(source, None, "bad.catalog").unwrap_err();
+        assert_eq!(error("Text\n").to_string(), "[bad.catalog:1] Got payload before ID.");
+        assert_eq!(error("-- 0123456789abcdef0123456789abcdef\n\n-- fedcba9876543210fedcba9876543210\nText\n")
+                       .to_string(), "[bad.catalog:3] No payload text.");
+        assert_eq!(error("-- 0123456789abcdef0123456789abcdef x\nText\n").to_string(),
+                   "[bad.catalog:1] Language too short.");
+        assert_eq!(error("-- 0123456789abcdef0123456789abcdef\n").kind(), ErrorKind::InvalidData);
+        // Headers need a blank line before them and a valid id, else they are text
+        let mut catalog = Catalog::new();
+        catalog.import("-- 0123456789abcdef0123456789abcdef\nText\n-- fedcba9876543210fedcba9876543210\n\
+                        \n-- not an id at all, but long enough\n", None, "ok.catalog").unwrap();
+        assert_eq!(catalog.get(ID).unwrap(), "Text\n-- fedcba9876543210fedcba9876543210\n\n\
+                                              -- not an id at all, but long enough\n");
+    }
+
+    #[test]
+    fn test_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let (local, system) = (dir.path().join("local"), dir.path().join("system"));
+        fs::create_dir(&local).unwrap();
+        fs::create_dir(&system).unwrap();
+        fs::write(system.join("a.catalog"), SOURCE).unwrap();
+        fs::write(system.join("b.de.catalog"), "-- 00000000000000000000000000000001\nGerman\n").unwrap();
+        fs::write(system.join("c.catalog"), "-- 00000000000000000000000000000002\nHidden\n").unwrap();
+        fs::write(system.join("d.txt"), "Not a catalog\n").unwrap();
+        fs::write(local.join("c.catalog"), "-- 00000000000000000000000000000003\nOverride\n").unwrap();
+        symlink("/dev/null", local.join("a.catalog")).unwrap();
+
+        let mut catalog = Catalog::open(&[&local, &system, &dir.path().join("missing")]).unwrap();
+        assert_eq!(catalog.ids().collect::<Vec<_>>(), vec![1, 3]);
+        // The language of texts defaults to the one in the file name
+        catalog.language = Some("en".to_owned());
+        assert_eq!(catalog.get(1), Some("German\n"));
+        catalog.import_file(&system.join("b.de.catalog")).unwrap();
+        assert_eq!(catalog.ids().count(), 2);
+        assert!(Catalog::open(&[&system]).unwrap().get(ID).is_some());
+    }
+
+    /// Writes an entry per item list and reads them back
+    fn entries(path: &Path, entries: &[&[&[u8]]]) -> Vec<Entry> {
+        JournalBuilder::new(path).time(0, 0).entries(entries).close();
+        let journal = Journal::new(File::open(path).unwrap()).unwrap();
+        let entries = journal.entries().collect::<Result<Vec<Entry>, _>>();
+        entries.unwrap()
+    }
+
+    #[test]
+    fn test_explain() {
+        let dir = tempfile::tempdir().unwrap();
+        let long = [&b"UNIT="[..], &[b'u'; 4092]].concat();
+        let entries = entries(&dir.path().join("test.journal"), &[
+            &[b"MESSAGE_ID=0123456789abcdef0123456789abcdef", b"UNIT=foo.service", b"RESULT=done"],
+            &[b"MESSAGE_ID=0123456789abcdef0123456789abcdef", &long],
+            &[b"MESSAGE_ID=00000000000000000000000000000001", b"UNIT=foo.service"],
+            &[b"MESSAGE=no id"],
+        ]);
+        let catalog = catalog(None);
+
+        assert_eq!(catalog.explain(&entries[0]).unwrap(), "Subject: Unit foo.service started\nDefined-By: test\n\n\
+                                                           The unit foo.service has begun with done.\n\n\
+                                                           Not replaced: @lower@ @A1@ @@.\n");
+        // Missing and too long fields are replaced by their name
+        assert!(catalog.explain(&entries[1]).unwrap().starts_with("Subject: Unit UNIT started"));
+        assert!(catalog.explain(&entries[1]).unwrap().contains("begun with RESULT."));
+        assert_eq!(catalog.explain(&entries[2]), None);
+        assert_eq!(catalog.explain(&entries[3]), None);
+    }
+}
@@ -0,0 +1,114 @@
+//! Journal files for the integration tests, written with `JournalWriter`.
+//! Each test file includes this module, so not all of it is used by each.
+#![allow(dead_code)]
+
+use sdjournal::writer::*;
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+
+/// Opens `path` for reading and writing, creating it if it does not exist
+pub fn open_rw(path: &Path) -> File {
+    OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path).unwrap()
+}
+
+/// Builds a journal file entry by entry. Unless given, entries are one
+/// microsecond apart starting at realtime 1000 and monotonic 10, in boot 2.
+pub struct JournalBuilder {
+    writer: JournalWriter<File>,
+    realtime: u64,
+    monotonic: u64,
+    boot_id: u128,
+}
+
+impl JournalBuilder {
+    /// Creates a journal at `path` with machine id 1 and boot id 2
+    pub fn new(path: &Path) -> JournalBuilder {
+        JournalBuilder::with_params(path, &JournalParams::new(1, 2).unwrap())
+    }
+
+    pub fn with_params(path: &Path, params: &JournalParams) -> JournalBuilder {
+        let writer = JournalWriter::create(open_rw(path), params).unwrap();
+        JournalBuilder { writer, realtime: 1000, monotonic: 10, boot_id: 2 }
+    }
+
+    /// Numbers the following entries from `seqnum`
+    pub fn seqnum(mut self, seqnum: u64) -> JournalBuilder {
+        self.writer.set_next_seqnum(seqnum).unwrap();
+        self
+    }
+
+    /// Timestamps the next entry with `realtime` and `monotonic`
+    pub fn time(mut self, realtime: u64, monotonic: u64) -> JournalBuilder {
+        self.realtime = realtime;
+        self.monotonic = monotonic;
+        self
+    }
+
+    pub fn boot_id(mut self, boot_id: u128) -> JournalBuilder {
+        self.boot_id = boot_id;
+        self
+    }
+
+    /// Appends an entry with explicit timestamps and boot id, which the
+    /// following entries continue from
+    pub fn entry_at(self, realtime: u64, monotonic: u64, boot_id: u128, items: &[&[u8]]) -> JournalBuilder {
+        self.time(realtime, monotonic).boot_id(boot_id).entry(items)
+    }
+
+    pub fn entry(mut self, items: &[&[u8]]) -> JournalBuilder {
+        self.writer.append_entry(self.realtime, self.monotonic, self.boot_id, items).unwrap();
+        self.realtime += 1;
+        self.monotonic += 1;
+        self
+    }
+
+    pub fn entries(self, entries: &[&[&[u8]]]) -> JournalBuilder {
+        entries.iter().fold(self, |builder, items| builder.entry(items))
+    }
+
+    /// Appends `n` entries with the messages `message 00`, `message 01`, ...
+    /// and priority 6
+    pub fn messages(self, n: u64) -> JournalBuilder {
+        (0..n).fold(self, |builder, i| {
+            let message = format!("MESSAGE=message {:02}", i);
+            builder.entry(&[message.as_bytes(), b"PRIORITY=6"])
+        })
+    }
+
+    /// The writer, with the journal still online
+    pub fn writer(self) -> JournalWriter<File> {
+        self.writer
+    }
+
+    pub fn close(self) {
+        self.writer.close().unwrap();
+    }
+}
+
+/// Rewrites the DATA object holding `payload` in the journal at `path` as
+/// ZSTD compressed, the way journald stores large payloads. The frame has the
+/// same length as the payload, so the object keeps its size; for that the
+/// payload has to end with a run of 13 equal bytes.
+pub fn compress_payload(path: &Path, payload: &[u8]) {
+    let len = payload.len();
+    assert!(len >= 13 && payload[len - 13..].iter().all(|&c| c == payload[len - 1]));
+    let block_header = |last: u32, block_type: u32, size: usize| {
+        (last | block_type << 1 | (size as u32) << 3).to_le_bytes()[..3].to_vec()
+    };
+    // Magic, no content size and a 1 KiB window, then a raw block with all
+    // but the run and an RLE block with the run
+    let mut frame = vec![0x28, 0xb5, 0x2f, 0xfd, 0x00, 0x00];
+    frame.extend(block_header(0, 0, len - 13));
+    frame.extend(&payload[..len - 13]);
+    frame.extend(block_header(1, 1, 13));
+    frame.push(payload[len - 1]);
+    assert_eq!(frame.len(), len);
+
+    let mut contents = std::fs::read(path).unwrap();
+    let pos = contents.windows(len).position(|w| w == payload).unwrap();
+    // The object header starts 64 bytes before the payload, with the flags
+    // in its second byte
+    contents[pos - 64 + 1] = sdjournal::journal::OBJECT_COMPRESSED_ZSTD;
+    contents[pos..pos + len].copy_from_slice(&frame);
+    std::fs::write(path, &contents).unwrap();
+}
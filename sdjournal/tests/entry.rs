@@ -0,0 +1,94 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::*;
+    use sdjournal::entry::*;
+    use sdjournal::journal::*;
+    use std::fs::File;
+    use pretty_assertions::assert_eq;
+
+    const ITEMS: &[&[u8]] = &[b"MESSAGE_ID=0123", b"MESSAGE=hello", b"TAG=one", b"TAG=two", b"BLOB=\xff", b"M=x"];
+
+    #[test]
+    fn test_entry_lookup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        JournalBuilder::new(&path).seqnum(5).boot_id(0xabcd).entry(ITEMS).close();
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let entries = journal.entries().collect::<Result<Vec<Entry>, _>>().unwrap();
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert_eq!((entry.seqnum, entry.realtime, entry.monotonic, entry.boot_id), (5, 1000, 10, 0xabcd));
+        assert_eq!(entry.seqnum_id, journal.header.seqnum_id);
+
+        assert_eq!(entry.get("MESSAGE"), Some(&b"hello"[..]));
+        assert_eq!(entry.get_str("MESSAGE_ID"), Some("0123"));
+        assert_eq!(entry.get("MESSAGE="), None);
+        assert_eq!(entry.get("MESS"), None);
+        assert_eq!(entry.get("MESSAGE_IDENTIFIER"), None);
+        assert_eq!(entry.get("BLOB"), Some(&b"\xff"[..]));
+        assert_eq!(entry.get_str("BLOB"), None);
+        assert!(entry.contains("M"));
+        assert!(!entry.contains("PRIORITY"));
+        assert_eq!(entry.get_all("TAG").collect::<Vec<_>>(), vec![&b"one"[..], &b"two"[..]]);
+        assert_eq!(entry.get("TAG"), Some(&b"one"[..]));
+
+        let fields: Vec<(&str, &[u8])> = entry.fields().collect();
+        assert_eq!(fields, vec![("MESSAGE_ID", &b"0123"[..]), ("MESSAGE", b"hello"), ("TAG", b"one"),
+                                ("TAG", b"two"), ("BLOB", b"\xff"), ("M", b"x")]);
+        assert_eq!(entry.payloads().collect::<Vec<_>>(), ITEMS.to_vec());
+        assert_eq!(entry.cursor(), format!("s={:032x};i=5;b=0000000000000000000000000000abcd;m=a;t=3e8;x={:x}",
+                                           journal.header.seqnum_id, entry.xor_hash));
+    }
+
+    #[test]
+    fn test_compressed_items() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let message = format!("MESSAGE=core dumped{}", "!".repeat(20));
+        let broken = format!("MESSAGE=out of memory{}", "!".repeat(20));
+        JournalBuilder::new(&path)
+            .entry(&[message.as_bytes(), b"PRIORITY=2"])
+            .entry(&[broken.as_bytes(), b"PRIORITY=2"])
+            .entry(&[b"MESSAGE=hello", b"PRIORITY=6"])
+            .close();
+        compress_payload(&path, message.as_bytes());
+        compress_payload(&path, broken.as_bytes());
+        // Replace the last block of the second frame with an empty one that
+        // is not marked last, so the frame ends early
+        let mut contents = std::fs::read(&path).unwrap();
+        let end = contents.windows(13).position(|w| w == b"out of memory").unwrap() + broken.len() - 17;
+        contents[end - 4..end].copy_from_slice(&[0; 4]);
+        std::fs::write(&path, &contents).unwrap();
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let entries: Vec<_> = journal.entries().collect();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].as_ref().unwrap().payloads().collect::<Vec<_>>(),
+                   vec![message.as_bytes(), b"PRIORITY=2"]);
+        assert!(entries[1].is_err());
+        assert_eq!(entries[2].as_ref().unwrap().get_str("MESSAGE"), Some("hello"));
+
+        // The hash table lookup compares the decompressed payload
+        let found = journal.find_data(message.as_bytes()).unwrap().unwrap();
+        assert_eq!(&*found.decompressed_payload().unwrap(), message.as_bytes());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_get_data_exact() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        JournalBuilder::new(&path).seqnum(5).boot_id(0xabcd).entry(ITEMS).close();
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let entry = journal.iter_entries().next().unwrap();
+        let mut file = &journal.file;
+        assert_eq!(entry.get_data("MESSAGE", &mut file), Some("hello".to_owned()));
+        assert_eq!(entry.get_data("MESSAGE=", &mut file), Some("hello".to_owned()));
+        assert_eq!(entry.get_data("MESSAGE_ID_TOO_LONG", &mut file), None);
+        assert_eq!(entry.get_data("BLOB", &mut file), None);
+    }
+}
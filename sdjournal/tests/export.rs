@@ -0,0 +1,144 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::*;
+    use sdjournal::export::*;
+    use sdjournal::journal::*;
+    use sdjournal::writer::*;
+    use std::fs::File;
+    use std::path::Path;
+    use pretty_assertions::assert_eq;
+
+    fn write_journal(path: &Path, entries: &[&[&[u8]]]) {
+        let params = JournalParams::new(1, 0x1234).unwrap();
+        JournalBuilder::with_params(path, &params).boot_id(0xabcd).entries(entries).close();
+    }
+
+    fn export(path: &Path) -> Vec<u8> {
+        let journal = Journal::new(File::open(path).unwrap()).unwrap();
+        let mut out = Vec::new();
+        for entry in journal.entries() {
+            write_entry(&mut out, &entry.unwrap()).unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn test_export_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path, &[&[b"MESSAGE=hello", b"PRIORITY=6", b"_BOOT_ID=0000000000000000000000000000abcd"],
+                               &[b"MESSAGE=tab\tseparated"]]);
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let entries: Vec<_> = journal.iter_entries().collect();
+        let cursor = |e: &EntryObject| format!("s={:032x};i={:x};b={:032x};m={:x};t={:x};x={:x}",
+                                               journal.header.seqnum_id, e.seqnum, 0xabcd,
+                                               e.monotonic, e.realtime, e.xor_hash);
+        let expected = format!("__CURSOR={}\n__REALTIME_TIMESTAMP=1000\n__MONOTONIC_TIMESTAMP=10\n\
+                                _BOOT_ID=0000000000000000000000000000abcd\nMESSAGE=hello\nPRIORITY=6\n\n\
+                                __CURSOR={}\n__REALTIME_TIMESTAMP=1001\n__MONOTONIC_TIMESTAMP=11\n\
+                                _BOOT_ID=0000000000000000000000000000abcd\nMESSAGE=tab\tseparated\n\n",
+                               cursor(&entries[0]), cursor(&entries[1]));
+        assert_eq!(String::from_utf8(export(&path)).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_export_binary() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path, &[&[b"MESSAGE=two\nlines", b"BLOB=\x00\xff", b"EMPTY="]]);
+
+        let out = export(&path);
+        let start = out.windows(8).position(|w| w == b"MESSAGE\n").unwrap();
+        let mut expected = b"MESSAGE\n".to_vec();
+        expected.extend_from_slice(&9u64.to_le_bytes());
+        expected.extend_from_slice(b"two\nlines\nBLOB\n");
+        expected.extend_from_slice(&2u64.to_le_bytes());
+        expected.extend_from_slice(b"\x00\xff\nEMPTY=\n\n");
+        assert_eq!(&out[start..], &expected[..]);
+    }
+
+    #[test]
+    fn test_write_field_invalid() {
+        let mut out = Vec::new();
+        assert!(write_field(&mut out, b"=value").is_err());
+        assert!(write_field(&mut out, b"NO_EQUALS").is_err());
+        assert_eq!(out, b"");
+    }
+
+    fn parse(stream: &[u8]) -> Vec<ExportEntry> {
+        ExportReader::new(stream).collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn test_parse_entries() {
+        let mut stream = b"__CURSOR=s=000000000000000000000000000000ff;i=1a;b=1;m=2;t=3;x=4\n\
+                           __REALTIME_TIMESTAMP=1000\n__MONOTONIC_TIMESTAMP=10\n\
+                           _BOOT_ID=0000000000000000000000000000abcd\nMESSAGE=hello\nBLOB\n".to_vec();
+        stream.extend_from_slice(&3u64.to_le_bytes());
+        stream.extend_from_slice(b"a\nb\n\n\n__SEQNUM=7\n__SEQNUM_ID=01\nMESSAGE=last");
+
+        let entries = parse(&stream);
+        assert_eq!(entries, vec![
+            ExportEntry {
+                cursor: Some("s=000000000000000000000000000000ff;i=1a;b=1;m=2;t=3;x=4".to_owned()),
+                realtime: Some(1000),
+                monotonic: Some(10),
+                boot_id: Some(0xabcd),
+                seqnum: Some(0x1a),
+                seqnum_id: Some(0xff),
+                fields: vec![b"_BOOT_ID=0000000000000000000000000000abcd".to_vec(),
+                             b"MESSAGE=hello".to_vec(), b"BLOB=a\nb".to_vec()],
+            },
+            ExportEntry {
+                seqnum: Some(7),
+                seqnum_id: Some(1),
+                fields: vec![b"MESSAGE=last".to_vec()],
+                ..Default::default()
+            },
+        ]);
+        assert_eq!(entries[0].get("BLOB"), Some(&b"a\nb"[..]));
+        assert_eq!(entries[0].get("BLO"), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let first_error = |stream: &[u8]| ExportReader::new(stream).find_map(|e| e.err()).map(|e| e.kind());
+        assert_eq!(first_error(b"message=lower case\n\n"), Some(std::io::ErrorKind::InvalidData));
+        assert_eq!(first_error(b"__REALTIME_TIMESTAMP=soon\n\n"), Some(std::io::ErrorKind::InvalidData));
+        assert_eq!(first_error(b"BLOB\n\x05\x00\x00\x00\x00\x00\x00\x00abc"), Some(std::io::ErrorKind::UnexpectedEof));
+        assert_eq!(first_error(b"BLOB\n\x01\x00\x00\x00\x00\x00\x00\x00ab\n"), Some(std::io::ErrorKind::InvalidData));
+        assert_eq!(first_error(b"\n\nMESSAGE=fine\n"), None);
+    }
+
+    #[test]
+    fn test_import_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let imported = dir.path().join("imported.journal");
+        write_journal(&path, &[&[b"MESSAGE=hello", b"_MACHINE_ID=00000000000000000000000000000001"],
+                               &[b"MESSAGE=two\nlines", b"BLOB=\x00\xff"],
+                               &[b"MESSAGE=last"]]);
+        let stream = export(&path);
+
+        assert_eq!(import(&stream[..], open_rw(&imported)).unwrap(), 3);
+
+        let original = Journal::new(File::open(&path).unwrap()).unwrap();
+        let journal = Journal::new(File::open(&imported).unwrap()).unwrap();
+        assert!(journal.verify().is_clean());
+        assert_eq!(journal.header.machine_id, 1);
+        assert_eq!(journal.header.seqnum_id, original.header.seqnum_id);
+        // The stream carries _BOOT_ID as a field, which becomes part of the entries
+        let without_boot_id = |stream: Vec<u8>| -> Vec<Vec<u8>> {
+            stream.split(|&c| c == b'\n')
+                .filter(|l| !l.starts_with(b"__CURSOR=") && !l.starts_with(b"_BOOT_ID="))
+                .map(|l| l.to_vec())
+                .collect()
+        };
+        assert_eq!(without_boot_id(export(&imported)), without_boot_id(stream));
+        let seqnums = |j: &Journal<File>| j.iter_entries().map(|e| (e.seqnum, e.boot_id)).collect::<Vec<_>>();
+        assert_eq!(seqnums(&journal), seqnums(&original));
+    }
+}
@@ -0,0 +1,100 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::*;
+    use sdjournal::entry::Entry;
+    use sdjournal::fields::*;
+    use sdjournal::journal::*;
+    use std::fs::File;
+    use std::io::ErrorKind;
+    use pretty_assertions::assert_eq;
+
+    /// Writes a journal with one entry made of `items` and reads it back
+    fn entry(items: &[&[u8]]) -> Entry {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        JournalBuilder::new(&path).entry(items).close();
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let entry = journal.entries().next().unwrap();
+        entry.unwrap()
+    }
+
+    #[test]
+    fn test_typed_fields() {
+        let entry = entry(&[b"MESSAGE=hello", b"MESSAGE_ID=d93fb3c9c24d451a97cea615ce59c00b", b"PRIORITY=3",
+                            b"SYSLOG_FACILITY=3", b"SYSLOG_IDENTIFIER=ident", b"_PID=8608", b"_UID=0",
+                            b"_GID=100", b"_HOSTNAME=vm", b"_TRANSPORT=stdout",
+                            b"_MACHINE_ID=3d1219c7c4c5404aaa1f6d2a48adfda4", b"_SYSTEMD_UNIT=foo.service",
+                            b"CODE_FILE=src/main.c", b"CODE_LINE=42", b"CODE_FUNC=main",
+                            b"_SOURCE_REALTIME_TIMESTAMP=1563627902000042", b"_SOURCE_MONOTONIC_TIMESTAMP=5"]);
+
+        assert_eq!(entry.message().unwrap(), Some("hello"));
+        assert_eq!(entry.message_id().unwrap(), Some(0xd93fb3c9c24d451a97cea615ce59c00b));
+        assert_eq!(entry.priority().unwrap(), Some(Priority::Error));
+        assert_eq!(entry.syslog_facility().unwrap(), Some(3));
+        assert_eq!(entry.syslog_identifier().unwrap(), Some("ident"));
+        assert_eq!(entry.pid().unwrap(), Some(8608));
+        assert_eq!(entry.uid().unwrap(), Some(0));
+        assert_eq!(entry.gid().unwrap(), Some(100));
+        assert_eq!(entry.hostname().unwrap(), Some("vm"));
+        assert_eq!(entry.transport().unwrap(), Some(Transport::Stdout));
+        assert_eq!(entry.machine_id().unwrap(), Some(0x3d1219c7c4c5404aaa1f6d2a48adfda4));
+        assert_eq!(entry.systemd_unit().unwrap(), Some("foo.service"));
+        assert_eq!(entry.systemd_user_unit().unwrap(), None);
+        assert_eq!(entry.code_file().unwrap(), Some("src/main.c"));
+        assert_eq!(entry.code_line().unwrap(), Some(42));
+        assert_eq!(entry.code_func().unwrap(), Some("main"));
+        assert_eq!(entry.source_realtime().unwrap(), Some(1563627902000042));
+        assert_eq!(entry.source_monotonic().unwrap(), Some(5));
+    }
+
+    #[test]
+    fn test_malformed_fields() {
+        let entry = entry(&[b"MESSAGE=\xff", b"MESSAGE_ID=nothex", b"PRIORITY=8", b"SYSLOG_FACILITY=24",
+                            b"_PID=-1", b"_UID=", b"_TRANSPORT=carrier pigeon", b"CODE_LINE=4294967296"]);
+
+        assert_eq!(entry.message().unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(entry.message_id().unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(entry.priority().unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(entry.syslog_facility().unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(entry.pid().unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(entry.uid().unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(entry.transport().unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(entry.code_line().unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(entry.gid().unwrap(), None);
+    }
+
+    #[test]
+    fn test_priority_names() {
+        assert_eq!("err".parse::<Priority>().unwrap(), Priority::Error);
+        assert_eq!("7".parse::<Priority>().unwrap(), Priority::Debug);
+        assert!("8".parse::<Priority>().is_err());
+        assert!("error".parse::<Priority>().is_err());
+        assert_eq!(Priority::Warning.to_string(), "warning");
+        assert_eq!(Priority::from_u8(0), Some(Priority::Emergency));
+        assert!(Priority::Emergency < Priority::Debug);
+        assert_eq!(Transport::Kernel.to_string(), "kernel");
+        assert_eq!("journal".parse::<Transport>().unwrap(), Transport::Journal);
+    }
+
+    #[test]
+    fn test_priority_range() {
+        assert_eq!(Priority::parse_range("warning").unwrap(), Priority::Emergency..=Priority::Warning);
+        assert_eq!(Priority::parse_range("0..3").unwrap(), Priority::Emergency..=Priority::Error);
+        assert_eq!(Priority::parse_range("err..alert").unwrap(), Priority::Alert..=Priority::Error);
+        assert_eq!(Priority::parse_range("info..info").unwrap(), Priority::Info..=Priority::Info);
+        assert!(Priority::parse_range("..3").is_err());
+        assert!(Priority::parse_range("3..").is_err());
+        assert!(Priority::parse_range("0..9").is_err());
+    }
+
+    #[test]
+    fn test_parse_id128() {
+        assert_eq!(parse_id128("3d1219c7c4c5404aaa1f6d2a48adfda4"), Some(0x3d1219c7c4c5404aaa1f6d2a48adfda4));
+        assert_eq!(parse_id128("3d1219c7-c4c5-404a-aa1f-6d2a48adfda4"), Some(0x3d1219c7c4c5404aaa1f6d2a48adfda4));
+        assert_eq!(parse_id128("3d1219c7c4c5404aaa1f6d2a48adfda"), None);
+        assert_eq!(parse_id128("+d1219c7c4c5404aaa1f6d2a48adfda4"), None);
+        assert_eq!(parse_id128("3d1219c7c-4c5-404a-aa1f-6d2a48adfda4"), None);
+    }
+}
@@ -0,0 +1,182 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::*;
+    use sdjournal::fields::Priority;
+    use sdjournal::filter::*;
+    use sdjournal::journal::*;
+    use sdjournal::writer::*;
+    use std::fs::File;
+    use std::io::ErrorKind;
+    use std::path::Path;
+    use pretty_assertions::assert_eq;
+
+    /// Writes one entry per item list, with the index as its realtime
+    fn write_journal(path: &Path, entries: &[&[&[u8]]]) {
+        // A single bucket puts every payload into the same hash chain
+        let mut params = JournalParams::new(1, 2).unwrap();
+        params.data_hash_table_buckets = 1;
+        JournalBuilder::with_params(path, &params).time(0, 0).entries(entries).close();
+    }
+
+    /// The realtimes of the entries `filter` selects
+    fn selected(path: &Path, filter: &Filter) -> Vec<u64> {
+        let journal = Journal::new(File::open(path).unwrap()).unwrap();
+        let entries = journal.entries_matching(filter).unwrap();
+        entries.map(|e| e.unwrap().realtime).collect()
+    }
+
+    const ENTRIES: &[&[&[u8]]] = &[
+        &[b"MESSAGE=a", b"PRIORITY=6", b"_SYSTEMD_UNIT=foo.service"],
+        &[b"MESSAGE=b", b"PRIORITY=3", b"_SYSTEMD_UNIT=bar.service"],
+        &[b"MESSAGE=c"],
+        &[b"MESSAGE=d", b"PRIORITY=0", b"_SYSTEMD_UNIT=foo.service"],
+        &[b"MESSAGE=e", b"PRIORITY=3", b"_SYSTEMD_UNIT=foo.service"],
+    ];
+
+    #[test]
+    fn test_priority_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path, ENTRIES);
+
+        let mut filter = Filter::new();
+        filter.add_priorities(Priority::parse_range("err").unwrap());
+        assert_eq!(selected(&path, &filter), vec![1, 3, 4]);
+
+        let mut filter = Filter::new();
+        filter.add_priorities(Priority::parse_range("info..err").unwrap());
+        assert_eq!(selected(&path, &filter), vec![0, 1, 4]);
+
+        let mut filter = Filter::new();
+        filter.add_priorities(Priority::parse_range("debug").unwrap());
+        assert!(filter.is_empty());
+        assert_eq!(selected(&path, &filter), vec![0, 1, 2, 3, 4]);
+
+        let mut filter = Filter::new();
+        filter.add_priorities(Priority::parse_range("1..2").unwrap());
+        assert_eq!(selected(&path, &filter), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn test_match_conjunction() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path, ENTRIES);
+
+        let mut filter = Filter::new();
+        filter.add_match(b"_SYSTEMD_UNIT=foo.service").unwrap();
+        assert_eq!(selected(&path, &filter), vec![0, 3, 4]);
+        filter.add_match(b"PRIORITY=3").unwrap();
+        assert_eq!(selected(&path, &filter), vec![4]);
+        filter.add_match(b"PRIORITY=6").unwrap();
+        assert_eq!(selected(&path, &filter), vec![0, 4]);
+        filter.add_match(b"_SYSTEMD_UNIT=bar.service").unwrap();
+        assert_eq!(selected(&path, &filter), vec![0, 1, 4]);
+        filter.add_match(b"MESSAGE=nowhere").unwrap();
+        assert_eq!(selected(&path, &filter), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn test_invalid_match() {
+        let mut filter = Filter::new();
+        assert_eq!(filter.add_match(b"MESSAGE").unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(filter.add_match(b"message=x").unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(filter.add_match(b"=x").unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert!(filter.is_empty());
+        filter.add_match(b"MESSAGE=a=b").unwrap();
+        assert!(!filter.is_empty());
+    }
+
+    #[test]
+    fn test_find_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path, ENTRIES);
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let data = journal.find_data(b"PRIORITY=3").unwrap().unwrap();
+        assert_eq!(data.payload, b"PRIORITY=3");
+        assert_eq!(data.n_entries, 2);
+        let offsets = journal.data_entry_offsets(&data).unwrap();
+        let realtimes: Vec<u64> = offsets.iter()
+            .map(|&o| match get_obj_at_offset(&mut &journal.file, o).unwrap() {
+                Object::Entry(e) => e.realtime,
+                o => panic!("not an entry: {}", o),
+            })
+            .collect();
+        assert_eq!(realtimes, vec![1, 4]);
+        assert!(journal.find_data(b"PRIORITY=").unwrap().is_none());
+        assert!(journal.find_data(b"PRIORITY=33").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_realtime_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path, ENTRIES);
+
+        let mut filter = Filter::new();
+        filter.set_since(1);
+        assert!(!filter.is_empty());
+        assert_eq!(selected(&path, &filter), vec![1, 2, 3, 4]);
+        filter.set_until(3);
+        assert_eq!(selected(&path, &filter), vec![1, 2, 3]);
+        filter.add_match(b"_SYSTEMD_UNIT=foo.service").unwrap();
+        assert_eq!(selected(&path, &filter), vec![3]);
+
+        let mut filter = Filter::new();
+        filter.set_until(0);
+        assert_eq!(selected(&path, &filter), vec![0]);
+        filter.set_since(5);
+        assert_eq!(selected(&path, &filter), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn test_newest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path, ENTRIES);
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let mut filter = Filter::new();
+        filter.add_match(b"_SYSTEMD_UNIT=foo.service").unwrap();
+        let newest: Vec<u64> = journal.entries_matching(&filter).unwrap().rev().map(|e| e.unwrap().realtime).collect();
+        assert_eq!(newest, vec![4, 3, 0]);
+        filter.set_until(3);
+        let newest = journal.entries_matching(&filter).unwrap().next_back().unwrap().unwrap();
+        assert_eq!(newest.realtime, 3);
+    }
+
+    #[test]
+    fn test_newest_first_unfiltered() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        // Enough entries to fill several entry arrays
+        let messages: Vec<String> = (0..40).map(|i| format!("MESSAGE={}", i)).collect();
+        let items: Vec<[&[u8]; 1]> = messages.iter().map(|m| [m.as_bytes()]).collect();
+        let entries: Vec<&[&[u8]]> = items.iter().map(|i| &i[..]).collect();
+        write_journal(&path, &entries);
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let newest: Vec<u64> = journal.entries_matching(&Filter::new()).unwrap().rev().map(|e| e.unwrap().realtime).collect();
+        assert_eq!(newest, (0..40).rev().collect::<Vec<_>>());
+        let mut filter = Filter::new();
+        filter.set_since(7);
+        filter.set_until(30);
+        assert_eq!(selected(&path, &filter), (7..=30).collect::<Vec<_>>());
+
+        // A damaged entry is an error item rather than the end of the walk
+        let mut contents = std::fs::read(&path).unwrap();
+        let mut fields = 40u64.to_le_bytes().to_vec();
+        fields.extend_from_slice(&39u64.to_le_bytes());
+        let pos = contents.windows(16).position(|w| w == &fields[..]).unwrap() - 16;
+        contents[pos] = 0;
+        std::fs::write(&path, &contents).unwrap();
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let mut newest = journal.entries_matching(&Filter::new()).unwrap().rev();
+        assert!(newest.next().unwrap().is_err());
+        assert_eq!(newest.next().unwrap().unwrap().realtime, 38);
+    }
+}
@@ -0,0 +1,42 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::*;
+    use sdjournal::entry::Entry;
+    use sdjournal::grep::*;
+    use sdjournal::journal::*;
+    use std::fs::File;
+    use std::io::ErrorKind;
+    use std::path::Path;
+    use pretty_assertions::assert_eq;
+
+    /// Writes one entry per item list and reads them back
+    fn entries(path: &Path, entries: &[&[&[u8]]]) -> Vec<Entry> {
+        JournalBuilder::new(path).time(0, 0).entries(entries).close();
+        let journal = Journal::new(File::open(path).unwrap()).unwrap();
+        let entries = journal.entries().collect::<Result<Vec<Entry>, _>>();
+        entries.unwrap()
+    }
+
+    /// The indices of the `entries` `grep` matches
+    fn grepped(entries: &[Entry], grep: &Grep) -> Vec<usize> {
+        entries.iter().enumerate().filter(|(_, e)| grep.matches(e)).map(|(i, _)| i).collect()
+    }
+
+    #[test]
+    fn test_grep() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let entries = entries(&path, &[&[b"MESSAGE=Started foo"], &[b"MESSAGE=stopped foo"], &[b"OTHER=started"],
+                                       &[b"MESSAGE=bad \xff started"], &[b"MESSAGE=line\nstarted"]]);
+
+        assert_eq!(grepped(&entries, &Grep::new("started", None).unwrap()), vec![0, 3, 4]);
+        assert_eq!(grepped(&entries, &Grep::new("Started", None).unwrap()), vec![0]);
+        assert_eq!(grepped(&entries, &Grep::new("started", Some(true)).unwrap()), vec![3, 4]);
+        assert_eq!(grepped(&entries, &Grep::new("STARTED", Some(false)).unwrap()), vec![0, 3, 4]);
+        assert_eq!(grepped(&entries, &Grep::new("^s.*d foo$", None).unwrap()), vec![0, 1]);
+        assert_eq!(grepped(&entries, &Grep::new("", None).unwrap()), vec![0, 1, 3, 4]);
+        assert_eq!(Grep::new("[", None).unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,79 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::*;
+    use sdjournal::journal::*;
+    use sdjournal::json::*;
+    use sdjournal::writer::*;
+    use std::fs::File;
+    use std::path::Path;
+    use pretty_assertions::assert_eq;
+
+    fn write_journal(path: &Path, items: &[&[u8]]) {
+        JournalBuilder::with_params(path, &JournalParams::new(1, 0x1234).unwrap()).boot_id(0xabcd).entry(items).close();
+    }
+
+    /// The entry as JSON, with the cursor replaced by `C`
+    fn json(path: &Path, mode: JsonMode, show_all: bool) -> String {
+        let journal = Journal::new(File::open(path).unwrap()).unwrap();
+        let entry = journal.entries().next().unwrap().unwrap();
+        let mut out = Vec::new();
+        write_entry(&mut out, &entry, mode, show_all).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        out.replace(&entry.cursor(), "C")
+    }
+
+    #[test]
+    fn test_json_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path, &[b"MESSAGE=say \"hi\"\n\tback\\slash", b"BLOB=a\x00", b"TAG=one", b"TAG=two",
+                               b"_BOOT_ID=0000000000000000000000000000abcd", b"EMPTY="]);
+
+        assert_eq!(json(&path, JsonMode::Json, false),
+                   "{\"__CURSOR\":\"C\",\"__REALTIME_TIMESTAMP\":\"1000\",\"__MONOTONIC_TIMESTAMP\":\"10\",\
+                    \"_BOOT_ID\":\"0000000000000000000000000000abcd\",\
+                    \"MESSAGE\":\"say \\\"hi\\\"\\n\\tback\\\\slash\",\"BLOB\":[97,0],\
+                    \"TAG\":[\"one\",\"two\"],\"EMPTY\":\"\"}\n");
+    }
+
+    #[test]
+    fn test_json_modes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path, &[b"MESSAGE=hello", b"BLOB=\xff", b"BLOB=\xfe"]);
+
+        let compact = "{\"__CURSOR\":\"C\",\"__REALTIME_TIMESTAMP\":\"1000\",\"__MONOTONIC_TIMESTAMP\":\"10\",\
+                       \"_BOOT_ID\":\"0000000000000000000000000000abcd\",\"MESSAGE\":\"hello\",\"BLOB\":[[255],[254]]}";
+        assert_eq!(json(&path, JsonMode::Sse, false), format!("data: {}\n\n", compact));
+        assert_eq!(json(&path, JsonMode::Seq, false), format!("\x1e{}\n", compact));
+        assert_eq!(json(&path, JsonMode::Pretty, false),
+                   "{\n\
+                    \t\"__CURSOR\" : \"C\",\n\
+                    \t\"__REALTIME_TIMESTAMP\" : \"1000\",\n\
+                    \t\"__MONOTONIC_TIMESTAMP\" : \"10\",\n\
+                    \t\"_BOOT_ID\" : \"0000000000000000000000000000abcd\",\n\
+                    \t\"MESSAGE\" : \"hello\",\n\
+                    \t\"BLOB\" : [\n\
+                    \t\t[\n\
+                    \t\t\t255\n\
+                    \t\t],\n\
+                    \t\t[\n\
+                    \t\t\t254\n\
+                    \t\t]\n\
+                    \t]\n\
+                    }\n");
+    }
+
+    #[test]
+    fn test_json_threshold() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let long = format!("MESSAGE={}", "x".repeat(JSON_THRESHOLD));
+        write_journal(&path, &[long.as_bytes(), b"SHORT=y"]);
+
+        assert!(json(&path, JsonMode::Json, false).ends_with(",\"MESSAGE\":null,\"SHORT\":\"y\"}\n"));
+        assert!(json(&path, JsonMode::Json, true).contains(&format!("\"MESSAGE\":\"{}\"", "x".repeat(JSON_THRESHOLD))));
+    }
+}
@@ -0,0 +1,138 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::*;
+    use sdjournal::catalog::*;
+    use sdjournal::journal::*;
+    use sdjournal::output::*;
+    use sdjournal::time::format_timestamp;
+    use std::fs::File;
+    use std::path::Path;
+    use pretty_assertions::assert_eq;
+
+    // 2019-07-20 13:05:02.000042 UTC
+    const REALTIME: u64 = 1_563_627_902_000_042;
+
+    fn write_journal(path: &Path, entries: &[(u64, u128, &[&[u8]])]) {
+        let mut builder = JournalBuilder::new(path);
+        for (i, &(monotonic, boot_id, items)) in entries.iter().enumerate() {
+            builder = builder.entry_at(REALTIME + i as u64 * 1_500_000, monotonic, boot_id, items);
+        }
+        builder.close();
+    }
+
+    fn output(path: &Path, mode: OutputMode, show_all: bool) -> String {
+        let journal = Journal::new(File::open(path).unwrap()).unwrap();
+        let mut formatter = TextFormatter::new(mode);
+        formatter.utc = true;
+        formatter.show_all = show_all;
+        let mut out = Vec::new();
+        for entry in journal.entries() {
+            formatter.write_entry(&mut out, &entry.unwrap()).unwrap();
+        }
+        String::from_utf8_lossy(&out).into_owned()
+    }
+
+    const SYSLOG: &[&[u8]] = &[b"MESSAGE=hello", b"_HOSTNAME=host", b"SYSLOG_IDENTIFIER=ident", b"_PID=42",
+                               b"_SYSTEMD_UNIT=foo.service"];
+
+    #[test]
+    fn test_short_modes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path, &[(3_000_001, 7, SYSLOG), (3_250_000, 7, &[b"MESSAGE=two\nlines\n", b"_COMM=cat"]),
+                               (1_000_000, 8, &[b"MESSAGE=\x01", b"SYSLOG_PID=9"]), (5, 8, &[b"PRIORITY=6"])]);
+
+        assert_eq!(output(&path, OutputMode::Short, false),
+                   "Jul 20 13:05:02 host ident[42]: hello\n\
+                    Jul 20 13:05:03 cat: two\n                     lines\n\
+                    Jul 20 13:05:05 unknown[9]: [1B blob data]\n");
+        assert_eq!(output(&path, OutputMode::ShortPrecise, false).lines().next(),
+                   Some("Jul 20 13:05:02.000042 host ident[42]: hello"));
+        assert_eq!(output(&path, OutputMode::ShortIso, false).lines().next(),
+                   Some("2019-07-20T13:05:02+00:00 host ident[42]: hello"));
+        assert_eq!(output(&path, OutputMode::ShortIsoPrecise, false).lines().next(),
+                   Some("2019-07-20T13:05:02.000042+00:00 host ident[42]: hello"));
+        assert_eq!(output(&path, OutputMode::ShortFull, false).lines().next(),
+                   Some("Sat 2019-07-20 13:05:02 UTC host ident[42]: hello"));
+        assert_eq!(output(&path, OutputMode::WithUnit, false).lines().next(),
+                   Some("Sat 2019-07-20 13:05:02 UTC host foo.service[42]: hello"));
+        assert_eq!(output(&path, OutputMode::ShortUnix, false).lines().next(),
+                   Some("1563627902.000042 host ident[42]: hello"));
+        assert_eq!(output(&path, OutputMode::ShortMonotonic, false).lines().next(),
+                   Some("[    3.000001] host ident[42]: hello"));
+        assert_eq!(output(&path, OutputMode::ShortDelta, false),
+                   "[    3.000001                ] host ident[42]: hello\n\
+                    [    3.250000 <    0.249999 >] cat: two\n                                    lines\n\
+                    [    1.000000 <    1.500000*>] unknown[9]: [1B blob data]\n");
+        assert_eq!(output(&path, OutputMode::Short, true).lines().nth(3), Some("Jul 20 13:05:05 unknown[9]: \x01"));
+        assert_eq!(output(&path, OutputMode::Cat, false), "hello\ntwo\nlines\n\n\x01\n");
+    }
+
+    #[test]
+    fn test_source_timestamps() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path, &[(5, 7, &[b"MESSAGE=late", b"_SOURCE_REALTIME_TIMESTAMP=1563627900000000",
+                                        b"_SOURCE_MONOTONIC_TIMESTAMP=2000000"])]);
+
+        assert_eq!(output(&path, OutputMode::Short, false), "Jul 20 13:05:00 unknown: late\n");
+        assert_eq!(output(&path, OutputMode::ShortMonotonic, false), "[    2.000000] unknown: late\n");
+    }
+
+    #[test]
+    fn test_verbose() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path, &[(5, 7, &[b"MESSAGE=two\nlines", b"BLOB=\x00\xff", b"EMPTY="])]);
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let cursor = journal.entries().next().unwrap().unwrap().cursor();
+        assert_eq!(output(&path, OutputMode::Verbose, false),
+                   format!("Sat 2019-07-20 13:05:02.000042 UTC [{}]\n    \
+                            MESSAGE=two\n            lines\n    BLOB=[2B blob data]\n    EMPTY=\n", cursor));
+        assert!(output(&path, OutputMode::Verbose, true).contains("    BLOB=\x00\u{fffd}\n"));
+    }
+
+    #[test]
+    fn test_format_helpers() {
+        assert_eq!(format_bytes(2), "2B");
+        assert_eq!(format_bytes(8 << 20), "8.0M");
+        assert_eq!(format_bytes(1536), "1.5K");
+        assert_eq!(format_timestamp(REALTIME, "%a %Y-%m-%d %H:%M:%S %Z", true), "Sat 2019-07-20 13:05:02 UTC");
+        assert_eq!(format_timestamp(REALTIME, "%H:%M%:z %z", true), "13:05+00:00 +0000");
+        // In whatever the local time zone is, `%:z` is `%z` with a colon
+        let mut offset = format_timestamp(REALTIME, "%z", false);
+        offset.insert(3, ':');
+        assert_eq!(format_timestamp(REALTIME, "%:z", false), offset);
+        assert_eq!("short-iso".parse::<OutputMode>().unwrap(), OutputMode::ShortIso);
+        assert!("shorter".parse::<OutputMode>().is_err());
+    }
+
+    #[test]
+    fn test_catalog() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path, &[(1, 7, &[b"MESSAGE=started", b"MESSAGE_ID=0123456789abcdef0123456789abcdef",
+                                        b"UNIT=foo.service"]), (2, 7, &[b"MESSAGE=plain"])]);
+        let mut catalog = Catalog::new();
+        catalog.import("-- 0123456789abcdef0123456789abcdef\nSubject: @UNIT@ started\n\nIt did.\n\n\n", None,
+                       "test.catalog").unwrap();
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let mut out = Vec::new();
+        for mode in [OutputMode::Short, OutputMode::Cat] {
+            let mut formatter = TextFormatter::new(mode);
+            formatter.utc = true;
+            formatter.set_catalog(catalog.clone());
+            for entry in journal.entries() {
+                formatter.write_entry(&mut out, &entry.unwrap()).unwrap();
+            }
+        }
+
+        let mark = if is_locale_utf8() { "\u{2591}\u{2591} " } else { "-- " };
+        assert_eq!(String::from_utf8(out).unwrap(),
+                   format!("Jul 20 13:05:02 unknown: started\n{mark}Subject: foo.service started\n{mark}\n\
+                            {mark}It did.\nJul 20 13:05:03 unknown: plain\nstarted\nplain\n"));
+    }
+}
@@ -0,0 +1,115 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::*;
+    use sdjournal::journal::*;
+    use sdjournal::repair::*;
+    use std::fs::File;
+    use std::path::Path;
+    use pretty_assertions::assert_eq;
+
+    fn write_journal(path: &Path) {
+        JournalBuilder::new(path).seqnum(100).boot_id(0x0123456789abcdef).messages(20).close();
+    }
+
+    /// Seqnum, boot id and message of every entry
+    fn entries(path: &Path) -> Vec<(u64, u128, String)> {
+        let journal = Journal::new(File::open(path).unwrap()).unwrap();
+        journal.entries()
+            .map(|e| e.unwrap())
+            .map(|e| (e.seqnum, e.boot_id, e.get_str("MESSAGE").unwrap().to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn test_repair_intact_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let out = dir.path().join("repaired.journal");
+        write_journal(&path);
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let report = repair(&journal, open_rw(&out)).unwrap();
+        assert_eq!(report, RepairReport { recovered: 20, ..Default::default() });
+
+        assert_eq!(entries(&out), entries(&path));
+        let repaired = Journal::new(File::open(&out).unwrap()).unwrap();
+        assert!(repaired.verify().is_clean());
+        assert_eq!(repaired.header.seqnum_id, journal.header.seqnum_id);
+    }
+
+    #[test]
+    fn test_repair_damaged_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let out = dir.path().join("repaired.journal");
+        write_journal(&path);
+
+        // Break the payload of one message and the header of another
+        let mut contents = std::fs::read(&path).unwrap();
+        let pos = contents.windows(10).position(|w| w == b"message 03").unwrap();
+        contents[pos] = b'M';
+        let pos = contents.windows(10).position(|w| w == b"message 12").unwrap() - 8 - 64;
+        contents[pos..pos + 16].copy_from_slice(&[0xff; 16]);
+        std::fs::write(&path, &contents).unwrap();
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let report = repair(&journal, open_rw(&out)).unwrap();
+        assert_eq!(report.recovered, 18);
+        assert_eq!(report.lost(), 2);
+        // The entry whose DATA object lost its header is still found
+        assert_eq!(report.rejected, 2);
+        assert_eq!(report.missing, 0);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].0, pos as u64);
+
+        let messages: Vec<String> = entries(&out).into_iter().map(|(_, _, m)| m).collect();
+        assert!(!messages.contains(&"message 03".to_owned()));
+        assert!(!messages.contains(&"message 12".to_owned()));
+        assert_eq!(messages.len(), 18);
+        assert!(Journal::new(File::open(&out).unwrap()).unwrap().verify().is_clean());
+    }
+
+    #[test]
+    fn test_repair_damaged_shared_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let out = dir.path().join("repaired.journal");
+        write_journal(&path);
+
+        // Every entry references the one PRIORITY=6 object
+        let mut contents = std::fs::read(&path).unwrap();
+        let pos = contents.windows(10).position(|w| w == b"PRIORITY=6").unwrap() - 64;
+        contents[pos..pos + 16].copy_from_slice(&[0xff; 16]);
+        std::fs::write(&path, &contents).unwrap();
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let report = repair(&journal, open_rw(&out)).unwrap();
+        assert_eq!(report.recovered, 0);
+        assert_eq!(report.rejected, 20);
+        assert_eq!(report.missing, 0);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].0, pos as u64);
+        assert_eq!(entries(&out), vec![]);
+    }
+
+    #[test]
+    fn test_repair_compressed_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let out = dir.path().join("repaired.journal");
+        let message = format!("MESSAGE=core dumped{}", "!".repeat(20));
+        JournalBuilder::new(&path).messages(2).entry(&[message.as_bytes(), b"PRIORITY=2"]).close();
+        compress_payload(&path, message.as_bytes());
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let report = repair(&journal, open_rw(&out)).unwrap();
+        assert_eq!(report, RepairReport { recovered: 3, ..Default::default() });
+
+        let messages: Vec<String> = entries(&out).into_iter().map(|(_, _, m)| m).collect();
+        assert_eq!(messages, vec!["message 00", "message 01", &message["MESSAGE=".len()..]]);
+        let repaired = Journal::new(File::open(&out).unwrap()).unwrap();
+        assert!(repaired.verify().is_clean());
+    }
+}
@@ -0,0 +1,155 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::*;
+    use sdjournal::journal::*;
+    use sdjournal::rotate::*;
+    use sdjournal::seal::*;
+    use sdjournal::writer::*;
+    use std::fs::{self, File};
+    use pretty_assertions::assert_eq;
+
+    fn archived_files(dir: &std::path::Path) -> Vec<(ArchivedName, std::path::PathBuf)> {
+        let mut files: Vec<_> = fs::read_dir(dir).unwrap()
+            .map(|e| e.unwrap().path())
+            .filter_map(|p| Some((ArchivedName::parse(p.file_name()?.to_str()?)?, p)))
+            .collect();
+        files.sort_by_key(|(n, _)| n.head_seqnum);
+        files
+    }
+
+    #[test]
+    fn test_archived_name_roundtrip() {
+        let name = ArchivedName {
+            name: "system".to_owned(),
+            seqnum_id: 0xf5c61067f7f64d32963ef8770ad232e6,
+            head_seqnum: 0x1f,
+            head_realtime: 0x5a1f3c2d4e5f6,
+        };
+        let file_name = name.to_string();
+        assert_eq!(file_name, "system@f5c61067f7f64d32963ef8770ad232e6-000000000000001f-0005a1f3c2d4e5f6.journal");
+        assert_eq!(ArchivedName::parse(&file_name), Some(name));
+        assert_eq!(ArchivedName::parse("system.journal"), None);
+        assert_eq!(ArchivedName::parse("system@0005a1f3c2d4e5f6-000000000000001f.journal~"), None);
+    }
+
+    #[test]
+    fn test_rotate_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let params = JournalParams::new(1, 2).unwrap();
+        let limits = RotationLimits { max_file_size: Some(64 * 1024), max_file_usec: None };
+        let mut writer = RotatingWriter::open(dir.path(), "system", params, limits).unwrap();
+        let seqnum_id = writer.header().unwrap().seqnum_id;
+        for i in 0..1000u64 {
+            let message = format!("MESSAGE=message number {}", i);
+            writer.append_entry(1000 + i, i, 2, &[message.as_bytes()]).unwrap();
+        }
+        writer.close().unwrap();
+
+        let archived = archived_files(dir.path());
+        assert!(archived.len() > 1);
+        let mut next_seqnum = 1;
+        for (name, path) in archived.iter() {
+            let journal = Journal::new(File::open(path).unwrap()).unwrap();
+            assert_eq!(journal.header.state, JournalState::Archived);
+            assert_eq!(journal.header.seqnum_id, seqnum_id);
+            assert_eq!(name.seqnum_id, seqnum_id);
+            assert_eq!(name.head_seqnum, next_seqnum);
+            assert_eq!(name.head_realtime, journal.header.head_entry_realtime);
+            next_seqnum = journal.header.tail_entry_seqnum + 1;
+        }
+
+        let active = Journal::new(File::open(dir.path().join("system.journal")).unwrap()).unwrap();
+        assert_eq!(active.header.state, JournalState::Offline);
+        assert_eq!(active.header.seqnum_id, seqnum_id);
+        assert_eq!(active.header.head_entry_seqnum, next_seqnum);
+        assert_eq!(active.header.tail_entry_seqnum, 1000);
+    }
+
+    #[test]
+    fn test_rotate_by_age() {
+        let dir = tempfile::tempdir().unwrap();
+        let params = JournalParams::new(1, 2).unwrap();
+        let limits = RotationLimits { max_file_size: None, max_file_usec: Some(1_000_000) };
+        let mut writer = RotatingWriter::open(dir.path(), "user-1000", params, limits).unwrap();
+        for i in 0..10u64 {
+            writer.append_entry(1_000_000 + i * 400_000, i, 2, &[b"MESSAGE=tick"]).unwrap();
+        }
+        writer.close().unwrap();
+
+        // Entries 0-2, 3-5 and 6-8 are archived, entry 9 is active
+        let archived = archived_files(dir.path());
+        let heads: Vec<u64> = archived.iter().map(|(n, _)| n.head_seqnum).collect();
+        assert_eq!(heads, vec![1, 4, 7]);
+    }
+
+    #[test]
+    fn test_reopen_continues_active_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let params = JournalParams::new(1, 2).unwrap();
+        let mut writer = RotatingWriter::open(dir.path(), "system", params.clone(), RotationLimits::default()).unwrap();
+        writer.append_entry(1000, 1, 2, &[b"MESSAGE=one"]).unwrap();
+        writer.close().unwrap();
+
+        let mut writer = RotatingWriter::open(dir.path(), "system", params, RotationLimits::default()).unwrap();
+        writer.append_entry(2000, 2, 2, &[b"MESSAGE=two"]).unwrap();
+        let archived = writer.rotate().unwrap();
+        writer.close().unwrap();
+
+        let journal = Journal::new(File::open(archived).unwrap()).unwrap();
+        assert_eq!(journal.header.n_entries, 2);
+        assert_eq!(journal.header.tail_entry_seqnum, 2);
+    }
+
+    /// Names of the files moved aside as damaged
+    fn disposed_files(dir: &std::path::Path) -> Vec<String> {
+        fs::read_dir(dir).unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .filter(|n| n.ends_with(".journal~"))
+            .collect()
+    }
+
+    #[test]
+    fn test_open_archives_sealed_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("system.journal");
+        let params = JournalParams::new(1, 2).unwrap();
+        let (sealing, verification) = SealingKey::generate(1, 2, 60_000_000, 1_000_000_000_000).unwrap();
+        let start = sealing.start_usec;
+        let mut writer = JournalWriter::create_sealed(open_rw(&path), &params, sealing).unwrap();
+        writer.append_entry(start, 1, 2, &[b"MESSAGE=sealed"]).unwrap();
+        writer.close().unwrap();
+
+        // The writer cannot continue the seal, so the intact file is archived
+        let mut writer = RotatingWriter::open(dir.path(), "system", params, RotationLimits::default()).unwrap();
+        writer.append_entry(start + 1, 2, 2, &[b"MESSAGE=fresh"]).unwrap();
+        writer.close().unwrap();
+
+        assert_eq!(disposed_files(dir.path()), Vec::<String>::new());
+        let archived = archived_files(dir.path());
+        assert_eq!(archived.len(), 1);
+        let journal = Journal::new(File::open(&archived[0].1).unwrap()).unwrap();
+        assert_eq!(journal.header.state, JournalState::Archived);
+        assert!(journal.verify_seal(&verification).is_ok());
+        let active = Journal::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(active.header.seqnum_id, journal.header.seqnum_id);
+        assert_eq!(active.header.head_entry_seqnum, 2);
+    }
+
+    #[test]
+    fn test_open_disposes_unclean_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("system.journal");
+        let mut writer = JournalWriter::create(open_rw(&path), &JournalParams::new(1, 2).unwrap()).unwrap();
+        writer.append_entry(1000, 1, 2, &[b"MESSAGE=crashed"]).unwrap();
+        drop(writer);
+
+        let writer = RotatingWriter::open(dir.path(), "system", JournalParams::new(1, 2).unwrap(),
+                                          RotationLimits::default()).unwrap();
+        assert_eq!(writer.header().unwrap().n_entries, 0);
+        writer.close().unwrap();
+        assert_eq!(disposed_files(dir.path()).len(), 1);
+        assert_eq!(archived_files(dir.path()).len(), 0);
+    }
+}
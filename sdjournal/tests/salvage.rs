@@ -0,0 +1,97 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::*;
+    use sdjournal::iter::Salvaged;
+    use sdjournal::journal::*;
+    use std::fs::File;
+    use std::path::Path;
+    use pretty_assertions::assert_eq;
+
+    /// Writes a closed journal with twenty entries and returns its contents
+    fn write_journal(path: &Path) -> Vec<u8> {
+        JournalBuilder::new(path).messages(20).close();
+        std::fs::read(path).unwrap()
+    }
+
+    fn salvage(path: &Path) -> (Vec<u64>, Vec<(u64, u64)>) {
+        let journal = Journal::new(File::open(path).unwrap()).unwrap();
+        let mut objects = Vec::new();
+        let mut skipped = Vec::new();
+        for salvaged in journal.salvage_iter() {
+            match salvaged {
+                Salvaged::Object(offset, _) | Salvaged::Entry(offset, ..) => objects.push(offset),
+                Salvaged::Skipped { start, end } => skipped.push((start, end)),
+            }
+        }
+        (objects, skipped)
+    }
+
+    fn data_offset(contents: &[u8], message: &[u8]) -> u64 {
+        (contents.windows(message.len()).position(|w| w == message).unwrap() - 8 - 64) as u64
+    }
+
+    #[test]
+    fn test_salvage_clean() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path);
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let (objects, skipped) = salvage(&path);
+        assert_eq!(skipped, vec![]);
+        assert_eq!(objects.len() as u64, journal.header.n_objects);
+        assert_eq!(objects.last(), Some(&journal.header.tail_object_offset));
+        assert_eq!(journal.obj_iter().count(), objects.len());
+    }
+
+    #[test]
+    fn test_salvage_resynchronizes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let mut contents = write_journal(&path);
+        let damaged = data_offset(&contents, b"message 05");
+        let next = data_offset(&contents, b"message 06");
+        // Smash the header of the data object and everything up to the next one
+        for b in contents[damaged as usize..next as usize].iter_mut() {
+            *b = 0xff;
+        }
+        std::fs::write(&path, &contents).unwrap();
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        assert!((journal.obj_iter().count() as u64) < journal.header.n_objects);
+
+        let (objects, skipped) = salvage(&path);
+        assert_eq!(skipped, vec![(damaged, next)]);
+        assert!(objects.contains(&next));
+        assert_eq!(objects.last(), Some(&journal.header.tail_object_offset));
+    }
+
+    #[test]
+    fn test_salvage_zeroed_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let mut contents = write_journal(&path);
+        let start = data_offset(&contents, b"message 10");
+        for b in contents[start as usize..start as usize + 512].iter_mut() {
+            *b = 0;
+        }
+        std::fs::write(&path, &contents).unwrap();
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let (objects, skipped) = salvage(&path);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].0, start);
+        assert!(skipped[0].1 >= start + 512);
+        assert!(objects.iter().all(|&o| o < start || o >= skipped[0].1));
+        assert_eq!(objects.last(), Some(&journal.header.tail_object_offset));
+
+        // Zeroes at the end of the arena are unused space, not damage
+        contents.resize(contents.len() + 4096, 0);
+        let arena_size = journal.header.arena_size + 4096;
+        contents[96..104].copy_from_slice(&arena_size.to_le_bytes());
+        std::fs::write(&path, &contents).unwrap();
+        assert_eq!(salvage(&path).1, skipped);
+    }
+}
@@ -0,0 +1,153 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::*;
+    use sdjournal::fsprg::*;
+    use sdjournal::journal::*;
+    use sdjournal::seal::*;
+    use sdjournal::writer::*;
+    use std::fs::File;
+    use pretty_assertions::assert_eq;
+
+    const KEY: &str = "bf05be-63eb01-a03706-27e045/1e634a-35a4e900";
+
+    #[test]
+    fn test_verification_key_roundtrip() {
+        let key: VerificationKey = KEY.parse().unwrap();
+        assert_eq!(key.seed, vec![0xbf, 0x05, 0xbe, 0x63, 0xeb, 0x01, 0xa0, 0x37, 0x06, 0x27, 0xe0, 0x45]);
+        assert_eq!(key.interval_usec, 0x35a4e900);
+        assert_eq!(key.start_usec, 0x1e634a * 0x35a4e900);
+        assert_eq!(key.epoch(key.start_usec + 3 * key.interval_usec + 1), 3);
+        assert_eq!(key.to_string(), KEY);
+
+        assert!("bf05be63eb01a0370627e045".parse::<VerificationKey>().is_err());
+        assert!("bf05be-63eb01/1e634a-35a4e900".parse::<VerificationKey>().is_err());
+        assert!("bf05be-63eb01-a03706-27e045/1e634a-0".parse::<VerificationKey>().is_err());
+    }
+
+    #[test]
+    fn test_fsprg_seek_matches_evolve() {
+        let seed = [7u8; FSPRG_RECOMMENDED_SEEDLEN];
+        let (msk, mpk) = gen_mk(&seed, 256);
+        let mut state = FsprgState::new(&mpk, &seed);
+        assert_eq!(state.epoch(), 0);
+        assert!(FsprgState::seek(0, &msk, &seed) == state);
+
+        for epoch in 1..5 {
+            state.evolve();
+            assert_eq!(state.epoch(), epoch);
+            assert!(FsprgState::seek(epoch, &msk, &seed) == state);
+        }
+        assert!(FsprgState::from_bytes(state.as_bytes()) == Some(state.clone()));
+        assert!(FsprgState::from_bytes(&state.as_bytes()[1..]).is_none());
+    }
+
+    #[test]
+    fn test_verify_seal_unsealed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let mut writer = JournalWriter::create(open_rw(&path), &JournalParams::new(1, 2).unwrap()).unwrap();
+        writer.append_entry(1000, 10, 2, &[b"MESSAGE=unsealed"]).unwrap();
+        writer.close().unwrap();
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        assert!(journal.verify_seal(&KEY.parse().unwrap()).is_err());
+    }
+
+    fn append_messages(writer: &mut JournalWriter<File>, start: u64, step: u64, range: std::ops::Range<u64>) {
+        for i in range {
+            let message = format!("MESSAGE=message {}", i);
+            writer.append_entry(start + i * step, i, 2, &[message.as_bytes(), b"PRIORITY=6"]).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_sealing_key_roundtrip() {
+        let (sealing, verification) = SealingKey::generate(1, 2, 60_000_000, 1_000_030_000_000).unwrap();
+        assert_eq!(sealing.start_usec, 1_000_020_000_000);
+        assert_eq!(verification.start_usec, sealing.start_usec);
+        assert_eq!(sealing.epoch(sealing.start_usec + 150_000_000), Some(2));
+        assert_eq!(sealing.epoch(sealing.start_usec - 1), None);
+
+        let mut buf = Vec::new();
+        sealing.write(&mut buf).unwrap();
+        assert_eq!(buf.len() as u64, FSS_HEADER_SZ + state_size(FSPRG_RECOMMENDED_SECPAR) as u64);
+        assert_eq!(SealingKey::read(&buf[..]).unwrap(), sealing);
+        buf[0] = b'X';
+        assert!(SealingKey::read(&buf[..]).is_err());
+    }
+
+    #[test]
+    fn test_sealed_write_and_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let interval = 60_000_000;
+        let (sealing, verification) = SealingKey::generate(1, 2, interval, 1_000_000_000_000).unwrap();
+        let start = sealing.start_usec;
+
+        // Three entries per epoch over four epochs
+        let mut writer = JournalWriter::create_sealed(open_rw(&path), &JournalParams::new(1, 2).unwrap(), sealing).unwrap();
+        append_messages(&mut writer, start, interval / 3, 0..6);
+        let sealing = writer.sealing_key().unwrap().clone();
+        assert_eq!(sealing.state.epoch(), 1);
+        writer.close().unwrap();
+
+        // Appending needs the key and continues the seal
+        assert!(JournalWriter::open(open_rw(&path)).is_err());
+        let mut writer = JournalWriter::open_sealed(open_rw(&path), sealing).unwrap();
+        append_messages(&mut writer, start, interval / 3, 6..12);
+        writer.close().unwrap();
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        assert!(journal.header.compatible_flags & HEADER_COMPATIBLE_SEALED != 0);
+        let report = journal.verify_seal(&verification).unwrap();
+        assert_eq!(report.n_tags, journal.header.n_tags);
+        assert_eq!(report.first_contained, start);
+        assert_eq!(report.last_validated, start + 11 * (interval / 3));
+        assert_eq!(report.last_validated, report.last_contained);
+    }
+
+    #[test]
+    fn test_sealed_tampering_detected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let (sealing, verification) = SealingKey::generate(1, 2, 60_000_000, 1_000_000_000_000).unwrap();
+        let start = sealing.start_usec;
+        let mut writer = JournalWriter::create_sealed(open_rw(&path), &JournalParams::new(1, 2).unwrap(), sealing).unwrap();
+        append_messages(&mut writer, start, 1_000_000, 0..3);
+        writer.close().unwrap();
+
+        let mut contents = std::fs::read(&path).unwrap();
+        let pos = contents.windows(9).position(|w| w == b"message 1").unwrap();
+        contents[pos + 8] = b'7';
+        std::fs::write(&path, &contents).unwrap();
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        assert!(journal.verify_seal(&verification).is_err());
+    }
+
+    #[test]
+    fn test_sealed_bad_object_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let (sealing, verification) = SealingKey::generate(1, 2, 60_000_000, 1_000_000_000_000).unwrap();
+        let start = sealing.start_usec;
+        let mut writer = JournalWriter::create_sealed(open_rw(&path), &JournalParams::new(1, 2).unwrap(), sealing).unwrap();
+        append_messages(&mut writer, start, 1_000_000, 0..3);
+        writer.close().unwrap();
+
+        let contents = std::fs::read(&path).unwrap();
+        let pos = contents.windows(9).position(|w| w == b"message 1").unwrap();
+        // The size of the data object sits 8 bytes into it, and its payload 64
+        let size_pos = pos - b"MESSAGE=".len() - 64 + 8;
+        for &size in &[1, 0x1000_0000, u64::MAX - 3] {
+            let mut damaged = contents.clone();
+            damaged[size_pos..size_pos + 8].copy_from_slice(&size.to_le_bytes());
+            std::fs::write(&path, &damaged).unwrap();
+
+            let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+            assert!(journal.verify_seal(&verification).is_err());
+        }
+    }
+}
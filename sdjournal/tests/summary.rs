@@ -0,0 +1,128 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::*;
+    use sdjournal::journal::*;
+    use sdjournal::multi::MultiJournal;
+    use sdjournal::summary::write_header_report;
+    use sdjournal::writer::JournalParams;
+    use std::fs::File;
+    use std::path::Path;
+    use pretty_assertions::assert_eq;
+
+    /// Writes `n` entries with realtimes 100, 101, ... and seqnums from 5
+    fn write_journal(path: &Path, n: u64) {
+        let builder = JournalBuilder::new(path).seqnum(5).time(100, 0);
+        (0..n).fold(builder, |builder, _| builder.entry(&[b"MESSAGE=hello"])).close();
+    }
+
+    #[test]
+    fn test_summaries() {
+        let dir = tempfile::tempdir().unwrap();
+        write_journal(&dir.path().join("a.journal"), 3);
+        write_journal(&dir.path().join("b.journal"), 0);
+
+        let journals = MultiJournal::open_directory(dir.path()).unwrap();
+        let summaries = journals.summaries().unwrap();
+        assert_eq!(summaries.len(), 2);
+        let (a, b) = (&summaries[0], &summaries[1]);
+        assert_eq!(a.path, dir.path().join("a.journal"));
+        assert_eq!(a.state, JournalState::Offline);
+        assert_eq!((a.n_entries, a.head_seqnum, a.tail_seqnum), (3, 5, 7));
+        assert_eq!((a.head_realtime, a.tail_realtime), (100, 102));
+        assert_eq!(a.seqnum_id, journals.journals[0].1.header.seqnum_id);
+        let (a_used, b_used) = (a.arena_used.unwrap(), b.arena_used.unwrap());
+        assert!(a_used > b_used && a_used <= a.arena_size);
+        assert_eq!((b.n_entries, b.head_seqnum, b.tail_seqnum, b.head_realtime), (0, 0, 0, 0));
+
+        // The objects end where the last one does
+        let header = &journals.journals[0].1.header;
+        assert_eq!(a_used % 8, 0);
+        assert!(header.header_size + a_used > header.tail_object_offset);
+        assert_eq!(journals.disk_usage().unwrap(), a.disk_usage + b.disk_usage);
+    }
+
+    #[test]
+    fn test_header_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path, 0);
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let mut out = Vec::new();
+        write_header_report(&mut out, &path, &journal).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+
+        assert_eq!(lines[0], format!("File path: {}", path.display()));
+        let file_id = format!("{:032x}", journal.header.file_id);
+        assert_eq!(lines[1], format!("File ID: {}-{}-{}-{}-{}", &file_id[..8], &file_id[8..12], &file_id[12..16],
+                                     &file_id[16..20], &file_id[20..]));
+        assert!(lines.contains(&"Machine ID: 00000000-0000-0000-0000-000000000001"));
+        assert!(lines.contains(&"State: OFFLINE"));
+        assert!(lines.contains(&"Incompatible flags:"));
+        assert!(lines.contains(&"Header size: 240"));
+        // Headers without the hash chain depths are outdated
+        assert!(lines.contains(&"Rotate suggested: yes"));
+        assert!(lines.contains(&"Head realtime timestamp: n/a (0)"));
+        assert!(lines.contains(&"Tail monotonic timestamp: 0 (0)"));
+        assert!(lines.contains(&"Data hash table fill: 0.0%"));
+        assert!(lines.contains(&"Deepest field hash chain: 0"));
+        assert!(lines.contains(&"Deepest data hash chain: 0"));
+        assert!(lines[lines.len() - 2].starts_with("Disk usage: "));
+        assert!(lines[lines.len() - 1].starts_with("Arena used: "));
+        assert_eq!(format!("{:?}", journal.header).matches("n_data").count(), 1);
+    }
+
+    /// The header report of a journal with single bucket hash tables and
+    /// three DATA objects on two fields
+    fn chained_header_report(path: &Path, header_size: u64) -> String {
+        let params = JournalParams {
+            data_hash_table_buckets: 1,
+            field_hash_table_buckets: 1,
+            header_size,
+            ..JournalParams::new(1, 2).unwrap()
+        };
+        JournalBuilder::with_params(path, &params)
+            .entries(&[&[b"MESSAGE=one", b"PRIORITY=6"], &[b"MESSAGE=two", b"PRIORITY=6"]])
+            .close();
+        let journal = Journal::new(File::open(path).unwrap()).unwrap();
+        let mut out = Vec::new();
+        write_header_report(&mut out, path, &journal).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_header_report_hash_chains() {
+        let dir = tempfile::tempdir().unwrap();
+        // Measured from the hash tables when the header does not record them
+        let measured = chained_header_report(&dir.path().join("old.journal"), HEADER_SZ);
+        assert!(measured.contains("Header size: 240\n"));
+        assert!(measured.contains("Deepest field hash chain: 2\nDeepest data hash chain: 3\n"));
+        // and otherwise taken from the header
+        let recorded = chained_header_report(&dir.path().join("new.journal"), 256);
+        assert!(recorded.contains("Header size: 256\n"));
+        assert!(recorded.contains("Deepest field hash chain: 2\nDeepest data hash chain: 3\n"));
+    }
+
+    #[test]
+    fn test_implausible_tail_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path, 3);
+        let tail = Journal::new(File::open(&path).unwrap()).unwrap().header.tail_object_offset as usize;
+
+        let contents = std::fs::read(&path).unwrap();
+        for &size in &[u64::MAX - 3, 1 << 40, 1] {
+            let mut damaged = contents.clone();
+            damaged[tail + 8..tail + 16].copy_from_slice(&size.to_le_bytes());
+            std::fs::write(&path, &damaged).unwrap();
+
+            let journals = MultiJournal::open(&[&path]).unwrap();
+            assert_eq!(journals.summaries().unwrap()[0].arena_used, None);
+            let mut out = Vec::new();
+            write_header_report(&mut out, &path, &journals.journals[0].1).unwrap();
+            assert!(String::from_utf8(out).unwrap().ends_with("Arena used: unknown\n"));
+        }
+    }
+}
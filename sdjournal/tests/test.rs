@@ -274,7 +274,7 @@ mod tests {
         let file = File::open("tests/user-1000.journal").unwrap();
         let journal = Journal::new(&file).unwrap();
 
-        assert_eq!(journal.verify(), true);
+        assert!(journal.verify().is_clean());
     }
 
     #[test]
@@ -282,6 +282,6 @@ mod tests {
         let file = File::open("tests/system.journal").unwrap();
         let journal = Journal::new(&file).unwrap();
 
-        assert_eq!(journal.verify(), true);
+        assert!(journal.verify().is_clean());
     }
 }
@@ -0,0 +1,58 @@
+#[cfg(test)]
+mod tests {
+    use sdjournal::time::*;
+    use pretty_assertions::assert_eq;
+
+    /// Sun 2026-10-18 19:20:30.5 UTC
+    const NOW: u64 = 1_792_351_230_500_000;
+
+    #[test]
+    fn test_relative_timestamps() {
+        assert_eq!(parse_timestamp("now", NOW), Some(NOW));
+        assert_eq!(parse_timestamp("-1h", NOW), Some(NOW - USEC_PER_HOUR));
+        assert_eq!(parse_timestamp("+30min", NOW), Some(NOW + 30 * USEC_PER_MINUTE));
+        assert_eq!(parse_timestamp("2 days ago", NOW), Some(NOW - 2 * USEC_PER_DAY));
+        assert_eq!(parse_timestamp("1h 30min left", NOW), Some(NOW + 90 * USEC_PER_MINUTE));
+        assert_eq!(parse_timestamp("@1792351230", NOW), Some(1_792_351_230_000_000));
+        assert_eq!(parse_timestamp("@1792351230.25", NOW), Some(1_792_351_230_250_000));
+        assert_eq!(parse_timestamp("-1x", NOW), None);
+        assert_eq!(parse_timestamp("@", NOW), None);
+    }
+
+    #[test]
+    fn test_absolute_timestamps() {
+        assert_eq!(parse_timestamp("2026-10-18 19:20:30 UTC", NOW), Some(1_792_351_230_000_000));
+        assert_eq!(parse_timestamp("2026-10-18 19:20:30.5 UTC", NOW), Some(NOW));
+        assert_eq!(parse_timestamp("Sun 2026-10-18 19:20:30 UTC", NOW), Some(1_792_351_230_000_000));
+        assert_eq!(parse_timestamp("Sunday 2026-10-18 19:20 UTC", NOW), Some(1_792_351_200_000_000));
+        assert_eq!(parse_timestamp("2026-10-18 UTC", NOW), Some(1_792_281_600_000_000));
+        assert_eq!(parse_timestamp("08:00 UTC", NOW), Some(1_792_310_400_000_000));
+        assert_eq!(parse_timestamp("Mon 2026-10-18 19:20:30 UTC", NOW), None);
+        assert_eq!(parse_timestamp("2026-13-01 UTC", NOW), None);
+        assert_eq!(parse_timestamp("2026-10-18 24:00:00 UTC", NOW), None);
+        assert_eq!(parse_timestamp("2026-10-18T19:20:30 UTC", NOW), None);
+        assert_eq!(parse_timestamp("yesterday evening", NOW), None);
+    }
+
+    #[test]
+    fn test_days() {
+        let today = parse_timestamp("today", NOW).unwrap();
+        assert!(today <= NOW && NOW - today < USEC_PER_DAY);
+        assert_eq!(format_timestamp(today, "%H:%M:%S", false), "00:00:00");
+        let yesterday = parse_timestamp("yesterday", NOW).unwrap();
+        assert_eq!(format_timestamp(yesterday, "%H:%M:%S", false), "00:00:00");
+        assert!(today - yesterday >= 23 * USEC_PER_HOUR && today - yesterday <= 25 * USEC_PER_HOUR);
+        assert!(parse_timestamp("tomorrow", NOW).unwrap() > NOW);
+    }
+
+    #[test]
+    fn test_format_timespan() {
+        assert_eq!(format_timespan(0, USEC_PER_MSEC), "0");
+        assert_eq!(format_timespan(129, USEC_PER_MSEC), "129us");
+        assert_eq!(format_timespan(19_005_000, USEC_PER_MSEC), "19.005s");
+        assert_eq!(format_timespan(1_457_094_321, USEC_PER_MSEC), "24min 17.094s");
+        assert_eq!(format_timespan(2 * USEC_PER_HOUR + 4 * USEC_PER_SEC, USEC_PER_MSEC), "2h 4s");
+        assert_eq!(format_timespan(USEC_PER_WEEK + USEC_PER_DAY + 1500, 1), "1w 1d 1.500ms");
+        assert_eq!(format_timespan(1_500_000, 1), "1.500000s");
+    }
+}
@@ -0,0 +1,86 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::*;
+    use sdjournal::filter::*;
+    use sdjournal::multi::MultiJournal;
+    use sdjournal::unit::*;
+    use std::path::Path;
+    use pretty_assertions::assert_eq;
+
+    /// Writes one entry per item list, with the index as its realtime
+    fn write_journal(path: &Path, entries: &[&[&[u8]]]) {
+        JournalBuilder::new(path).time(0, 0).entries(entries).close();
+    }
+
+    /// The realtimes of the entries any of `alternatives` selects
+    fn selected(journals: &MultiJournal, alternatives: Vec<Filter>) -> Vec<u64> {
+        let mut filter = Filter::new();
+        filter.add_any(alternatives);
+        journals.entries_matching(&filter).unwrap().map(|e| e.unwrap().realtime).collect()
+    }
+
+    const ENTRIES: &[&[&[u8]]] = &[
+        &[b"MESSAGE=itself", b"_UID=1000", b"_SYSTEMD_UNIT=foo.service"],
+        &[b"MESSAGE=dumped", b"MESSAGE_ID=fc2e22bc6ee647b6b90729ab34a250b1", b"_UID=0", b"COREDUMP_UNIT=foo.service"],
+        &[b"MESSAGE=forged", b"MESSAGE_ID=fc2e22bc6ee647b6b90729ab34a250b1", b"_UID=1000",
+          b"COREDUMP_UNIT=foo.service"],
+        &[b"MESSAGE=started", b"_PID=1", b"UNIT=foo.service"],
+        &[b"MESSAGE=not pid 1", b"_PID=2", b"UNIT=foo.service"],
+        &[b"MESSAGE=about", b"_UID=0", b"OBJECT_SYSTEMD_UNIT=foo.service"],
+        &[b"MESSAGE=other", b"_SYSTEMD_UNIT=bar.service"],
+        &[b"MESSAGE=socket", b"_SYSTEMD_UNIT=foo.socket"],
+        &[b"MESSAGE=sliced", b"_SYSTEMD_UNIT=session-1.scope", b"_SYSTEMD_SLICE=user.slice"],
+        &[b"MESSAGE=user", b"_UID=1000", b"_SYSTEMD_USER_UNIT=app.service"],
+        &[b"MESSAGE=user manager", b"_UID=1000", b"USER_UNIT=app.service"],
+        &[b"MESSAGE=other user", b"_UID=1001", b"_SYSTEMD_USER_UNIT=app.service"],
+    ];
+
+    #[test]
+    fn test_unit_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path, ENTRIES);
+        let journals = MultiJournal::open(&[&path]).unwrap();
+
+        assert_eq!(selected(&journals, unit_matches("foo.service")), vec![0, 1, 3, 5]);
+        assert_eq!(selected(&journals, unit_matches("user.slice")), vec![8]);
+        assert_eq!(selected(&journals, unit_matches("nope.service")), Vec::<u64>::new());
+        assert_eq!(selected(&journals, user_unit_matches("app.service", 1000)), vec![9, 10]);
+        assert_eq!(selected(&journals, Vec::new()), Vec::<u64>::new());
+
+        let mut alternatives = unit_matches("bar.service");
+        alternatives.extend(unit_matches("foo.socket"));
+        assert_eq!(selected(&journals, alternatives), vec![6, 7]);
+    }
+
+    #[test]
+    fn test_unit_globs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path, ENTRIES);
+        let journals = MultiJournal::open(&[&path]).unwrap();
+
+        let units = expand_unit_globs(&journals, &SYSTEM_UNIT_FIELDS, &["foo.*".to_owned()]).unwrap();
+        assert_eq!(units.into_iter().collect::<Vec<_>>(), vec!["foo.service", "foo.socket"]);
+        let units = expand_unit_globs(&journals, &SYSTEM_UNIT_FIELDS, &["*.s[lc]*".to_owned()]).unwrap();
+        assert_eq!(units.into_iter().collect::<Vec<_>>(), vec!["session-1.scope", "user.slice"]);
+        let units = expand_unit_globs(&journals, &USER_UNIT_FIELDS, &["a?p*".to_owned()]).unwrap();
+        assert_eq!(units.into_iter().collect::<Vec<_>>(), vec!["app.service"]);
+        assert!(expand_unit_globs(&journals, &SYSTEM_UNIT_FIELDS, &["x*".to_owned()]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_mangle_unit_name() {
+        assert_eq!(mangle_unit_name("foo"), "foo.service");
+        assert_eq!(mangle_unit_name("foo.socket"), "foo.socket");
+        assert_eq!(mangle_unit_name("foo.bar"), "foo.bar.service");
+        assert_eq!(mangle_unit_name("getty@tty1"), "getty@tty1.service");
+        assert_eq!(mangle_unit_name("foo*"), "foo*");
+        assert_eq!(mangle_unit_name("a b"), "a\\x20b.service");
+        assert_eq!(mangle_unit_name("dev/sda"), "dev-sda.service");
+        assert!(is_glob("foo.[ab]*"));
+        assert!(!is_glob("foo.service"));
+    }
+}
@@ -0,0 +1,80 @@
+#[cfg(test)]
+mod tests {
+    use sdjournal::rotate::*;
+    use sdjournal::time::parse_timespan;
+    use sdjournal::vacuum::*;
+    use sdjournal::writer::*;
+    use std::fs;
+    use pretty_assertions::assert_eq;
+
+    /// Writes 4 archived files of 3 entries each plus an active file
+    fn populate(dir: &std::path::Path) {
+        let params = JournalParams::new(1, 2).unwrap();
+        let limits = RotationLimits { max_file_size: None, max_file_usec: Some(2_500_000) };
+        let mut writer = RotatingWriter::open(dir, "system", params, limits).unwrap();
+        for i in 0..13u64 {
+            writer.append_entry((i + 1) * 1_000_000, i, 2, &[b"MESSAGE=hello"]).unwrap();
+        }
+        writer.close().unwrap();
+    }
+
+    fn journal_files(dir: &std::path::Path) -> usize {
+        fs::read_dir(dir).unwrap().count()
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("500M"), Some(500 * 1024 * 1024));
+        assert_eq!(parse_size("1G"), Some(1 << 30));
+        assert_eq!(parse_size("lots"), None);
+        assert_eq!(parse_timespan("2weeks"), Some(14 * 24 * 3600 * 1_000_000));
+        assert_eq!(parse_timespan("1h 30min"), Some(90 * 60 * 1_000_000));
+        assert_eq!(parse_timespan("90"), Some(90 * 1_000_000));
+        assert_eq!(parse_timespan("10 fortnights"), None);
+    }
+
+    #[test]
+    fn test_vacuum_files_keeps_active() {
+        let dir = tempfile::tempdir().unwrap();
+        populate(dir.path());
+        assert_eq!(journal_files(dir.path()), 5);
+
+        let limits = VacuumLimits { max_files: Some(0), ..Default::default() };
+        let plan = vacuum_plan(dir.path(), &limits).unwrap();
+        assert_eq!(plan.len(), 4);
+        assert!(plan.iter().all(|c| c.path != dir.path().join("system.journal")));
+        let heads: Vec<u64> = plan.iter().map(|c| c.head_seqnum).collect();
+        assert_eq!(heads, vec![1, 4, 7, 10]);
+        // Planning is a dry run
+        assert_eq!(journal_files(dir.path()), 5);
+
+        let limits = VacuumLimits { max_files: Some(3), ..Default::default() };
+        let removed = vacuum(dir.path(), &limits).unwrap();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(removed[0].head_seqnum, 1);
+        assert_eq!(journal_files(dir.path()), 3);
+    }
+
+    #[test]
+    fn test_vacuum_time() {
+        let dir = tempfile::tempdir().unwrap();
+        populate(dir.path());
+
+        // The first two archived files end at 3s and 6s
+        let limits = VacuumLimits { min_realtime: Some(6_500_000), ..Default::default() };
+        let removed = vacuum(dir.path(), &limits).unwrap();
+        let tails: Vec<u64> = removed.iter().map(|c| c.tail_realtime).collect();
+        assert_eq!(tails, vec![3_000_000, 6_000_000]);
+    }
+
+    #[test]
+    fn test_vacuum_size() {
+        let dir = tempfile::tempdir().unwrap();
+        populate(dir.path());
+
+        let limits = VacuumLimits { max_disk_usage: Some(0), ..Default::default() };
+        assert_eq!(vacuum(dir.path(), &limits).unwrap().len(), 4);
+        assert!(dir.path().join("system.journal").exists());
+    }
+}
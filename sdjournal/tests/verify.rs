@@ -0,0 +1,255 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::*;
+    use sdjournal::journal::*;
+    use sdjournal::verify::*;
+    use sdjournal::writer::*;
+    use std::convert::TryInto;
+    use std::fs::File;
+    use std::path::Path;
+    use pretty_assertions::assert_eq;
+
+    /// Writes a journal with ten entries and returns the writer still open
+    fn write_journal(path: &Path) -> JournalWriter<File> {
+        JournalBuilder::new(path).messages(10).writer()
+    }
+
+    #[test]
+    fn test_verify_clean() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path).close().unwrap();
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let report = journal.verify();
+        assert_eq!(report.problems, vec![]);
+        assert_eq!(report.status(), VerifyStatus::Clean);
+        assert_eq!(report.n_objects, journal.header.n_objects);
+        assert_eq!(report.n_entries, 10);
+        assert_eq!(report.n_data, 11);
+        assert_eq!(report.n_fields, 2);
+        assert_eq!(report.n_hash_tables, 2);
+    }
+
+    #[test]
+    fn test_verify_online() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        drop(write_journal(&path));
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let report = journal.verify();
+        assert_eq!(report.status(), VerifyStatus::Repaired);
+        assert_eq!(report.problems.len(), 1);
+        assert_eq!(report.problems[0].severity, Severity::Warning);
+        assert_eq!(report.problems[0].offset, 0);
+    }
+
+    #[test]
+    fn test_verify_reports_every_hash_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path).close().unwrap();
+
+        let mut contents = std::fs::read(&path).unwrap();
+        let mut damaged = Vec::new();
+        for message in &[&b"message 03"[..], &b"message 07"[..]] {
+            let pos = contents.windows(message.len()).position(|w| w == *message).unwrap();
+            contents[pos + 9] = b'x';
+            // The payload starts 64 bytes into the data object
+            damaged.push(pos as u64 - b"MESSAGE=".len() as u64 - 64);
+        }
+        std::fs::write(&path, &contents).unwrap();
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let report = journal.verify();
+        assert_eq!(report.status(), VerifyStatus::Corrupt);
+        assert_eq!(report.problems.iter().map(|p| p.offset).collect::<Vec<_>>(), damaged);
+        assert!(report.problems.iter().all(|p| p.object_type == Some(ObjectType::ObjectData)));
+        assert_eq!(report.n_entries, 10);
+    }
+
+    #[test]
+    fn test_verify_keyed_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let params = JournalParams { keyed_hash: true, ..JournalParams::new(1, 2).unwrap() };
+        JournalBuilder::with_params(&path, &params).messages(10).close();
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        assert_ne!(journal.header.incompatible_flags & HEADER_INCOMPATIBLE_KEYED_HASH, 0);
+        assert_ne!(journal.header.hash_payload(b"PRIORITY=6"), sdjournal::hash::rhash64(b"PRIORITY=6"));
+        let report = journal.verify();
+        assert_eq!(report.problems, vec![]);
+        assert_eq!(report.n_entries, 10);
+
+        let mut contents = std::fs::read(&path).unwrap();
+        let pos = contents.windows(10).position(|w| w == b"message 03").unwrap();
+        contents[pos + 9] = b'x';
+        std::fs::write(&path, &contents).unwrap();
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let report = journal.verify();
+        assert_eq!(report.status(), VerifyStatus::Corrupt);
+        // Keyed files XOR the Jenkins hashes of the payloads into the entry,
+        // so the entry referencing the damaged payload is flagged as well
+        let types = report.problems.iter().map(|p| p.object_type).collect::<Vec<_>>();
+        assert_eq!(types, vec![Some(ObjectType::ObjectData), Some(ObjectType::ObjectEntry)]);
+        assert_eq!(report.problems[0].offset, pos as u64 - b"MESSAGE=".len() as u64 - 64);
+    }
+
+    #[test]
+    fn test_verify_keyed_hash_entry_xor_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let params = JournalParams { keyed_hash: true, ..JournalParams::new(1, 2).unwrap() };
+        JournalBuilder::with_params(&path, &params).messages(3).close();
+
+        // Like journald, keyed files still XOR the Jenkins hashes of the
+        // payloads into the entry, so the same entry matches across files
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let entry = entry_offsets(&journal)[1];
+        let items: [&[u8]; 2] = [b"MESSAGE=message 01", b"PRIORITY=6"];
+        let jenkins = items.iter().fold(0, |acc, p| acc ^ sdjournal::hash::rhash64(p));
+        let siphash = items.iter().fold(0, |acc, p| acc ^ journal.header.hash_payload(p));
+        assert_ne!(jenkins, siphash);
+        assert_eq!(journal.entries().nth(1).unwrap().unwrap().xor_hash, jenkins);
+        assert!(journal.verify().is_clean());
+
+        let mut contents = std::fs::read(&path).unwrap();
+        set_u64(&mut contents, entry as usize + 56, siphash);
+        std::fs::write(&path, &contents).unwrap();
+        let report = Journal::new(File::open(&path).unwrap()).unwrap().verify();
+        assert_eq!(descriptions(&report), vec![(entry, Severity::Error, format!(
+            "XOR hash mismatch: stored {:016x}, computed {:016x}", siphash, jenkins))]);
+    }
+
+    /// Closes a fresh journal, lets `damage` modify its bytes and verifies it
+    fn verify_damaged<F: FnMut(&mut Vec<u8>, &Journal<File>)>(mut damage: F) -> VerifyReport {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        write_journal(&path).close().unwrap();
+
+        let mut contents = std::fs::read(&path).unwrap();
+        damage(&mut contents, &Journal::new(File::open(&path).unwrap()).unwrap());
+        std::fs::write(&path, &contents).unwrap();
+        Journal::new(File::open(&path).unwrap()).unwrap().verify()
+    }
+
+    fn entry_offsets(journal: &Journal<File>) -> Vec<u64> {
+        journal.ea_iter().flat_map(|ea| ea.items).collect()
+    }
+
+    /// Positions of the slots of the main entry array chain, in order
+    fn main_entry_array_slots(journal: &Journal<File>) -> Vec<usize> {
+        let mut slots = Vec::new();
+        let mut offset = journal.header.entry_array_offset;
+        for ea in journal.ea_iter() {
+            let capacity = (ea.object.size - 24) / 8;
+            slots.extend((0..capacity).map(|i| (offset + 24 + 8 * i) as usize));
+            offset = ea.next_entry_array_offset;
+        }
+        slots
+    }
+
+    fn set_u64(contents: &mut [u8], pos: usize, value: u64) {
+        contents[pos..pos + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn descriptions(report: &VerifyReport) -> Vec<(u64, Severity, String)> {
+        report.problems.iter().map(|p| (p.offset, p.severity, p.description.clone())).collect()
+    }
+
+    #[test]
+    fn test_verify_bad_signature() {
+        let report = verify_damaged(|c, _| c[0] = b'X');
+        assert_eq!(report.status(), VerifyStatus::Corrupt);
+        assert_eq!(report.n_objects, 0);
+        assert_eq!(descriptions(&report), vec![(0, Severity::Error, "Invalid signature".to_owned())]);
+    }
+
+    #[test]
+    fn test_verify_stale_counters() {
+        let report = verify_damaged(|c, j| set_u64(c, 144, j.header.n_objects + 1));
+        assert_eq!(report.status(), VerifyStatus::Repaired);
+        assert_eq!(descriptions(&report), vec![(0, Severity::Warning, format!("Header claims {} objects but {} were found",
+                                                                              report.n_objects + 1, report.n_objects))]);
+    }
+
+    #[test]
+    fn test_verify_entry_xor_hash() {
+        let mut entry = 0;
+        let report = verify_damaged(|c, j| {
+            entry = entry_offsets(j)[4];
+            c[entry as usize + 56] ^= 1;
+        });
+        assert_eq!(report.status(), VerifyStatus::Corrupt);
+        assert_eq!(report.problems.len(), 1);
+        assert_eq!(report.problems[0].offset, entry);
+        assert!(report.problems[0].description.starts_with("XOR hash mismatch"));
+    }
+
+    #[test]
+    fn test_verify_entries_out_of_order() {
+        let mut entries = vec![];
+        let report = verify_damaged(|c, j| {
+            entries = entry_offsets(j);
+            let slots = main_entry_array_slots(j);
+            set_u64(c, slots[2], entries[3]);
+            set_u64(c, slots[3], entries[2]);
+        });
+        assert_eq!(report.status(), VerifyStatus::Corrupt);
+        assert_eq!(report.problems.iter().map(|p| p.offset).collect::<Vec<_>>(), vec![entries[2]]);
+    }
+
+    #[test]
+    fn test_verify_unreferenced_entry() {
+        let mut entries = vec![];
+        let report = verify_damaged(|c, j| {
+            entries = entry_offsets(j);
+            set_u64(c, main_entry_array_slots(j)[9], 0);
+        });
+        assert_eq!(report.status(), VerifyStatus::Corrupt);
+        assert!(descriptions(&report).contains(&(entries[9], Severity::Error,
+                                                 "Entry is not listed in the main entry array".to_owned())));
+    }
+
+    #[test]
+    fn test_verify_data_entry_count() {
+        // PRIORITY=6 is shared by all ten entries
+        let priority = |c: &[u8]| c.windows(10).position(|w| w == b"PRIORITY=6").unwrap() - 64;
+
+        let mut data = 0;
+        let report = verify_damaged(|c, _| {
+            data = priority(c);
+            set_u64(c, data + 56, 11);
+        });
+        assert_eq!(descriptions(&report), vec![(data as u64, Severity::Error,
+                                                "Data object claims 11 entries but links to 10".to_owned())]);
+
+        let mut entries = vec![];
+        let report = verify_damaged(|c, j| {
+            entries = entry_offsets(j);
+            data = priority(c);
+            set_u64(c, data + 56, 9);
+        });
+        assert_eq!(descriptions(&report), vec![(entries[9], Severity::Error,
+                                                format!("Entry is not listed by its data object at {:#x}", data))]);
+    }
+
+    #[test]
+    fn test_verify_hash_chain() {
+        let mut data = 0;
+        let report = verify_damaged(|c, j| {
+            // Unlink the first data object from its bucket
+            data = c.windows(10).position(|w| w == b"message 00").unwrap() - 8 - 64;
+            let hash = u64::from_le_bytes(c[data + 16..data + 24].try_into().unwrap());
+            let buckets = j.header.data_hash_table_size / 16;
+            let bucket = (j.header.data_hash_table_offset + hash % buckets * 16) as usize;
+            set_u64(c, bucket, 0);
+            set_u64(c, bucket + 8, 0);
+        });
+        assert_eq!(descriptions(&report), vec![(data as u64, Severity::Error, "Object is not linked into the hash table".to_owned())]);
+    }
+}
@@ -0,0 +1,207 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::*;
+    use sdjournal::journal::*;
+    use sdjournal::writer::*;
+    use std::fs::File;
+    use pretty_assertions::assert_eq;
+
+    fn messages(journal: &Journal<File>) -> Vec<Vec<u8>> {
+        let mut messages = Vec::new();
+        for entry in journal.iter_entries() {
+            for item in entry.items {
+                if let Object::Data(d) = item.item {
+                    if d.payload.starts_with(b"MESSAGE=") {
+                        messages.push(d.payload[8..].to_vec());
+                    }
+                }
+            }
+        }
+        messages
+    }
+
+    #[test]
+    fn test_create_and_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let params = JournalParams::new(1, 2).unwrap();
+        let mut writer = JournalWriter::create(open_rw(&path), &params).unwrap();
+        for i in 0..10u64 {
+            let message = format!("MESSAGE=message {}", i);
+            writer.append_entry(1000 + i, 10 + i, 2, &[message.as_bytes(), b"PRIORITY=6"]).unwrap();
+        }
+        writer.close().unwrap();
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(journal.header.state, JournalState::Offline);
+        assert_eq!(journal.header.n_entries, 10);
+        assert_eq!(journal.header.head_entry_seqnum, 1);
+        assert_eq!(journal.header.tail_entry_seqnum, 10);
+        assert_eq!(journal.header.head_entry_realtime, 1000);
+        assert_eq!(journal.header.tail_entry_realtime, 1009);
+        // 10 messages + 1 shared priority
+        assert_eq!(journal.header.n_data, 11);
+        assert_eq!(journal.header.n_fields, 2);
+        assert_eq!(journal.obj_iter().count() as u64, journal.header.n_objects);
+        assert_eq!(messages(&journal).len(), 10);
+        assert_eq!(messages(&journal)[3], b"message 3".to_vec());
+        assert!(journal.verify().is_clean());
+
+        for obj in journal.obj_iter() {
+            if let Object::Data(d) = obj {
+                if d.payload == b"PRIORITY=6" {
+                    assert_eq!(d.n_entries, 10);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_hash_table_items() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let mut writer = JournalWriter::create(open_rw(&path), &JournalParams::new(1, 2).unwrap()).unwrap();
+        writer.append_entry(1000, 10, 2, &[b"MESSAGE=hello"]).unwrap();
+        writer.close().unwrap();
+
+        // The items follow the 16 byte object header, one per bucket
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        let header = &journal.header;
+        let mut file = &journal.file;
+        for (offset, size) in [(header.data_hash_table_offset, header.data_hash_table_size),
+                               (header.field_hash_table_offset, header.field_hash_table_size)] {
+            match get_obj_at_offset(&mut file, offset - OBJECT_HEADER_SZ).unwrap() {
+                Object::HashTable(table) => {
+                    assert_eq!(table.items.len() as u64, size / HASH_ITEM_SZ);
+                    assert_eq!(table.items.iter().filter(|item| item.hash_head_offset != 0).count(), 1);
+                }
+                other => panic!("not a hash table: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_append_to_offline_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let params = JournalParams::new(1, 2).unwrap();
+        let mut writer = JournalWriter::create(open_rw(&path), &params).unwrap();
+        writer.append_entry(1000, 10, 2, &[b"MESSAGE=first", b"PRIORITY=6"]).unwrap();
+        writer.close().unwrap();
+
+        let mut writer = JournalWriter::open(open_rw(&path)).unwrap();
+        assert_eq!(writer.header.state, JournalState::Online);
+        assert_eq!(writer.next_seqnum(), 2);
+        for i in 0..20u64 {
+            writer.append_entry(2000 + i, 20 + i, 2, &[b"MESSAGE=again", b"PRIORITY=6"]).unwrap();
+        }
+        writer.close().unwrap();
+
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(journal.header.state, JournalState::Offline);
+        assert_eq!(journal.header.n_entries, 21);
+        assert_eq!(journal.header.tail_entry_seqnum, 21);
+        // PRIORITY=6 was reused rather than written again
+        assert_eq!(journal.header.n_data, 3);
+        assert_eq!(journal.header.n_fields, 2);
+        assert_eq!(journal.iter_entries().count(), 21);
+        assert_eq!(messages(&journal)[0], b"first".to_vec());
+        assert_eq!(messages(&journal)[20], b"again".to_vec());
+    }
+
+    /// The last array of the main entry array chain and the entries in it
+    fn tail_entry_array(journal: &Journal<File>) -> (u64, Vec<u64>) {
+        let mut file = &journal.file;
+        let mut offset = journal.header.entry_array_offset;
+        loop {
+            match get_obj_at_offset(&mut file, offset).unwrap() {
+                Object::EntryArray(a) if a.next_entry_array_offset != 0 => offset = a.next_entry_array_offset,
+                Object::EntryArray(a) => return (offset, a.items.into_iter().filter(|&item| item != 0).collect()),
+                other => panic!("not an entry array: {:?}", other),
+            }
+        }
+    }
+
+    /// Checks the fields systemd 246 to 254 added to the header against the file
+    fn assert_newer_header_fields(path: &std::path::Path, data_depth: u64) {
+        let journal = Journal::new(File::open(path).unwrap()).unwrap();
+        let (array, entries) = tail_entry_array(&journal);
+        assert_eq!(journal.header.tail_entry_array_offset, Some(array as u32));
+        assert_eq!(journal.header.tail_entry_array_n_entries, Some(entries.len() as u32));
+        assert_eq!(journal.header.tail_entry_offset, entries.last().copied());
+        assert_eq!(journal.header.data_hash_chain_depth, Some(data_depth));
+        assert_eq!(journal.header.field_hash_chain_depth, Some(2));
+        assert!(journal.verify().is_clean());
+    }
+
+    #[test]
+    fn test_newer_header_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        // Single buckets chain every payload and every field name
+        let params = JournalParams {
+            header_size: MAX_HEADER_SZ,
+            data_hash_table_buckets: 1,
+            field_hash_table_buckets: 1,
+            ..JournalParams::new(1, 2).unwrap()
+        };
+        JournalBuilder::with_params(&path, &params).messages(10).close();
+        // 10 messages + 1 shared priority, the last 6 entries in the second array
+        assert_newer_header_fields(&path, 11);
+        assert_eq!(tail_entry_array(&Journal::new(File::open(&path).unwrap()).unwrap()).1.len(), 6);
+
+        let mut writer = JournalWriter::open(open_rw(&path)).unwrap();
+        for i in 0..10u64 {
+            let message = format!("MESSAGE=again {}", i);
+            writer.append_entry(2000 + i, 20 + i, 2, &[message.as_bytes(), b"PRIORITY=6"]).unwrap();
+        }
+        writer.close().unwrap();
+        assert_newer_header_fields(&path, 21);
+        let journal = Journal::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(journal.header.header_size, MAX_HEADER_SZ);
+        assert_eq!(journal.iter_entries().count(), 20);
+        // Arrays of 4, 8 and 16 entries
+        assert_eq!(tail_entry_array(&journal).1.len(), 8);
+    }
+
+    #[test]
+    fn test_unsupported_header_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let params = JournalParams { header_size: HEADER_SZ + 8, ..JournalParams::new(1, 2).unwrap() };
+        assert!(JournalWriter::create(open_rw(&path), &params).is_err());
+
+        // Fields after the ones the writer knows would go stale
+        let params = JournalParams { header_size: MAX_HEADER_SZ, ..JournalParams::new(1, 2).unwrap() };
+        JournalBuilder::with_params(&path, &params).messages(1).close();
+        let mut contents = std::fs::read(&path).unwrap();
+        contents[88..96].copy_from_slice(&(MAX_HEADER_SZ + 8).to_le_bytes());
+        std::fs::write(&path, &contents).unwrap();
+        assert!(JournalWriter::open(open_rw(&path)).is_err());
+    }
+
+    #[test]
+    fn test_open_refuses_online_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let params = JournalParams::new(1, 2).unwrap();
+        let mut writer = JournalWriter::create(open_rw(&path), &params).unwrap();
+        writer.append_entry(1000, 10, 2, &[b"MESSAGE=first"]).unwrap();
+        drop(writer);
+
+        assert!(JournalWriter::open(open_rw(&path)).is_err());
+    }
+
+    #[test]
+    fn test_append_entry_rejects_bad_items() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.journal");
+        let params = JournalParams::new(1, 2).unwrap();
+        let mut writer = JournalWriter::create(open_rw(&path), &params).unwrap();
+        assert!(writer.append_entry(1000, 10, 2, &[b"no equals sign"]).is_err());
+        assert!(writer.append_entry(1000, 10, 2, &[b"=value"]).is_err());
+        assert!(writer.append_entry(1000, 10, 2, &[]).is_err());
+    }
+}